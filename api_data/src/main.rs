@@ -0,0 +1,191 @@
+use axum::{extract::State, http::StatusCode, response::Json, routing::{get, post}, Router};
+use dotenvy::dotenv;
+use libsql::{params, Builder, Connection, Database};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+use std::sync::Arc;
+
+// 操作できるテーブル。mcp_3 , mcp_4 の item_price と同じスキーマ
+const CONTENT_LIST: &[&str] = &["item_price"];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Item {
+    id: i64,
+    data: String,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateParams {
+    content: String,
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    content: String,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetParams {
+    content: String,
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateParams {
+    content: String,
+    id: i64,
+    data: String,
+}
+
+type ApiResult = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+fn api_error(status: StatusCode, message: String) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "ret": "NG", "message": message })))
+}
+
+fn db_error(e: libsql::Error) -> (StatusCode, Json<Value>) {
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e))
+}
+
+/**
+* content (テーブル名) と data (JSON 文字列) を検証する。
+*/
+fn check_content(content: &str) -> Result<(), (StatusCode, Json<Value>)> {
+    if CONTENT_LIST.contains(&content) {
+        Ok(())
+    } else {
+        Err(api_error(StatusCode::BAD_REQUEST, format!("Invalid content: {}", content)))
+    }
+}
+
+fn check_data(data: &str) -> Result<(), (StatusCode, Json<Value>)> {
+    serde_json::from_str::<Value>(data)
+        .map(|_| ())
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("Invalid data JSON: {}", e)))
+}
+
+async fn select_item(conn: &Connection, content: &str, id: i64) -> Result<Option<Item>, libsql::Error> {
+    let sql = format!("SELECT id, data ,created_at, updated_at FROM {} WHERE id = ?1", content);
+    let mut rows = conn.query(&sql, params![id]).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(Item {
+            id: row.get(0)?,
+            data: row.get(1)?,
+            created_at: row.get(2)?,
+            updated_at: row.get(3)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/**
+* TURSO_AUTH_TOKEN が未設定の場合は、TURSO_DATABASE_URL をローカルの SQLite ファイルとして開く。
+*/
+async fn open_database() -> anyhow::Result<Database> {
+    let url = env::var("TURSO_DATABASE_URL").unwrap_or("api_data.db".to_string());
+    let db = match env::var("TURSO_AUTH_TOKEN") {
+        Ok(token) if !token.is_empty() => Builder::new_remote(url, token).build().await?,
+        _ => Builder::new_local(url.trim_start_matches("file:")).build().await?,
+    };
+    Ok(db)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let db = open_database().await?;
+    let conn = db.connect()?;
+    conn.execute_batch(include_str!("../scheme.sql")).await?;
+
+    let app_state = Arc::new(db);
+
+    let app = Router::new()
+        .route("/", get(root))
+        .route("/api/data/create", post(data_create))
+        .route("/api/data/list", post(data_list))
+        .route("/api/data/get", post(data_get))
+        .route("/api/data/update", post(data_update))
+        .route("/api/data/delete", post(data_delete))
+        .with_state(app_state);
+
+    let port = env::var("PORT").unwrap_or("8787".to_string());
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+    eprintln!("API Server started. port={}", port);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn root() -> String {
+    String::from("root\n")
+}
+
+async fn data_create(State(db): State<Arc<Database>>, Json(payload): Json<CreateParams>) -> ApiResult {
+    check_content(&payload.content)?;
+    check_data(&payload.data)?;
+    let conn = db.connect().map_err(db_error)?;
+    let sql = format!("INSERT INTO {} (data) VALUES (?1)", payload.content);
+    conn.execute(&sql, params![payload.data.as_str()]).await.map_err(db_error)?;
+    let id = conn.last_insert_rowid();
+    Ok(Json(json!({ "ret": "OK", "id": id })))
+}
+
+async fn data_list(State(db): State<Arc<Database>>, Json(payload): Json<ListParams>) -> ApiResult {
+    check_content(&payload.content)?;
+    let conn = db.connect().map_err(db_error)?;
+    let sql = format!(
+        "SELECT id, data ,created_at, updated_at FROM {} ORDER BY created_at DESC LIMIT ?1",
+        payload.content
+    );
+    let mut rows = conn.query(&sql, params![payload.limit.unwrap_or(100)]).await.map_err(db_error)?;
+    let mut items: Vec<Item> = Vec::new();
+    while let Some(row) = rows.next().await.map_err(db_error)? {
+        items.push(Item {
+            id: row.get(0).map_err(db_error)?,
+            data: row.get(1).map_err(db_error)?,
+            created_at: row.get(2).map_err(db_error)?,
+            updated_at: row.get(3).map_err(db_error)?,
+        });
+    }
+    Ok(Json(json!({ "ret": "OK", "data": items })))
+}
+
+async fn data_get(State(db): State<Arc<Database>>, Json(payload): Json<GetParams>) -> ApiResult {
+    check_content(&payload.content)?;
+    let conn = db.connect().map_err(db_error)?;
+    match select_item(&conn, &payload.content, payload.id).await.map_err(db_error)? {
+        Some(item) => Ok(Json(json!({ "ret": "OK", "data": item }))),
+        None => Err(api_error(StatusCode::NOT_FOUND, format!("Not found, id={}", payload.id))),
+    }
+}
+
+async fn data_update(State(db): State<Arc<Database>>, Json(payload): Json<UpdateParams>) -> ApiResult {
+    check_content(&payload.content)?;
+    check_data(&payload.data)?;
+    let conn = db.connect().map_err(db_error)?;
+    let sql = format!(
+        "UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        payload.content
+    );
+    let count = conn.execute(&sql, params![payload.data.as_str(), payload.id]).await.map_err(db_error)?;
+    if count == 0 {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Not found, id={}", payload.id)));
+    }
+    let item = select_item(&conn, &payload.content, payload.id).await.map_err(db_error)?;
+    Ok(Json(json!({ "ret": "OK", "data": item })))
+}
+
+async fn data_delete(State(db): State<Arc<Database>>, Json(payload): Json<GetParams>) -> ApiResult {
+    check_content(&payload.content)?;
+    let conn = db.connect().map_err(db_error)?;
+    let sql = format!("DELETE FROM {} WHERE id = ?1", payload.content);
+    let count = conn.execute(&sql, params![payload.id]).await.map_err(db_error)?;
+    if count == 0 {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Not found, id={}", payload.id)));
+    }
+    Ok(Json(json!({ "ret": "OK", "id": payload.id })))
+}
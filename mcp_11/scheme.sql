@@ -7,3 +7,28 @@ CREATE TABLE IF NOT EXISTS test (
   created_at TIMESTAMP NOT NULL DEFAULT now(),
   updated_at TIMESTAMP NOT NULL DEFAULT now()
 );
+
+-- data_* (mcp_9 と同じ collection)。data は JSONB , GIN index で @> の条件に使う
+CREATE TABLE IF NOT EXISTS todo (
+  id BIGSERIAL PRIMARY KEY,
+  data JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT now(),
+  updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS todo_data_gin ON todo USING GIN (data jsonb_path_ops);
+
+CREATE TABLE IF NOT EXISTS er_chart (
+  id BIGSERIAL PRIMARY KEY,
+  data JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT now(),
+  updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS er_chart_data_gin ON er_chart USING GIN (data jsonb_path_ops);
+
+-- md_data の data は Markdown のため TEXT (mcp_9 と同じ)
+CREATE TABLE IF NOT EXISTS md_data (
+  id BIGSERIAL PRIMARY KEY,
+  data TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT now(),
+  updated_at TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
 use std::env;
 use std::io::{self, BufRead, Write};
 use dotenvy::dotenv;
@@ -30,6 +32,78 @@ pub struct JsonRpcError {
     message: String,
 }
 
+/**
+* エラーの response。
+*
+* @param request_id
+* @param code
+* @param message
+*
+* @return
+*/
+pub fn error_response(request_id: Option<Value>, code: i32, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+        }),
+    }
+}
+
+/**
+* text を1つ返す response。
+*
+* @param request_id
+* @param text
+*
+* @return
+*/
+pub fn text_response(request_id: Option<Value>, text: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": text
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
+/**
+* tools/call の arguments を読み込む。arguments が無い場合は Tool not found 、読み込めない場合は Invalid parameters の response を返す。
+*
+* @param params
+* @param request_id
+*
+* @return
+*/
+pub fn parse_arguments<T: for<'de> Deserialize<'de>>(params: &Value, request_id: &Option<Value>) -> Result<T, JsonRpcResponse> {
+    let Some(arguments) = params.get("arguments") else {
+        return Err(error_response(request_id.clone(), -32601, "Tool not found".to_string()));
+    };
+    serde_json::from_value::<T>(arguments.clone())
+        .map_err(|e| error_response(request_id.clone(), -32602, format!("Invalid parameters: {}", e)))
+}
+
+/**
+* Postgres に接続する。
+*
+* @return
+*/
+pub async fn connect() -> PgPool {
+    let con_str = POSTGRES_CONNECTION_STR.to_string();
+    PgPoolOptions::new().max_connections(5)
+    .connect(&con_str).await.expect("Failed to create pool")
+}
+
 #[derive(Debug, Deserialize)]
 struct AddTenParams {
     value: i32,
@@ -54,9 +128,251 @@ fn purchase(product_name: String, price: i32) -> String {
 }
 
 mod mod_test;
+mod mod_data;
+mod mod_collection;
+mod mod_query;
+mod mod_er;
+
+/**
+* test_* の tools/list。table は scheme.sql の test
+*
+* @return
+*/
+fn test_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "test_create",
+            "description": "test にデータを登録します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "description": "タイトル" },
+                    "content": { "type": "string", "description": "内容" }
+                },
+                "required": ["title", "content"]
+            }
+        }),
+        json!({
+            "name": "test_list",
+            "description": "test のデータを新しい順に 10 件表示します。各行に etag を返します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": { "type": "string", "enum": ["test"], "description": "table 名" }
+                },
+                "required": ["content"]
+            }
+        }),
+        json!({
+            "name": "test_update",
+            "description": "test の id のデータを更新します。etag を指定した場合は、一致する場合のみ更新します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "title": { "type": "string", "description": "タイトル" },
+                    "content": { "type": "string", "description": "内容" },
+                    "etag": { "type": "string", "description": "test_list の etag" }
+                },
+                "required": ["id", "title", "content"]
+            }
+        }),
+        json!({
+            "name": "test_delete",
+            "description": "test の id のデータを削除します。etag を指定した場合は、一致する場合のみ削除します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": { "type": "string", "enum": ["test"], "description": "table 名" },
+                    "id": { "type": "integer" },
+                    "etag": { "type": "string", "description": "test_list の etag" }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+    ]
+}
+
+/**
+* data_* の tools/list。mcp_9 (Turso) と同じ tool の名前 , 引数とする。
+*
+* @return
+*/
+fn data_tools() -> Vec<Value> {
+    let collections = mod_collection::declared_collections();
+    let content = json!({
+        "type": "string",
+        "enum": collections.iter().map(|collection| collection.name.clone()).collect::<Vec<String>>(),
+        "description": format!("collection 名 ({})", collections.iter()
+            .map(|collection| format!("{}: {}", collection.name, collection.description))
+            .collect::<Vec<String>>().join(" , "))
+    });
+    vec![
+        json!({
+            "name": "data_create",
+            "description": "collection にデータを登録します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "data": {
+                        "type": "string",
+                        "description": "登録するデータ (JSON 文字列 , md_data は Markdown)"
+                    }
+                },
+                "required": ["content", "data"]
+            }
+        }),
+        json!({
+            "name": "data_list",
+            "description": "collection のデータを表示します。JSON パスの条件、並び順、項目の指定ができます。既定は新しい順に 10 件。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "filter": {
+                        "type": "array",
+                        "description": "条件 (すべて満たすデータ)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "JSON パス (例: $.status)"
+                                },
+                                "op": {
+                                    "type": "string",
+                                    "enum": ["eq", "ne", "gt", "gte", "lt", "lte", "in", "contains", "exists"],
+                                    "description": "比較 (既定 eq)。contains: 配列の要素 / 文字列の部分一致 , exists: value が false の場合は項目が無いデータ"
+                                },
+                                "value": {
+                                    "description": "比較する値。in の場合は配列"
+                                }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    "sort": {
+                        "type": "object",
+                        "description": "並び順 (既定 created_at の降順)",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "JSON パス、または id , created_at , updated_at"
+                            },
+                            "order": {
+                                "type": "string",
+                                "enum": ["asc", "desc"],
+                                "description": "既定 desc"
+                            }
+                        },
+                        "required": ["path"]
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "表示する項目の JSON パス。省略時は data 全体"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "件数 (既定 10 , 最大 100)"
+                    },
+                    "offset": {
+                        "type": "number",
+                        "description": "開始位置"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "前の結果の next_cursor。次のページを表示します"
+                    }
+                },
+                "required": ["content"]
+            }
+        }),
+        json!({
+            "name": "data_getone",
+            "description": "collection の id のデータを表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+        json!({
+            "name": "data_update",
+            "description": "collection の id のデータを更新します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は更新せず、現在のデータを返します"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "更新するデータ (JSON 文字列)"
+                    }
+                },
+                "required": ["content", "id", "data"]
+            }
+        }),
+        json!({
+            "name": "data_delete",
+            "description": "collection の id のデータを削除します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は更新せず、現在のデータを返します"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+    ]
+}
+
 
 async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
     match request.method.as_str() {
+        "initialize" => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: request.id,
+            result: Some(json!({
+                "protocolVersion": "2024-11-05",
+                "serverInfo": {
+                    "name": "rust-data-server",
+                    "version": "1.0.0"
+                },
+                "capabilities": {
+                    "tools": {}
+                }
+            })),
+            error: None,
+        },
+        "tools/list" => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: request.id,
+            result: Some(json!({ "tools": test_tools().into_iter().chain(data_tools()).collect::<Vec<Value>>() })),
+            error: None,
+        },
         "tools/call" => {
             if let Some(params) = request.params {
                 if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
@@ -68,6 +384,16 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                         mod_test::test_delete_handler(params, request.id).await
                     } else if tool_name == "test_update" {
                         mod_test::test_update_handler(params, request.id).await
+                    } else if tool_name == "data_create" {
+                        mod_data::data_create_handler(params, request.id).await
+                    } else if tool_name == "data_list" {
+                        mod_data::data_list_handler(params, request.id).await
+                    } else if tool_name == "data_getone" {
+                        mod_data::data_getone_handler(params, request.id).await
+                    } else if tool_name == "data_update" {
+                        mod_data::data_update_handler(params, request.id).await
+                    } else if tool_name == "data_delete" {
+                        mod_data::data_delete_handler(params, request.id).await
                     } else {
                         JsonRpcResponse {
                             jsonrpc: "2.0".to_string(),
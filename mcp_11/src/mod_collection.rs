@@ -0,0 +1,162 @@
+use serde_json::{json, Value};
+
+/// JSON Schema の後に行う data の検証
+pub type DataCheck = fn(&Value) -> Result<(), String>;
+
+/// data_* で扱う collection (table)。schema は data の JSON Schema
+/// mcp_9 (Turso) と同じ collection 名 , JSON Schema , check とする
+/// json は data 列が JSONB の collection , false は TEXT (Markdown など JSON でない data)
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub name: String,
+    pub description: String,
+    pub schema: Option<Value>,
+    pub json: bool,
+    pub check: Option<DataCheck>,
+}
+
+impl Collection {
+    /// INSERT , UPDATE の data の placeholder ($n)
+    pub fn data_placeholder(&self, index: usize) -> String {
+        if self.json { format!("${}::jsonb", index) } else { format!("${}", index) }
+    }
+}
+
+/**
+* scheme.sql で作成する collection の一覧。
+*
+* @return
+*/
+pub fn declared_collections() -> Vec<Collection> {
+    vec![
+        Collection {
+            name: "todo".to_string(),
+            description: "TODO (title , body)".to_string(),
+            schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "body": { "type": "string" }
+                },
+                "required": ["title"]
+            })),
+            json: true,
+            check: None,
+        },
+        Collection {
+            name: "er_chart".to_string(),
+            description: "ER 図 (entities , relations)".to_string(),
+            schema: Some(super::mod_er::chart_schema()),
+            json: true,
+            check: Some(super::mod_er::check_chart_value),
+        },
+        Collection {
+            name: "md_data".to_string(),
+            description: "Markdown".to_string(),
+            schema: None,
+            json: false,
+            check: None,
+        },
+    ]
+}
+
+/**
+* 名前から collection を探す。未登録の名前はエラーとする。
+*
+* @param name
+*
+* @return
+*/
+pub fn find_collection(name: &str) -> Result<Collection, String> {
+    let collections = declared_collections();
+    let names: Vec<&str> = collections.iter().map(|collection| collection.name.as_str()).collect();
+    let message = format!("unknown collection: {} ({})", name, names.join(" , "));
+    collections.into_iter()
+        .find(|collection| collection.name == name)
+        .ok_or(message)
+}
+
+/**
+* data を検証する。data 列が JSONB の collection は JSON のみ、TEXT の collection は検証しない。
+*
+* @param collection
+* @param data
+*
+* @return
+*/
+pub fn validate_data(collection: &Collection, data: &str) -> Result<(), String> {
+    if !collection.json {
+        return Ok(());
+    }
+    let value: Value = serde_json::from_str(data).map_err(|e| format!("data is not JSON: {}", e))?;
+    if let Some(schema) = &collection.schema {
+        validate_value(schema, &value, "$")?;
+    }
+    match collection.check {
+        Some(check) => check(&value),
+        None => Ok(()),
+    }
+}
+
+fn type_matches(type_name: &str, value: &Value) -> bool {
+    match type_name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/**
+* JSON Schema の type , enum , required , properties , additionalProperties , items で検証する。
+*
+* @param schema
+* @param value
+* @param path エラー表示用の JSON パス
+*
+* @return
+*/
+fn validate_value(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    match &schema["type"] {
+        Value::String(type_name) if !type_matches(type_name, value) => {
+            return Err(format!("{}: expected {}", path, type_name));
+        }
+        Value::Array(type_names) if !type_names.iter().any(|t| type_matches(t.as_str().unwrap_or(""), value)) => {
+            return Err(format!("{}: expected {}", path, Value::Array(type_names.clone())));
+        }
+        _ => {}
+    }
+    if let Some(values) = schema["enum"].as_array()
+        && !values.contains(value) {
+        return Err(format!("{}: must be one of {}", path, Value::Array(values.clone())));
+    }
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for key in required.iter().filter_map(|key| key.as_str()) {
+                if !object.contains_key(key) {
+                    return Err(format!("{}.{}: required", path, key));
+                }
+            }
+        }
+        let properties = schema["properties"].as_object();
+        for (key, child) in object {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(child_schema) => validate_value(child_schema, child, &format!("{}.{}", path, key))?,
+                None if schema["additionalProperties"] == json!(false) => {
+                    return Err(format!("{}.{}: unknown property", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_value(item_schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+
+use super::mod_query::SqlValue;
+use super::{connect, error_response, parse_arguments, text_response};
+
+#[derive(Debug, Deserialize,Serialize)]
+struct ItemParams {
+    content: String,
+    data: String,
+}
+#[derive(Debug, Deserialize)]
+struct ItemListParams {
+    content: String,
+    #[serde(flatten)]
+    query: super::mod_query::ListQuery,
+}
+#[derive(Debug, Deserialize,Serialize)]
+struct ItemGetParams {
+    content: String,
+    id: i32,
+}
+#[derive(Debug, Deserialize,Serialize)]
+struct ItemDeleteParams {
+    content: String,
+    id: i32,
+    etag: Option<String>,
+}
+#[derive(Debug, Deserialize,Serialize)]
+struct UpdateParams {
+    content: String,
+    data: String,
+    id: i32,
+    etag: Option<String>,
+}
+
+/// data_* の1行。mcp_9 (Turso) の data_* と同じ形式
+#[derive(Debug, Serialize)]
+pub struct Item {
+    id: i64,
+    data: String,
+    created_at: String,
+    updated_at: String,
+    etag: String,
+    // updated_at (マイクロ秒まで)。更新の競合の判定に使う
+    #[serde(skip)]
+    version: String,
+}
+
+// 更新の競合 (etag の不一致)
+const CONFLICT_CODE: i32 = -32009;
+
+// id , data , created_at , updated_at , version の列
+const ITEM_COLUMNS: &str = "id, data::text AS data,
+    to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+    to_char(updated_at, 'YYYY-MM-DD HH24:MI:SS') AS updated_at,
+    updated_at::text AS version";
+
+impl Item {
+    fn from_row(row: &sqlx::postgres::PgRow) -> Self {
+        let data: String = row.get("data");
+        let version: String = row.get("version");
+        Item {
+            id: row.get("id"),
+            etag: item_etag(&data, &version),
+            data,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            version,
+        }
+    }
+}
+
+/**
+* updated_at と data から etag を作る (FNV-1a 64bit)。
+*
+* @param data
+* @param version
+*
+* @return
+*/
+fn item_etag(data: &str, version: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in version.bytes().chain([b'\n']).chain(data.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/**
+* id のデータを読み込む。
+*
+* @param pool
+* @param table 登録済みの collection 名
+* @param id
+*
+* @return
+*/
+async fn find_item(pool: &PgPool, table: &str, id: i32) -> Option<Item> {
+    let sql = format!("SELECT {} FROM {} WHERE id = $1", ITEM_COLUMNS, table);
+    let row = sqlx::query(&sql)
+        .bind(id as i64)
+        .fetch_optional(pool)
+        .await.unwrap()?;
+    Some(Item::from_row(&row))
+}
+
+/**
+* etag が一致しない場合のエラー。現在のデータを含める。
+*/
+fn conflict_response(request_id: Option<Value>, etag: &str, current: Option<Item>) -> super::JsonRpcResponse {
+    let message = match current {
+        Some(current) => format!("Conflict, etag={} , current={}", etag, serde_json::to_string(&current).expect("JSON convert error")),
+        None => format!("Conflict, etag={} , current=deleted", etag),
+    };
+    error_response(request_id, CONFLICT_CODE, message)
+}
+
+/**
+* data_create: collection にデータを登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_create_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let item_params = match parse_arguments::<ItemParams>(&params, &request_id) {
+        Ok(item_params) => item_params,
+        Err(response) => return response,
+    };
+    let collection = match super::mod_collection::find_collection(&item_params.content) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    if let Err(e) = super::mod_collection::validate_data(&collection, &item_params.data) {
+        return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+    }
+    let pool = connect().await;
+    let sql = format!("INSERT INTO {} (data) VALUES ({})", collection.name, collection.data_placeholder(1));
+    sqlx::query(&sql)
+        .bind(&item_params.data)
+        .execute(&pool)
+        .await.unwrap();
+    text_response(request_id, "OK".to_string())
+}
+
+/**
+* data_getone: collection の id のデータを表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_getone_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let item_get_params = match parse_arguments::<ItemGetParams>(&params, &request_id) {
+        Ok(item_get_params) => item_get_params,
+        Err(response) => return response,
+    };
+    let collection = match super::mod_collection::find_collection(&item_get_params.content) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    let pool = connect().await;
+    let todos: Vec<Item> = find_item(&pool, &collection.name, item_get_params.id).await.into_iter().collect();
+    text_response(request_id, serde_json::to_string(&todos).expect("JSON convert error"))
+}
+
+/**
+* data_list: JSON パスの条件 (@> , #>) , 並び順 , 項目 , cursor で collection のデータを表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_list_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let item_list_params = match parse_arguments::<ItemListParams>(&params, &request_id) {
+        Ok(item_list_params) => item_list_params,
+        Err(response) => return response,
+    };
+    let collection = match super::mod_collection::find_collection(&item_list_params.content) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    let query = &item_list_params.query;
+    // TEXT の collection は JSON パスを使えない
+    if !collection.json && super::mod_query::uses_json_path(query) {
+        return error_response(request_id, -32602,
+            format!("Invalid parameters, {} is not JSON (filter , JSON path sort , fields are not available)", collection.name));
+    }
+    let list_sql = match super::mod_query::build_list_sql(&collection.name, query) {
+        Ok(list_sql) => list_sql,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    eprintln!("sql={}", list_sql.sql);
+    let pool = connect().await;
+    let rows = match super::mod_query::bind_values(sqlx::query(&list_sql.sql), &list_sql.values).fetch_all(&pool).await {
+        Ok(rows) => rows,
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    };
+    let mut todos: Vec<Item> = Vec::new();
+    let mut next_cursor: Option<String> = None;
+    for row in rows.iter() {
+        if todos.len() as i64 == list_sql.limit {
+            // limit + 1 件目があれば、最後の行から次のページの cursor を作る
+            let last = &rows[todos.len() - 1];
+            next_cursor = Some(super::mod_query::make_cursor(list_sql.sort_kind, last.get("sort_value"), last.get("id")));
+            break;
+        }
+        // etag は data 全体から作る
+        let mut item = Item::from_row(row);
+        if !query.fields.is_empty() {
+            item.data = super::mod_query::project_fields(&item.data, &query.fields);
+        }
+        todos.push(item);
+    }
+    let mut content_items = vec![json!({
+        "type": "text",
+        "text": serde_json::to_string(&todos).expect("JSON convert error")
+    })];
+    if let Some(next_cursor) = next_cursor {
+        content_items.push(json!({
+            "type": "text",
+            "text": format!("next_cursor={}", next_cursor)
+        }));
+    }
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": content_items
+        })),
+        error: None,
+    }
+}
+
+/**
+* data_update: collection の id のデータを更新する。etag を指定した場合は、一致する場合のみ更新する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_update_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let item_params = match parse_arguments::<UpdateParams>(&params, &request_id) {
+        Ok(item_params) => item_params,
+        Err(response) => return response,
+    };
+    let collection = match super::mod_collection::find_collection(&item_params.content) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    if let Err(e) = super::mod_collection::validate_data(&collection, &item_params.data) {
+        return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+    }
+    let pool = connect().await;
+    let Some(current) = find_item(&pool, &collection.name, item_params.id).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", item_params.id));
+    };
+    if let Some(etag) = &item_params.etag
+        && *etag != current.etag {
+        return conflict_response(request_id, etag, Some(current));
+    }
+    // 読み込み後に他の更新があった場合は、更新しない
+    let sql = format!("UPDATE {} SET data = {}, updated_at = now() WHERE id = $2 AND updated_at::text = $3",
+        collection.name, collection.data_placeholder(1));
+    let result = sqlx::query(&sql)
+        .bind(&item_params.data)
+        .bind(item_params.id as i64)
+        .bind(&current.version)
+        .execute(&pool)
+        .await.unwrap();
+    let updated = find_item(&pool, &collection.name, item_params.id).await;
+    if result.rows_affected() == 0 {
+        return conflict_response(request_id, &current.etag, updated);
+    }
+    text_response(request_id, format!("OK, etag={}", updated.map(|item| item.etag).unwrap_or_default()))
+}
+
+/**
+* data_delete: collection の id のデータを削除する。etag を指定した場合は、一致する場合のみ削除する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_delete_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let item_delete_params = match parse_arguments::<ItemDeleteParams>(&params, &request_id) {
+        Ok(item_delete_params) => item_delete_params,
+        Err(response) => return response,
+    };
+    let collection = match super::mod_collection::find_collection(&item_delete_params.content) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    let id_value = item_delete_params.id;
+    let pool = connect().await;
+    let Some(current) = find_item(&pool, &collection.name, id_value).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", id_value));
+    };
+    let mut sql = format!("DELETE FROM {} WHERE id = $1", collection.name);
+    let mut values = vec![SqlValue::Int(id_value as i64)];
+    if let Some(etag) = &item_delete_params.etag {
+        if *etag != current.etag {
+            return conflict_response(request_id, etag, Some(current));
+        }
+        // 読み込み後に更新された場合は削除しない
+        sql.push_str(" AND updated_at::text = $2");
+        values.push(SqlValue::Text(current.version.clone()));
+    }
+    let result = super::mod_query::bind_values(sqlx::query(&sql), &values)
+        .execute(&pool)
+        .await.unwrap();
+    if result.rows_affected() == 0
+        && let Some(etag) = &item_delete_params.etag {
+        return conflict_response(request_id, etag, find_item(&pool, &collection.name, id_value).await);
+    }
+    text_response(request_id, format!("Complete delete, id={}", id_value))
+}
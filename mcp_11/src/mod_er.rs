@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+// er_chart の ER 図のモデルと検証。mcp_9 の mod_er と同じ内容とする (mcp_9 と同じ data を受け付ける)
+
+/// er_chart の data。entities と relations の ER 図
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ErChart {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub entities: Vec<Entity>,
+    #[serde(default)]
+    pub relations: Vec<Relation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Entity {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub columns: Vec<Column>,
+}
+
+/// 列。references は参照先の entity.column
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub primary_key: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub not_null: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unique: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub references: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Text,
+    Real,
+    Boolean,
+    Timestamp,
+    Date,
+    Json,
+    Blob,
+}
+
+const COLUMN_TYPES: [&str; 8] = ["integer", "text", "real", "boolean", "timestamp", "date", "json", "blob"];
+
+/// from から見た to の多重度
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    OneToOne,
+    OneToMany,
+    ManyToOne,
+    ManyToMany,
+}
+
+const CARDINALITIES: [&str; 4] = ["one_to_one", "one_to_many", "many_to_one", "many_to_many"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Relation {
+    pub from: String,
+    pub to: String,
+    pub cardinality: Cardinality,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+}
+
+/**
+* er_chart の JSON Schema。collection の schema に使う。
+*
+* @return
+*/
+pub fn chart_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "description": { "type": "string" },
+                        "columns": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "type": { "type": "string", "enum": COLUMN_TYPES },
+                                    "primary_key": { "type": "boolean" },
+                                    "not_null": { "type": "boolean" },
+                                    "unique": { "type": "boolean" },
+                                    "references": { "type": "string" }
+                                },
+                                "required": ["name", "type"],
+                                "additionalProperties": false
+                            }
+                        }
+                    },
+                    "required": ["name", "columns"],
+                    "additionalProperties": false
+                }
+            },
+            "relations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "from": { "type": "string" },
+                        "to": { "type": "string" },
+                        "cardinality": { "type": "string", "enum": CARDINALITIES },
+                        "label": { "type": "string" }
+                    },
+                    "required": ["from", "to", "cardinality"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["entities"],
+        "additionalProperties": false
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/**
+* ER 図を検証する。名前の重複、references , relations の参照先を確認する。
+*
+* @param chart
+*
+* @return
+*/
+pub fn validate_chart(chart: &ErChart) -> Result<(), String> {
+    if chart.entities.is_empty() {
+        return Err("entities is empty".to_string());
+    }
+    let mut entity_names = HashSet::new();
+    for entity in &chart.entities {
+        if !is_identifier(&entity.name) {
+            return Err(format!("invalid entity name: {}", entity.name));
+        }
+        if !entity_names.insert(entity.name.to_lowercase()) {
+            return Err(format!("duplicate entity: {}", entity.name));
+        }
+        if entity.columns.is_empty() {
+            return Err(format!("{}: columns is empty", entity.name));
+        }
+        let mut column_names = HashSet::new();
+        for column in &entity.columns {
+            if !is_identifier(&column.name) {
+                return Err(format!("{}: invalid column name: {}", entity.name, column.name));
+            }
+            if !column_names.insert(column.name.to_lowercase()) {
+                return Err(format!("{}: duplicate column: {}", entity.name, column.name));
+            }
+        }
+    }
+    for entity in &chart.entities {
+        for column in &entity.columns {
+            if let Some(references) = &column.references {
+                let target = references.split_once('.')
+                    .and_then(|(table, name)| find_column(chart, table, name));
+                if target.is_none() {
+                    return Err(format!("{}.{}: unknown reference {} (entity.column)", entity.name, column.name, references));
+                }
+            }
+        }
+    }
+    for relation in &chart.relations {
+        for name in [&relation.from, &relation.to] {
+            if find_entity(chart, name).is_none() {
+                return Err(format!("relation {} -> {}: unknown entity {}", relation.from, relation.to, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+* er_chart の data を検証する。collection の検証 (JSON Schema の後) に使う。
+*
+* @param value
+*
+* @return
+*/
+pub fn check_chart_value(value: &Value) -> Result<(), String> {
+    let chart: ErChart = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+    validate_chart(&chart)
+}
+
+fn find_entity<'a>(chart: &'a ErChart, name: &str) -> Option<&'a Entity> {
+    chart.entities.iter().find(|entity| entity.name.eq_ignore_ascii_case(name))
+}
+
+fn find_column<'a>(chart: &'a ErChart, table: &str, name: &str) -> Option<&'a Column> {
+    find_entity(chart, table)?.columns.iter().find(|column| column.name.eq_ignore_ascii_case(name))
+}
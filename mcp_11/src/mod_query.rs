@@ -0,0 +1,433 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sqlx::postgres::PgArguments;
+use sqlx::Postgres;
+use sqlx::query::Query;
+
+/// data_list の JSON パスの条件。path は $.status , $.tags[0] の形式
+#[derive(Debug, Clone, Deserialize)]
+pub struct Filter {
+    pub path: String,
+    #[serde(default)]
+    pub op: FilterOp,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    #[default]
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    Contains,
+    Exists,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// 並び順。path は JSON パス、または id , created_at , updated_at
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sort {
+    pub path: String,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// data_list の検索条件。mcp_9 (Turso) の data_list と同じ形式
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub filter: Vec<Filter>,
+    pub sort: Option<Sort>,
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+    pub cursor: Option<String>,
+}
+
+fn default_limit() -> i64 {
+    10
+}
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/**
+* JSON パスを分解する。$ から始まり、.key と [n] のみ使用できる。
+*
+* @param path
+*
+* @return
+*/
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = || format!("invalid path: {} (例: $.status , $.tags[0])", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(key.to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            segments.push(PathSegment::Index(after[..end].parse().map_err(|_| invalid())?));
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+/**
+* JSON パスの値を取り出す。
+*
+* @param value
+* @param path
+*
+* @return
+*/
+pub fn extract_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let segments = parse_path(path).ok()?;
+    segments.iter().try_fold(value, |current, segment| match segment {
+        PathSegment::Key(key) => current.get(key),
+        PathSegment::Index(index) => current.get(*index),
+    })
+}
+
+/**
+* data から fields の値のみ取り出す。key は $. を除いたパスとする。
+*
+* @param data
+* @param fields
+*
+* @return
+*/
+pub fn project_fields(data: &str, fields: &[String]) -> String {
+    let value: Value = serde_json::from_str(data).unwrap_or(Value::Null);
+    let mut projected = Map::new();
+    for field in fields {
+        let key = field.strip_prefix("$.").unwrap_or(field).to_string();
+        projected.insert(key, extract_path(&value, field).cloned().unwrap_or(Value::Null));
+    }
+    Value::Object(projected).to_string()
+}
+
+/// bind する値。Json は jsonb , Path は #> の text[] とする
+#[derive(Debug, Clone)]
+pub enum SqlValue {
+    Int(i64),
+    Text(String),
+    Json(String),
+    Path(Vec<String>),
+}
+
+/**
+* SqlValue を順に bind する。
+*
+* @param query
+* @param values
+*
+* @return
+*/
+pub fn bind_values<'q>(mut query: Query<'q, Postgres, PgArguments>, values: &[SqlValue]) -> Query<'q, Postgres, PgArguments> {
+    for value in values {
+        query = match value {
+            SqlValue::Int(i) => query.bind(*i),
+            SqlValue::Text(s) => query.bind(s.clone()),
+            SqlValue::Json(s) => query.bind(s.clone()),
+            SqlValue::Path(path) => query.bind(path.clone()),
+        };
+    }
+    query
+}
+
+/// 組み立て中の SQL と、bind する値
+#[derive(Default)]
+struct SqlBuilder {
+    where_list: Vec<String>,
+    values: Vec<SqlValue>,
+}
+
+impl SqlBuilder {
+    /// 値を追加し、型を付けた placeholder ($n) を返す
+    fn bind(&mut self, value: SqlValue) -> String {
+        let cast = match &value {
+            SqlValue::Int(_) => "",
+            SqlValue::Text(_) => "::text",
+            SqlValue::Json(_) => "::jsonb",
+            SqlValue::Path(_) => "::text[]",
+        };
+        self.values.push(value);
+        format!("${}{}", self.values.len(), cast)
+    }
+
+    /// #> の text[] の placeholder
+    fn path(&mut self, segments: &[PathSegment]) -> String {
+        let path = segments.iter().map(|segment| match segment {
+            PathSegment::Key(key) => key.clone(),
+            PathSegment::Index(index) => index.to_string(),
+        }).collect();
+        self.bind(SqlValue::Path(path))
+    }
+
+    /// data #> path の式。JSON の null は、SQLite の json_extract と同じく NULL とする
+    fn extract(&mut self, segments: &[PathSegment]) -> String {
+        format!("NULLIF(data #> {}, 'null'::jsonb)", self.path(segments))
+    }
+}
+
+/**
+* path の位置に value を置いた JSON を作る。@> (GIN index) の条件に使う。
+* 例: $.a.b , 1 -> {"a": {"b": 1}}
+*/
+fn containment(segments: &[PathSegment], value: Value) -> Option<Value> {
+    segments.iter().rev().try_fold(value, |inner, segment| match segment {
+        PathSegment::Key(key) => Some(json!({ key.clone(): inner })),
+        PathSegment::Index(_) => None,
+    })
+}
+
+fn filter_sql(builder: &mut SqlBuilder, filter: &Filter) -> Result<String, String> {
+    let segments = parse_path(&filter.path)?;
+    let scalar = || {
+        if filter.value.is_array() || filter.value.is_object() {
+            return Err(format!("{}: value must be a scalar", filter.path));
+        }
+        Ok(filter.value.to_string())
+    };
+    match filter.op {
+        FilterOp::Eq if filter.value.is_null() => Ok(format!("{} IS NULL", builder.extract(&segments))),
+        FilterOp::Ne if filter.value.is_null() => Ok(format!("{} IS NOT NULL", builder.extract(&segments))),
+        FilterOp::Eq => {
+            let value = scalar()?;
+            // key のみのパスは @> とし、GIN index を使う
+            match containment(&segments, filter.value.clone()) {
+                Some(document) if !segments.is_empty() => Ok(format!("data @> {}", builder.bind(SqlValue::Json(document.to_string())))),
+                _ => Ok(format!("{} = {}", builder.extract(&segments), builder.bind(SqlValue::Json(value)))),
+            }
+        }
+        FilterOp::Ne => {
+            let expr = builder.extract(&segments);
+            let value = builder.bind(SqlValue::Json(scalar()?));
+            Ok(format!("({e} IS NULL OR {e} <> {v})", e = expr, v = value))
+        }
+        FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte => {
+            let op = match filter.op {
+                FilterOp::Gt => ">",
+                FilterOp::Gte => ">=",
+                FilterOp::Lt => "<",
+                _ => "<=",
+            };
+            // jsonb の比較は型ごとの順序になるため、同じ型の値のみ比較する
+            let expr = builder.extract(&segments);
+            let value = builder.bind(SqlValue::Json(scalar()?));
+            Ok(format!("(jsonb_typeof({e}) = jsonb_typeof({v}) AND {e} {op} {v})", e = expr, v = value, op = op))
+        }
+        FilterOp::In => {
+            let values = filter.value.as_array().ok_or(format!("{}: in requires an array", filter.path))?;
+            if values.is_empty() {
+                return Ok("false".to_string());
+            }
+            let expr = builder.extract(&segments);
+            let placeholders: Vec<String> = values.iter().map(|value| builder.bind(SqlValue::Json(value.to_string()))).collect();
+            Ok(format!("{} IN ({})", expr, placeholders.join(", ")))
+        }
+        FilterOp::Contains => {
+            // 配列は要素の一致、文字列は部分一致
+            let expr = builder.extract(&segments);
+            let element = builder.bind(SqlValue::Json(json!([filter.value]).to_string()));
+            let text = builder.bind(SqlValue::Text(match &filter.value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            }));
+            Ok(format!("((jsonb_typeof({e}) = 'array' AND {e} @> {element})
+            OR (jsonb_typeof({e}) = 'string' AND strpos({e} #>> '{{}}', {text}) > 0))", e = expr, element = element, text = text))
+        }
+        FilterOp::Exists => {
+            // JSON の null も、項目がある場合は exists とする
+            let path = builder.path(&segments);
+            let exists = filter.value.as_bool().unwrap_or(true);
+            Ok(format!("data #> {} IS {}NULL", path, if exists { "NOT " } else { "" }))
+        }
+    }
+}
+
+/// 並び順の種類。cursor の値の型に使う
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKind {
+    Id,
+    Timestamp,
+    Json,
+}
+
+/**
+* 並び順の式と種類。id , created_at , updated_at は列、それ以外は JSON パスとする。
+*/
+fn sort_sql(builder: &mut SqlBuilder, sort: &Sort) -> Result<(String, SortKind), String> {
+    match sort.path.as_str() {
+        "id" => Ok(("id".to_string(), SortKind::Id)),
+        "created_at" | "updated_at" => Ok((sort.path.clone(), SortKind::Timestamp)),
+        _ => Ok((builder.extract(&parse_path(&sort.path)?), SortKind::Json)),
+    }
+}
+
+/**
+* cursor の条件。cursor は前のページの最後の行の [並び順の値, id]。
+* 並び順の値が NULL の行は、昇順では先頭、降順では末尾とする (mcp_9 の SQLite と同じ)。
+*/
+fn cursor_sql(builder: &mut SqlBuilder, sort_expr: &str, kind: SortKind, order: SortOrder, cursor: &str) -> Result<String, String> {
+    let invalid = || format!("invalid cursor: {}", cursor);
+    let parsed: Value = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    let (sort_value, id) = match parsed.as_array().map(|items| items.as_slice()) {
+        Some([sort_value, id]) => (sort_value.clone(), id.as_i64().ok_or_else(invalid)?),
+        _ => return Err(invalid()),
+    };
+    let id = builder.bind(SqlValue::Int(id));
+    let op = match order {
+        SortOrder::Asc => ">",
+        SortOrder::Desc => "<",
+    };
+    if sort_value.is_null() {
+        return Ok(match order {
+            SortOrder::Asc => format!("(({e} IS NULL AND id {o} {id}) OR {e} IS NOT NULL)", e = sort_expr, o = op, id = id),
+            SortOrder::Desc => format!("({e} IS NULL AND id {o} {id})", e = sort_expr, o = op, id = id),
+        });
+    }
+    let value = match kind {
+        SortKind::Id => builder.bind(SqlValue::Int(sort_value.as_i64().ok_or_else(invalid)?)),
+        SortKind::Timestamp => format!("{}::timestamp", builder.bind(SqlValue::Text(sort_value.as_str().ok_or_else(invalid)?.to_string()))),
+        SortKind::Json => builder.bind(SqlValue::Json(sort_value.to_string())),
+    };
+    let after = format!("({e} {op} {v} OR ({e} = {v} AND id {op} {id}))", e = sort_expr, op = op, v = value, id = id);
+    Ok(match order {
+        SortOrder::Asc => after,
+        SortOrder::Desc => format!("({} OR {} IS NULL)", after, sort_expr),
+    })
+}
+
+/// data_list の SQL。列は id , data , created_at , updated_at , version , sort_value
+pub struct ListSql {
+    pub sql: String,
+    pub values: Vec<SqlValue>,
+    pub limit: i64,
+    pub sort_kind: SortKind,
+}
+
+/**
+* 条件 , JSON パスの並び順 , 項目のいずれかを使う検索か。data が JSON でない collection の確認に使う。
+*
+* @param query
+*
+* @return
+*/
+pub fn uses_json_path(query: &ListQuery) -> bool {
+    let json_sort = query.sort.as_ref()
+        .is_some_and(|sort| !matches!(sort.path.as_str(), "id" | "created_at" | "updated_at"));
+    !query.filter.is_empty() || !query.fields.is_empty() || json_sort
+}
+
+/**
+* data_list の条件から Postgres の SELECT を作る。値はすべて bind する。
+* 次のページの有無を判定するため、limit + 1 件を取得する。
+*
+* @param table 登録済みの collection 名
+* @param query
+*
+* @return
+*/
+pub fn build_list_sql(table: &str, query: &ListQuery) -> Result<ListSql, String> {
+    let mut builder = SqlBuilder::default();
+    let limit = query.limit.clamp(1, MAX_LIMIT);
+    for field in &query.fields {
+        parse_path(field)?;
+    }
+    for filter in &query.filter {
+        let sql = filter_sql(&mut builder, filter)?;
+        builder.where_list.push(sql);
+    }
+    let sort = query.sort.clone().unwrap_or(Sort {
+        path: "created_at".to_string(),
+        order: SortOrder::Desc,
+    });
+    let (sort_expr, sort_kind) = sort_sql(&mut builder, &sort)?;
+    if let Some(cursor) = &query.cursor {
+        let sql = cursor_sql(&mut builder, &sort_expr, sort_kind, sort.order, cursor)?;
+        builder.where_list.push(sql);
+    }
+    let where_sql = if builder.where_list.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", builder.where_list.join(" AND "))
+    };
+    // NULL の位置を SQLite と同じにする
+    let direction = match sort.order {
+        SortOrder::Asc => "ASC NULLS FIRST",
+        SortOrder::Desc => "DESC NULLS LAST",
+    };
+    let id_direction = match sort.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    let limit_sql = builder.bind(SqlValue::Int(limit + 1));
+    let offset_sql = builder.bind(SqlValue::Int(query.offset.max(0)));
+    let sql = format!("SELECT id, data::text AS data,
+    to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+    to_char(updated_at, 'YYYY-MM-DD HH24:MI:SS') AS updated_at,
+    updated_at::text AS version, ({sort})::text AS sort_value
+    FROM {table}
+    {where_sql}
+    ORDER BY {sort} {direction}, id {id_direction}
+    LIMIT {limit} OFFSET {offset}
+    ", sort = sort_expr, table = table, where_sql = where_sql, direction = direction,
+    id_direction = id_direction, limit = limit_sql, offset = offset_sql);
+    Ok(ListSql {
+        sql,
+        values: builder.values,
+        limit,
+        sort_kind,
+    })
+}
+
+/**
+* 行の並び順の値と id から、次のページの cursor を作る。
+*
+* @param kind
+* @param sort_value 並び順の値 (text)
+* @param id
+*
+* @return
+*/
+pub fn make_cursor(kind: SortKind, sort_value: Option<String>, id: i64) -> String {
+    let value = match (kind, sort_value) {
+        (_, None) => Value::Null,
+        (SortKind::Id, Some(value)) => value.parse::<i64>().map(|id| json!(id)).unwrap_or(Value::Null),
+        (SortKind::Timestamp, Some(value)) => json!(value),
+        (SortKind::Json, Some(value)) => serde_json::from_str(&value).unwrap_or(Value::Null),
+    };
+    json!([value, id]).to_string()
+}
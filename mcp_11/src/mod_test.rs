@@ -33,6 +33,90 @@ pub struct Item {
 struct ItemDeleteParams {
     content: String,
     id: i32,
+    etag: Option<String>,
+}
+
+// 更新の競合 (etag の不一致)
+const CONFLICT_CODE: i32 = -32009;
+
+/// etag を付けた test の1行
+#[derive(Debug, Serialize)]
+struct TestRow {
+    id: i32,
+    title: String,
+    content: String,
+    updated_at: String,
+    etag: String,
+}
+
+/**
+* updated_at と title , content から etag を作る (FNV-1a 64bit)。
+*
+* @param updated_at
+* @param title
+* @param content
+*
+* @return
+*/
+fn test_etag(updated_at: &str, title: &str, content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in [updated_at, title, content].join("\n").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/**
+* id の行を、etag を付けて読み込む。
+*
+* @param pool
+* @param table
+* @param id
+*
+* @return
+*/
+async fn find_test_row(pool: &PgPool, table: &str, id: i32) -> Option<TestRow> {
+    let sql = format!("SELECT id, title, content, updated_at::text AS updated_at FROM {} WHERE id = $1", table);
+    let row = sqlx::query(&sql)
+        .bind(id)
+        .fetch_optional(pool)
+        .await.unwrap()?;
+    let updated_at: String = row.get("updated_at");
+    let title: String = row.get("title");
+    let content: String = row.get("content");
+    Some(TestRow {
+        id: row.get("id"),
+        etag: test_etag(&updated_at, &title, &content),
+        title,
+        content,
+        updated_at,
+    })
+}
+
+/**
+* etag が一致しない場合のエラー。現在の行を message に含める。
+*
+* @param request_id
+* @param etag
+* @param current
+*
+* @return
+*/
+fn conflict_response(request_id: Option<Value>, etag: &str, current: Option<TestRow>) -> super::JsonRpcResponse {
+    let message = match current {
+        Some(current) => format!("Conflict, etag={} , current={}", etag, serde_json::to_string(&current).unwrap()),
+        None => format!("Conflict, etag={} , current=deleted", etag),
+    };
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(super::JsonRpcError {
+            code: CONFLICT_CODE,
+            message,
+        }),
+    }
 }
 
 /**
@@ -117,6 +201,7 @@ pub async fn test_list_handler(params: Value, request_id: Option<Value>) -> supe
         id: i64,
         title: String,
         content: String,
+        etag: String,
     }    
     let con_str = super::POSTGRES_CONNECTION_STR.to_string();
     let pool = PgPoolOptions::new().max_connections(5)
@@ -128,7 +213,7 @@ pub async fn test_list_handler(params: Value, request_id: Option<Value>) -> supe
             let content = item_list_params.content.clone();
             let order_sql = "ORDER BY created_at DESC LIMIT 10;";
             //let sql = format!("SELECT id, title, content ,created_at, updated_at 
-            let sql = format!("SELECT id, title, content  
+            let sql = format!("SELECT id, title, content, updated_at::text AS updated_at
             FROM {} {}
             "
             , content, order_sql
@@ -143,6 +228,7 @@ pub async fn test_list_handler(params: Value, request_id: Option<Value>) -> supe
                     id: row.get("id"),
                     title: row.get("title"),
                     content: row.get("content"),
+                    etag: test_etag(row.get("updated_at"), row.get("title"), row.get("content")),
                 })
                 .collect();           
             let out = serde_json::to_string(&todoItems).unwrap();
@@ -243,14 +329,33 @@ pub async fn test_delete_handler(params: Value, request_id: Option<Value>) -> su
                     };                            
                 }
 
-                let sql = format!("DELETE FROM {} WHERE id = {}"
-                ,content_value , id_value);
-                println!("sql={}", &sql);
+                if let Some(etag) = &item_delete_params.etag {
+                    let current = find_test_row(&pool, &content_value, id_value).await;
+                    let Some(current) = current.filter(|row| row.etag == *etag) else {
+                        return conflict_response(request_id, etag, find_test_row(&pool, &content_value, id_value).await);
+                    };
+                    // 読み込み後に更新された場合は、削除しない
+                    let sql = format!("DELETE FROM {} WHERE id = $1 AND updated_at::text = $2", content_value);
+                    eprintln!("sql={}", &sql);
+                    let result = sqlx::query(&sql)
+                        .bind(id_value)
+                        .bind(&current.updated_at)
+                        .execute(&pool)
+                        .await
+                        .unwrap();
+                    if result.rows_affected() == 0 {
+                        return conflict_response(request_id, etag, find_test_row(&pool, &content_value, id_value).await);
+                    }
+                } else {
+                    let sql = format!("DELETE FROM {} WHERE id = {}"
+                    ,content_value , id_value);
+                    println!("sql={}", &sql);
 
-                let result = sqlx::query(&sql)
-                    .execute(&pool)
-                    .await
-                    .unwrap();
+                    let result = sqlx::query(&sql)
+                        .execute(&pool)
+                        .await
+                        .unwrap();
+                }
 
                 let resp = format!("Complete delete, id={}", id_value);
                 return super::JsonRpcResponse {
@@ -306,6 +411,7 @@ pub async fn test_update_handler(params: Value, request_id: Option<Value>) -> su
         content: String,
         title: String,
         id: i32,
+        etag: Option<String>,
     }    
     let con_str = super::POSTGRES_CONNECTION_STR.to_string();
     let pool = PgPoolOptions::new().max_connections(5)
@@ -314,13 +420,36 @@ pub async fn test_update_handler(params: Value, request_id: Option<Value>) -> su
     if let Some(arguments) = params.get("arguments") {
         match serde_json::from_value::<UpdateParams>(arguments.clone()) {
             Ok(item_params) => {
-                let sql = format!("UPDATE test SET title = '{}' , content='{}' WHERE id = {}"
-                , &item_params.title, &item_params.content , &item_params.id
-               );
-                let result = sqlx::query(&sql)
+                let Some(current) = find_test_row(&pool, "test", item_params.id).await else {
+                    return super::JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        id: request_id,
+                        result: None,
+                        error: Some(super::JsonRpcError {
+                            code: -32602,
+                            message: format!("Invalid parameters, id={}", item_params.id),
+                        }),
+                    };
+                };
+                if let Some(etag) = &item_params.etag
+                    && *etag != current.etag {
+                    return conflict_response(request_id, etag, Some(current));
+                }
+                // 読み込み後に他の更新があった場合は、更新しない
+                let result = sqlx::query(
+                    "UPDATE test SET title = $1 , content = $2 , updated_at = now() WHERE id = $3 AND updated_at::text = $4",
+                )
+                .bind(&item_params.title)
+                .bind(&item_params.content)
+                .bind(item_params.id)
+                .bind(&current.updated_at)
                 .execute(&pool)
                 .await.unwrap();
-                println!("# /api/update END");
+                eprintln!("# /api/update END");
+                let updated = find_test_row(&pool, "test", item_params.id).await;
+                if result.rows_affected() == 0 {
+                    return conflict_response(request_id, &current.etag, updated);
+                }
 
                 return super::JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
@@ -329,7 +458,7 @@ pub async fn test_update_handler(params: Value, request_id: Option<Value>) -> su
                         "content": [
                             {
                                 "type": "text",
-                                "text": "OK".to_string()
+                                "text": format!("OK, etag={}", updated.map(|row| row.etag).unwrap_or_default())
                             }
                         ]
                     })),
@@ -26,6 +26,51 @@ struct JsonRpcError {
     message: String,
 }
 
+/**
+* エラーの response。
+*
+* @param request_id
+* @param code
+* @param message
+*
+* @return
+*/
+fn error_response(request_id: Option<Value>, code: i32, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+        }),
+    }
+}
+
+/**
+* text を1つ返す response。
+*
+* @param request_id
+* @param text
+*
+* @return
+*/
+fn text_response(request_id: Option<Value>, text: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": text
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct AddTenParams {
     value: i32,
@@ -41,7 +86,7 @@ struct PurchaseParams {
 fn purchase(product_name: String, price: i32) -> String {
     format!("「{}」を{}円で購入しました。", product_name, price)
 }
-const API_ENDPOINT: &'static str = "http://localhost:8787/api/data/create";
+mod mod_outbox;
 
 async fn purchase_handler(params: Value, request_id: Option<Value>) -> JsonRpcResponse {
     if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
@@ -49,58 +94,55 @@ async fn purchase_handler(params: Value, request_id: Option<Value>) -> JsonRpcRe
             if let Some(arguments) = params.get("arguments") {
                 match serde_json::from_value::<PurchaseParams>(arguments.clone()) {
                     Ok(purchase_params) => {
-                        let client = reqwest::Client::new();
-                        let post_data = PurchaseParams {
-                            name: purchase_params.name.clone(),
-                            price: purchase_params.price
-                        };    
-                        let json_string_variable = serde_json::to_string(&post_data).expect("JSON convert error");
-                        println!("変換されたJSON文字列: {}", json_string_variable);                         
-                        let send_data = json!({
-                            "content": "item_price",
-                            "data": &json_string_variable
-                        });
-                        match client.post(API_ENDPOINT).json(&send_data).send().await {
-                            Ok(api_res) => {
-                                if api_res.status().is_success() {
-                                    let result = purchase(purchase_params.name, purchase_params.price);
-                                    return JsonRpcResponse {
-                                        jsonrpc: "2.0".to_string(),
-                                        id: request_id,
-                                        result: Some(json!({
-                                            "content": [
-                                                {
-                                                    "type": "text",
-                                                    "text": result
-                                                }
-                                            ]
-                                        })),
-                                        error: None,
-                                    };
-                                } else {
-                                    return JsonRpcResponse {
-                                        jsonrpc: "2.0".to_string(),
-                                        id: request_id,
-                                        result: None,
-                                        error: Some(JsonRpcError {
-                                            code: -32000,
-                                            message: format!("API request failed with status: {}", api_res.status()),
-                                        }),
-                                    };
-                                }
-                            }
+                        let config = match mod_outbox::load_config() {
+                            Ok(config) => config,
                             Err(e) => {
                                 return JsonRpcResponse {
                                     jsonrpc: "2.0".to_string(),
                                     id: request_id,
                                     result: None,
                                     error: Some(JsonRpcError {
-                                        code: -32000,
-                                        message: format!("API request failed: {}", e),
+                                        code: -32603,
+                                        message: e,
                                     }),
                                 };
                             }
-                        }
+                        };
+                        let send_data = mod_outbox::build_payload(&config.payload_template, &purchase_params);
+                        let result = purchase(purchase_params.name, purchase_params.price);
+                        let result = match mod_outbox::send(&config, &send_data).await {
+                            Ok(()) => result,
+                            Err(e) => {
+                                // 送信できない場合は outbox に保存し、後で再送する
+                                match mod_outbox::enqueue(&config, send_data, e.clone()).await {
+                                    Ok(outbox_id) => format!("{}\n送信に失敗したため、outbox に保存しました。id={} , {}", result, outbox_id, e),
+                                    Err(outbox_error) => {
+                                        return JsonRpcResponse {
+                                            jsonrpc: "2.0".to_string(),
+                                            id: request_id,
+                                            result: None,
+                                            error: Some(JsonRpcError {
+                                                code: -32000,
+                                                message: format!("{} , {}", e, outbox_error),
+                                            }),
+                                        };
+                                    }
+                                }
+                            }
+                        };
+                        return JsonRpcResponse {
+                            jsonrpc: "2.0".to_string(),
+                            id: request_id,
+                            result: Some(json!({
+                                "content": [
+                                    {
+                                        "type": "text",
+                                        "text": result
+                                    }
+                                ]
+                            })),
+                            error: None,
+                        };
                     }
                     Err(e) => {
                         return JsonRpcResponse {
@@ -167,6 +209,25 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                             },
                             "required": ["name", "price"]
                         }
+                    },
+                    {
+                        "name": "purchase_outbox",
+                        "description": "送信に失敗して保存された購入データを、一覧 / 再送 / 削除します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "action": {
+                                    "type": "string",
+                                    "enum": ["list", "retry", "drop"],
+                                    "description": "list: 一覧 , retry: 再送 , drop: 削除"
+                                },
+                                "id": {
+                                    "type": "number",
+                                    "description": "outbox の ID。retry で省略した場合は全件を再送します"
+                                }
+                            },
+                            "required": ["action"]
+                        }
                     }
                 ]
             })),
@@ -174,7 +235,11 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
         },
         "tools/call" => {
             if let Some(params) = request.params {
-                purchase_handler(params, request.id).await
+                if params.get("name").and_then(|v| v.as_str()) == Some("purchase_outbox") {
+                    mod_outbox::purchase_outbox_handler(params, request.id).await
+                } else {
+                    purchase_handler(params, request.id).await
+                }
             } else {
                 JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
@@ -205,6 +270,7 @@ async fn main() {
     let mut stdout = io::stdout();
     
     eprintln!("MCP Server started. Waiting for requests...");
+    tokio::spawn(mod_outbox::retry_loop());
 
     for line in stdin.lock().lines() {
         match line {
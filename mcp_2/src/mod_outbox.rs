@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use super::{error_response, text_response};
+
+const DEFAULT_API_ENDPOINT: &str = "http://localhost:8787/api/data/create";
+const DEFAULT_PAYLOAD_TEMPLATE: &str = r#"{"content": "item_price", "data": "{{data}}"}"#;
+
+// 再送間隔: 30秒から倍々で、最大1時間
+const RETRY_BASE_SECS: u64 = 30;
+const RETRY_MAX_SECS: u64 = 3600;
+const RETRY_LOOP_SECS: u64 = 30;
+
+// outbox ファイルの読み書きと再送を直列化する
+static OUTBOX_LOCK: Mutex<()> = Mutex::const_new(());
+
+/**
+* 送信先の設定。環境変数から読み込む。
+*
+* API_ENDPOINT: 送信先 URL
+* API_AUTH_HEADER: 認証ヘッダー "Name: value" 形式 (例: "Authorization: Bearer xxx")
+* API_PAYLOAD_TEMPLATE: 送信する JSON のテンプレート。{{name}} , {{price}} , {{data}} を置換する
+* OUTBOX_PATH: 送信失敗時の保存先ファイル
+*/
+pub struct SinkConfig {
+    pub endpoint: String,
+    pub auth_header: Option<(String, String)>,
+    pub payload_template: Value,
+    pub outbox_path: PathBuf,
+}
+
+pub fn load_config() -> Result<SinkConfig, String> {
+    dotenvy::dotenv().ok();
+    let endpoint = env::var("API_ENDPOINT").unwrap_or(DEFAULT_API_ENDPOINT.to_string());
+    let auth_header = match env::var("API_AUTH_HEADER") {
+        Ok(header) if !header.trim().is_empty() => match header.split_once(':') {
+            Some((name, value)) => Some((name.trim().to_string(), value.trim().to_string())),
+            None => return Err(format!("API_AUTH_HEADER must be \"Name: value\", value={}", header)),
+        },
+        _ => None,
+    };
+    let template_str = env::var("API_PAYLOAD_TEMPLATE").unwrap_or(DEFAULT_PAYLOAD_TEMPLATE.to_string());
+    let payload_template: Value = serde_json::from_str(&template_str)
+        .map_err(|e| format!("API_PAYLOAD_TEMPLATE JSON error: {}", e))?;
+    let outbox_path = match env::var("OUTBOX_PATH") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("outbox.jsonl")))
+            .unwrap_or(PathBuf::from("outbox.jsonl")),
+    };
+    Ok(SinkConfig {
+        endpoint,
+        auth_header,
+        payload_template,
+        outbox_path,
+    })
+}
+
+/**
+* テンプレートの文字列値を置換して、送信する JSON を作成する。
+* 値が "{{price}}" のみの場合は数値のまま埋め込む。
+*
+* @param template
+* @param purchase
+*
+* @return
+*/
+pub fn build_payload(template: &Value, purchase: &super::PurchaseParams) -> Value {
+    let data = serde_json::to_string(purchase).expect("JSON convert error");
+    match template {
+        Value::String(s) if s == "{{price}}" => json!(purchase.price),
+        Value::String(s) => Value::String(
+            s.replace("{{name}}", &purchase.name)
+                .replace("{{price}}", &purchase.price.to_string())
+                .replace("{{data}}", &data),
+        ),
+        Value::Array(list) => Value::Array(list.iter().map(|v| build_payload(v, purchase)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter().map(|(k, v)| (k.clone(), build_payload(v, purchase))).collect(),
+        ),
+        _ => template.clone(),
+    }
+}
+
+pub async fn send(config: &SinkConfig, payload: &Value) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let mut request = client
+        .post(&config.endpoint)
+        .timeout(Duration::from_secs(10))
+        .json(payload);
+    if let Some((name, value)) = &config.auth_header {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    match request.send().await {
+        Ok(api_res) if api_res.status().is_success() => Ok(()),
+        Ok(api_res) => Err(format!("API request failed with status: {}", api_res.status())),
+        Err(e) => Err(format!("API request failed: {}", e)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxItem {
+    pub id: u64,
+    pub payload: Value,
+    pub attempts: u32,
+    pub next_retry_at: u64,
+    pub last_error: String,
+    pub created_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn backoff_secs(attempts: u32) -> u64 {
+    RETRY_BASE_SECS.saturating_mul(1u64 << attempts.saturating_sub(1).min(16)).min(RETRY_MAX_SECS)
+}
+
+fn load_items(config: &SinkConfig) -> Result<Vec<OutboxItem>, String> {
+    let text = match std::fs::read_to_string(&config.outbox_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("outbox read error: {}", e)),
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str::<OutboxItem>(line).map_err(|e| format!("outbox JSON error: {}", e)))
+        .collect()
+}
+
+// 一時ファイルに書き出してから置き換え、書き込み途中で落ちても内容を失わないようにする
+fn save_items(config: &SinkConfig, items: &[OutboxItem]) -> Result<(), String> {
+    let mut text = String::new();
+    for item in items {
+        text.push_str(&serde_json::to_string(item).expect("JSON convert error"));
+        text.push('\n');
+    }
+    let tmp_path = config.outbox_path.with_extension("jsonl.tmp");
+    std::fs::write(&tmp_path, text).map_err(|e| format!("outbox write error: {}", e))?;
+    std::fs::rename(&tmp_path, &config.outbox_path).map_err(|e| format!("outbox write error: {}", e))
+}
+
+/**
+* 送信に失敗した payload を outbox に保存する。
+*
+* @return outbox の id
+*/
+pub async fn enqueue(config: &SinkConfig, payload: Value, error: String) -> Result<u64, String> {
+    let _guard = OUTBOX_LOCK.lock().await;
+    let mut items = load_items(config)?;
+    let now = now_secs();
+    let id = items.iter().map(|item| item.id + 1).max().unwrap_or(1);
+    items.push(OutboxItem {
+        id,
+        payload,
+        attempts: 1,
+        next_retry_at: now + backoff_secs(1),
+        last_error: error,
+        created_at: now,
+    });
+    save_items(config, &items)?;
+    Ok(id)
+}
+
+/**
+* outbox の再送。送信できた項目は削除し、失敗した項目は次回の再送時刻を延ばす。
+*
+* @param force true の場合、再送時刻に関係なく送信する
+* @param only_id 指定した id のみ送信する
+*
+* @return (送信件数, 失敗件数)
+*/
+pub async fn retry(config: &SinkConfig, force: bool, only_id: Option<u64>) -> Result<(usize, usize), String> {
+    let _guard = OUTBOX_LOCK.lock().await;
+    let items = load_items(config)?;
+    let now = now_secs();
+    let mut remaining: Vec<OutboxItem> = Vec::new();
+    let (mut sent, mut failed) = (0, 0);
+    for mut item in items {
+        let target = match only_id {
+            Some(id) => item.id == id,
+            None => force || item.next_retry_at <= now,
+        };
+        if !target {
+            remaining.push(item);
+            continue;
+        }
+        match send(config, &item.payload).await {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                failed += 1;
+                item.attempts += 1;
+                item.next_retry_at = now_secs() + backoff_secs(item.attempts);
+                item.last_error = e;
+                remaining.push(item);
+            }
+        }
+    }
+    if sent > 0 || failed > 0 {
+        save_items(config, &remaining)?;
+    }
+    Ok((sent, failed))
+}
+
+/**
+* 再送時刻を過ぎた outbox の項目を、定期的に再送する。
+*/
+pub async fn retry_loop() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(RETRY_LOOP_SECS)).await;
+        match load_config() {
+            Ok(config) => {
+                if let Err(e) = retry(&config, false, None).await {
+                    eprintln!("outbox retry error: {}", e);
+                }
+            }
+            Err(e) => eprintln!("config error: {}", e),
+        }
+    }
+}
+
+/**
+* purchase_outbox: 未送信データの 一覧 / 再送 / 削除
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_outbox_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse {
+    #[derive(Debug, Deserialize)]
+    struct OutboxParams {
+        action: String,
+        id: Option<u64>,
+    }
+
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => return error_response(request_id, -32603, e),
+    };
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<OutboxParams>(arguments.clone()) {
+            Ok(outbox_params) => {
+                let result = match outbox_params.action.as_str() {
+                    "list" => {
+                        let _guard = OUTBOX_LOCK.lock().await;
+                        load_items(&config).map(|items| {
+                            let mut out_str = format!("pending: {}\n", items.len());
+                            for item in &items {
+                                out_str.push_str(&format!(
+                                    "id: {} , attempts= {} , next_retry_at= {} , last_error= {}\n{}\n",
+                                    item.id, item.attempts, item.next_retry_at, item.last_error, item.payload
+                                ));
+                            }
+                            out_str
+                        })
+                    }
+                    "retry" => retry(&config, true, outbox_params.id)
+                        .await
+                        .map(|(sent, failed)| format!("Complete retry, sent={} , failed={}", sent, failed)),
+                    "drop" => match outbox_params.id {
+                        Some(id) => {
+                            let _guard = OUTBOX_LOCK.lock().await;
+                            load_items(&config).and_then(|items| {
+                                let count = items.len();
+                                let remaining: Vec<OutboxItem> = items.into_iter().filter(|item| item.id != id).collect();
+                                if remaining.len() == count {
+                                    return Err(format!("Invalid parameters, id={}", id));
+                                }
+                                save_items(&config, &remaining).map(|_| format!("Complete drop, id={}", id))
+                            })
+                        }
+                        None => Err("Invalid parameters: id is required for drop".to_string()),
+                    },
+                    action => Err(format!("Invalid parameters, action={}", action)),
+                };
+                return match result {
+                    Ok(text) => text_response(request_id, text),
+                    Err(e) => error_response(request_id, -32602, e),
+                };
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
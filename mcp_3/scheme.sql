@@ -12,3 +12,99 @@ CREATE TABLE IF NOT EXISTS mcp_diary (
   updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
+
+CREATE TABLE IF NOT EXISTS receipt (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  data TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS item_master (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS item_alias (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  item_id INTEGER NOT NULL,
+  alias TEXT NOT NULL,
+  normalized TEXT NOT NULL UNIQUE,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS recurring_purchase (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  price INTEGER NOT NULL,
+  schedule TEXT NOT NULL,
+  interval_days INTEGER,
+  start_date TEXT NOT NULL,
+  next_due TEXT NOT NULL,
+  end_date TEXT,
+  active INTEGER NOT NULL DEFAULT 1,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_item_price_recurring
+  ON item_price (json_extract(data, '$.recurring_id'), json_extract(data, '$.date'))
+  WHERE json_extract(data, '$.recurring_id') IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS exchange_rate (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  currency TEXT NOT NULL,
+  rate_date TEXT NOT NULL,
+  rate REAL NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(currency, rate_date)
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS mcp_diary_fts USING fts5(title, body, tags, tokenize = 'trigram');
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_insert AFTER INSERT ON mcp_diary BEGIN
+  INSERT INTO mcp_diary_fts (rowid, title, body, tags) VALUES (new.id,
+    json_extract(new.data, '$.title'),
+    COALESCE(json_extract(new.data, '$.body'), json_extract(new.data, '$.text')),
+    (SELECT group_concat(value, ' ') FROM json_each(new.data, '$.tags')));
+END;
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_update AFTER UPDATE OF data ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_fts WHERE rowid = old.id;
+  INSERT INTO mcp_diary_fts (rowid, title, body, tags) VALUES (new.id,
+    json_extract(new.data, '$.title'),
+    COALESCE(json_extract(new.data, '$.body'), json_extract(new.data, '$.text')),
+    (SELECT group_concat(value, ' ') FROM json_each(new.data, '$.tags')));
+END;
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_delete AFTER DELETE ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_fts WHERE rowid = old.id;
+END;
+
+CREATE TABLE IF NOT EXISTS mcp_diary_embedding (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  diary_id INTEGER NOT NULL,
+  chunk_index INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  embedding F32_BLOB(1024) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_mcp_diary_embedding_diary_id ON mcp_diary_embedding (diary_id);
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_embedding_delete AFTER DELETE ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_embedding WHERE diary_id = old.id;
+END;
+
+CREATE TABLE IF NOT EXISTS mcp_diary_digest (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  from_date TEXT NOT NULL,
+  to_date TEXT NOT NULL,
+  source_hash TEXT NOT NULL,
+  summary TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(from_date, to_date)
+);
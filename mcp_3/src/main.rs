@@ -1,3 +1,4 @@
+#![recursion_limit = "256"]
 use libsql::Database;
 use libsql::Builder;
 use libsql::Connection;
@@ -32,6 +33,64 @@ pub struct JsonRpcError {
     message: String,
 }
 
+/**
+* エラーの response。
+*
+* @param request_id
+* @param code
+* @param message
+*
+* @return
+*/
+pub fn error_response(request_id: Option<Value>, code: i32, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+        }),
+    }
+}
+
+/**
+* text を1つ返す response。
+*
+* @param request_id
+* @param text
+*
+* @return
+*/
+pub fn text_response(request_id: Option<Value>, text: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": text
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
+/**
+* Turso に接続する。
+*
+* @return
+*/
+pub async fn connect() -> Connection {
+    dotenv().ok();
+    let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
+    let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
+    let db = Builder::new_remote(url, token).build().await.unwrap();
+    db.connect().unwrap()
+}
+
 #[derive(Debug, Deserialize)]
 struct AddTenParams {
     value: i32,
@@ -57,6 +116,16 @@ fn purchase(product_name: String, price: i32) -> String {
 
 mod mod_purchase;
 mod mod_diary;
+mod mod_diary_search;
+mod mod_diary_embed;
+mod mod_diary_note;
+mod mod_diary_digest;
+mod mod_receipt;
+mod mod_import;
+mod mod_item;
+mod mod_recurring;
+mod mod_ledger;
+mod mod_currency;
 
 async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
     match request.method.as_str() {
@@ -92,10 +161,43 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                                 },
                                 "price": {
                                     "type": "number",
-                                    "description": "価格"
+                                    "description": "価格 (基準通貨)"
+                                },
+                                "currency": {
+                                    "type": "string",
+                                    "description": "通貨コード (例: USD)。省略時は基準通貨"
+                                },
+                                "amount": {
+                                    "type": "number",
+                                    "description": "currency での金額 (小数可)"
+                                },
+                                "payer": {
+                                    "type": "string",
+                                    "description": "支払者"
+                                },
+                                "participants": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "割り勘の参加者 (省略時は支払者のみ)"
+                                },
+                                "split": {
+                                    "type": "object",
+                                    "description": "割り勘の方法",
+                                    "properties": {
+                                        "type": {
+                                            "type": "string",
+                                            "enum": ["equal", "ratio", "exact"],
+                                            "description": "equal: 均等 , ratio: 比率 , exact: 金額指定 (既定 equal)"
+                                        },
+                                        "values": {
+                                            "type": "object",
+                                            "additionalProperties": { "type": "number" },
+                                            "description": "ratio: 参加者ごとの比率 , exact: 参加者ごとの金額"
+                                        }
+                                    }
                                 }
                             },
-                            "required": ["name", "price"]
+                            "required": ["name"]
                         }
                     },
                     {
@@ -104,10 +206,118 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                         "inputSchema": {
                             "type": "object",
                             "properties": {
+                                "from": {
+                                    "type": "string",
+                                    "description": "購入日の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "購入日の終了 (YYYY-MM-DD)"
+                                },
+                                "name": {
+                                    "type": "string",
+                                    "description": "品名 (部分一致)"
+                                },
+                                "user": {
+                                    "type": "string",
+                                    "description": "利用者。支払者、または割り勘の参加者の購入データのみ表示します"
+                                },
+                                "limit": {
+                                    "type": "number",
+                                    "description": "件数"
+                                }
                             },
                             "required": []
                         }
                     },                    
+                    {
+                        "name": "purchase_export",
+                        "description": "purchase_list と同じ条件で、購入品リストを CSV / JSON に出力します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "format": {
+                                    "type": "string",
+                                    "enum": ["csv", "json"],
+                                    "description": "出力形式 (既定 csv)"
+                                },
+                                "path": {
+                                    "type": "string",
+                                    "description": "出力ファイルのパス。省略時は結果を返します"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "購入日の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "購入日の終了 (YYYY-MM-DD)"
+                                },
+                                "name": {
+                                    "type": "string",
+                                    "description": "品名 (部分一致)"
+                                },
+                                "user": {
+                                    "type": "string",
+                                    "description": "利用者。支払者、または割り勘の参加者の購入データのみ表示します"
+                                },
+                                "limit": {
+                                    "type": "number",
+                                    "description": "件数"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "purchase_import_csv",
+                        "description": "カード / 銀行明細の CSV を読み込み、購入データとして登録します。既存データと重複する行は登録しません。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "csv": {
+                                    "type": "string",
+                                    "description": "CSV の内容"
+                                },
+                                "path": {
+                                    "type": "string",
+                                    "description": "CSV ファイルのパス (csv 省略時)"
+                                },
+                                "delimiter": {
+                                    "type": "string",
+                                    "description": "区切り文字 (既定 ,)"
+                                },
+                                "has_header": {
+                                    "type": "boolean",
+                                    "description": "1行目がヘッダーの場合 true (既定 true)"
+                                },
+                                "columns": {
+                                    "type": "object",
+                                    "description": "列の割り当て。0 始まりの列番号、またはヘッダー名 (既定 date: 0, name: 1, price: 2)",
+                                    "properties": {
+                                        "date": {
+                                            "type": ["number", "string"],
+                                            "description": "日付の列"
+                                        },
+                                        "name": {
+                                            "type": ["number", "string"],
+                                            "description": "品名の列"
+                                        },
+                                        "price": {
+                                            "type": ["number", "string"],
+                                            "description": "金額の列"
+                                        }
+                                    },
+                                    "required": ["date", "name", "price"]
+                                },
+                                "dry_run": {
+                                    "type": "boolean",
+                                    "description": "true の場合、登録せずに結果のみ表示します"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
                     {
                         "name": "purchase_delete",
                         "description": "指定ID 値を受け取り、購入データを削除します。",
@@ -122,26 +332,605 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                             "required": ["id"]
                         }
                     },
+                    {
+                        "name": "purchase_receipt",
+                        "description": "店舗、日付、複数の明細行(品名、数量、単価、税率)を受け取り、税込合計を計算して1件のレシートとして登録します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "store": {
+                                    "type": "string",
+                                    "description": "店舗名"
+                                },
+                                "date": {
+                                    "type": "string",
+                                    "description": "購入日 (YYYY-MM-DD)"
+                                },
+                                "tax_included": {
+                                    "type": "boolean",
+                                    "description": "単価が税込の場合 true (既定 false: 税抜)"
+                                },
+                                "items": {
+                                    "type": "array",
+                                    "description": "明細行",
+                                    "items": {
+                                        "type": "object",
+                                        "properties": {
+                                            "name": {
+                                                "type": "string",
+                                                "description": "品名"
+                                            },
+                                            "quantity": {
+                                                "type": "number",
+                                                "description": "数量 (既定 1)"
+                                            },
+                                            "unit_price": {
+                                                "type": "number",
+                                                "description": "単価"
+                                            },
+                                            "tax_rate": {
+                                                "type": "number",
+                                                "enum": [8, 10],
+                                                "description": "税率 8: 軽減税率 , 10: 標準税率 (既定 8)"
+                                            }
+                                        },
+                                        "required": ["name", "unit_price"]
+                                    }
+                                }
+                            },
+                            "required": ["store", "date", "items"]
+                        }
+                    },
+                    {
+                        "name": "item_add",
+                        "description": "品目カタログに、品名と別名を登録します。(例: コーヒー , 別名: 珈琲 , coffee) 別名が他の品目に登録済みの場合はエラーになります (item_merge で統合)。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "name": {
+                                    "type": "string",
+                                    "description": "品名"
+                                },
+                                "aliases": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "別名"
+                                }
+                            },
+                            "required": ["name"]
+                        }
+                    },
+                    {
+                        "name": "item_list",
+                        "description": "品目カタログと別名の一覧を、表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {},
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "item_merge",
+                        "description": "品目 id を into_id に統合します。別名と購入データも統合先に移します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "統合元の品目 ID"
+                                },
+                                "into_id": {
+                                    "type": "number",
+                                    "description": "統合先の品目 ID"
+                                }
+                            },
+                            "required": ["id", "into_id"]
+                        }
+                    },
+                    {
+                        "name": "item_price_history",
+                        "description": "品目の価格推移を、全体と店舗別に表示します。今日の価格が通常より高い場合に知らせます。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "name": {
+                                    "type": "string",
+                                    "description": "品名 (別名可)"
+                                },
+                                "store": {
+                                    "type": "string",
+                                    "description": "店舗名"
+                                },
+                                "price": {
+                                    "type": "number",
+                                    "description": "今日の価格。省略時は今日の購入データと比較します"
+                                }
+                            },
+                            "required": ["name"]
+                        }
+                    },
+                    {
+                        "name": "recurring_add",
+                        "description": "定期購入 (サブスクリプション) を登録します。支払日を過ぎた分は購入データとして自動で登録されます。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "name": {
+                                    "type": "string",
+                                    "description": "品名"
+                                },
+                                "price": {
+                                    "type": "number",
+                                    "description": "金額"
+                                },
+                                "schedule": {
+                                    "type": "string",
+                                    "enum": ["monthly", "yearly", "days"],
+                                    "description": "monthly: 毎月 , yearly: 毎年 , days: interval_days 日ごと"
+                                },
+                                "interval_days": {
+                                    "type": "number",
+                                    "description": "schedule = days の間隔 (日)"
+                                },
+                                "start_date": {
+                                    "type": "string",
+                                    "description": "開始日 (YYYY-MM-DD)"
+                                },
+                                "next_due": {
+                                    "type": "string",
+                                    "description": "次回の支払日 (YYYY-MM-DD)。省略時は今日以降の最初の支払日"
+                                },
+                                "end_date": {
+                                    "type": "string",
+                                    "description": "終了日 (YYYY-MM-DD)"
+                                }
+                            },
+                            "required": ["name", "price", "schedule", "start_date"]
+                        }
+                    },
+                    {
+                        "name": "recurring_list",
+                        "description": "定期購入の一覧を、表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {},
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "recurring_delete",
+                        "description": "指定ID の定期購入を削除します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "ID"
+                                }
+                            },
+                            "required": ["id"]
+                        }
+                    },
+                    {
+                        "name": "recurring_catchup",
+                        "description": "支払日を過ぎた定期購入を、購入データとして登録します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {},
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "recurring_upcoming",
+                        "description": "今後の定期購入の支払予定と、1か月あたりの固定費を表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "days": {
+                                    "type": "number",
+                                    "description": "表示する日数 (既定 30)"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "purchase_split",
+                        "description": "指定ID の購入データに、支払者と割り勘を設定します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "ID"
+                                },
+                                "payer": {
+                                    "type": "string",
+                                    "description": "支払者"
+                                },
+                                "participants": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "割り勘の参加者 (省略時は支払者のみ)"
+                                },
+                                "split": {
+                                    "type": "object",
+                                    "description": "割り勘の方法",
+                                    "properties": {
+                                        "type": {
+                                            "type": "string",
+                                            "enum": ["equal", "ratio", "exact"],
+                                            "description": "equal: 均等 , ratio: 比率 , exact: 金額指定 (既定 equal)"
+                                        },
+                                        "values": {
+                                            "type": "object",
+                                            "additionalProperties": { "type": "number" },
+                                            "description": "ratio: 参加者ごとの比率 , exact: 参加者ごとの金額"
+                                        }
+                                    }
+                                }
+                            },
+                            "required": ["id", "payer"]
+                        }
+                    },
+                    {
+                        "name": "settle_up",
+                        "description": "期間内の立て替えを集計し、精算に必要な最小回数の送金を表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "from": {
+                                    "type": "string",
+                                    "description": "購入日の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "購入日の終了 (YYYY-MM-DD)"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "exchange_rate_set",
+                        "description": "為替レート (1 通貨 = rate 基準通貨) を登録します。同じ通貨、日付のレートは上書きします。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "currency": {
+                                    "type": "string",
+                                    "description": "通貨コード (例: USD)"
+                                },
+                                "date": {
+                                    "type": "string",
+                                    "description": "適用日 (YYYY-MM-DD)"
+                                },
+                                "rate": {
+                                    "type": "number",
+                                    "description": "レート"
+                                }
+                            },
+                            "required": ["currency", "date", "rate"]
+                        }
+                    },
+                    {
+                        "name": "exchange_rate_import_csv",
+                        "description": "日付 , 通貨 , レート の CSV から、為替レートを登録します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "csv": {
+                                    "type": "string",
+                                    "description": "CSV の内容"
+                                },
+                                "path": {
+                                    "type": "string",
+                                    "description": "CSV ファイルのパス (csv 省略時)"
+                                },
+                                "has_header": {
+                                    "type": "boolean",
+                                    "description": "1行目がヘッダーか (既定 true)"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "exchange_rate_list",
+                        "description": "登録済みの為替レートを表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "currency": {
+                                    "type": "string",
+                                    "description": "通貨コード"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
+                    {
+                        "name": "purchase_summary",
+                        "description": "期間内の購入を通貨ごとに集計し、購入日のレートで基準通貨に換算した合計を表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "from": {
+                                    "type": "string",
+                                    "description": "購入日の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "購入日の終了 (YYYY-MM-DD)"
+                                }
+                            },
+                            "required": []
+                        }
+                    },
                     {
                         "name": "diary_add",
                         "description": "2行目以降の 日記の記事、メモ を取得して。APIに送信します。",
                         "inputSchema": {
                             "type": "object",
                             "properties": {
-                                "text": {
+                                "title": {
+                                    "type": "string",
+                                    "description": "タイトル"
+                                },
+                                "body": {
+                                    "type": "string",
+                                    "description": "日記の記事"
+                                },
+                                "date": {
+                                    "type": "string",
+                                    "description": "日記の日付 (YYYY-MM-DD)。省略時は今日"
+                                },
+                                "tags": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "タグ"
+                                },
+                                "mood": {
+                                    "type": "string",
+                                    "description": "気分 (例: 良い , 普通 , 疲れた)"
+                                },
+                                "rating": {
+                                    "type": "number",
+                                    "description": "評価 (1 - 5)"
+                                }
+                            },
+                            "required": ["body"]
+                        }
+                    },
+                    {
+                        "name": "diary_get",
+                        "description": "id の日記を表示します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "日記の id"
+                                }
+                            },
+                            "required": ["id"]
+                        }
+                    },
+                    {
+                        "name": "diary_update",
+                        "description": "id の日記を更新します。指定した項目のみ変更します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "日記の id"
+                                },
+                                "title": {
+                                    "type": "string",
+                                    "description": "タイトル"
+                                },
+                                "body": {
                                     "type": "string",
                                     "description": "日記の記事"
                                 },
+                                "date": {
+                                    "type": "string",
+                                    "description": "日記の日付 (YYYY-MM-DD)。過去の日付に変更できます"
+                                },
+                                "tags": {
+                                    "type": "array",
+                                    "items": { "type": "string" },
+                                    "description": "タグ"
+                                },
+                                "mood": {
+                                    "type": "string",
+                                    "description": "気分 (例: 良い , 普通 , 疲れた)"
+                                },
+                                "rating": {
+                                    "type": "number",
+                                    "description": "評価 (1 - 5)"
+                                }
+                            },
+                            "required": ["id"]
+                        }
+                    },
+                    {
+                        "name": "diary_delete",
+                        "description": "id の日記を削除します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "id": {
+                                    "type": "number",
+                                    "description": "日記の id"
+                                }
+                            },
+                            "required": ["id"]
+                        }
+                    },
+                    {
+                        "name": "diary_search",
+                        "description": "日記を全文検索し、関連度の高い順に日付と該当箇所を表示します。空白区切りの語は、すべて含む日記を検索します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "query": {
+                                    "type": "string",
+                                    "description": "検索語"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                },
+                                "limit": {
+                                    "type": "number",
+                                    "description": "件数 (既定 10)"
+                                }
                             },
-                            "required": ["text"]
+                            "required": ["query"]
+                        }
+                    },
+                    {
+                        "name": "diary_recall",
+                        "description": "質問文に意味の近い日記を検索し、近い順に日付と該当箇所を表示します。例: 最後に山登りに行ったのはいつ?",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "query": {
+                                    "type": "string",
+                                    "description": "質問文"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                },
+                                "max_distance": {
+                                    "type": "number",
+                                    "description": "コサイン距離の上限 (0 - 2)。省略時は上限なし"
+                                },
+                                "limit": {
+                                    "type": "number",
+                                    "description": "件数 (既定 5)"
+                                }
+                            },
+                            "required": ["query"]
+                        }
+                    },
+                    {
+                        "name": "diary_export",
+                        "description": "日記を日付ごとに、Obsidian の vault のフォルダへ YYYY-MM-DD.md で出力します。tags , mood は frontmatter に出力します。diary_export が出力したもの以外の note は上書きしません。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "出力先のフォルダ"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    {
+                        "name": "diary_import",
+                        "description": "Obsidian の vault のフォルダの YYYY-MM-DD.md を読み込み、日記を登録します。frontmatter の id が一致する日記は更新します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "読み込むフォルダ"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                },
+                                "dry_run": {
+                                    "type": "boolean",
+                                    "description": "true の場合、登録せずに結果のみ表示します"
+                                }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    {
+                        "name": "diary_digest",
+                        "description": "期間の日記を日付ごとにまとめ、件数、タグ、気分を集計します。summarize が true の場合、LLM で期間を要約します。",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "period": {
+                                    "type": "string",
+                                    "enum": ["week", "month"],
+                                    "description": "期間 (既定 week)。週は月曜始まり"
+                                },
+                                "date": {
+                                    "type": "string",
+                                    "description": "期間に含む日付 (YYYY-MM-DD)。省略時は今日"
+                                },
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)。to と合わせて指定した場合は period より優先"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                },
+                                "summarize": {
+                                    "type": "boolean",
+                                    "description": "true の場合、LLM で要約します (既定 false)"
+                                },
+                                "refresh": {
+                                    "type": "boolean",
+                                    "description": "true の場合、キャッシュを使わずに要約を作り直します"
+                                }
+                            },
+                            "required": []
                         }
                     },
                     {
                         "name": "diary_list",
-                        "description": "日記 記事リストを、表示します。",
+                        "description": "日記 記事リストを、日付の新しい順に表示します。",
                         "inputSchema": {
                             "type": "object",
-                            "properties": {},
+                            "properties": {
+                                "from": {
+                                    "type": "string",
+                                    "description": "日付の開始 (YYYY-MM-DD)"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "日付の終了 (YYYY-MM-DD)"
+                                },
+                                "tag": {
+                                    "type": "string",
+                                    "description": "タグ"
+                                },
+                                "limit": {
+                                    "type": "number",
+                                    "description": "件数 (既定 5)"
+                                }
+                            },
                             "required": []
                         }
                     },
@@ -159,8 +948,60 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                         mod_purchase::purchase_list_handler(params, request.id).await
                     } else if tool_name == "purchase_delete"{
                         mod_purchase::purchase_delete_handler(params, request.id).await
+                    } else if tool_name == "purchase_export"{
+                        mod_purchase::purchase_export_handler(params, request.id).await
+                    } else if tool_name == "purchase_import_csv"{
+                        mod_import::purchase_import_csv_handler(params, request.id).await
+                    } else if tool_name == "purchase_receipt"{
+                        mod_receipt::purchase_receipt_handler(params, request.id).await
+                    } else if tool_name == "item_add"{
+                        mod_item::item_add_handler(params, request.id).await
+                    } else if tool_name == "item_list"{
+                        mod_item::item_list_handler(params, request.id).await
+                    } else if tool_name == "item_merge"{
+                        mod_item::item_merge_handler(params, request.id).await
+                    } else if tool_name == "item_price_history"{
+                        mod_item::item_price_history_handler(params, request.id).await
+                    } else if tool_name == "recurring_add"{
+                        mod_recurring::recurring_add_handler(params, request.id).await
+                    } else if tool_name == "recurring_list"{
+                        mod_recurring::recurring_list_handler(params, request.id).await
+                    } else if tool_name == "recurring_delete"{
+                        mod_recurring::recurring_delete_handler(params, request.id).await
+                    } else if tool_name == "recurring_catchup"{
+                        mod_recurring::recurring_catchup_handler(params, request.id).await
+                    } else if tool_name == "recurring_upcoming"{
+                        mod_recurring::recurring_upcoming_handler(params, request.id).await
+                    } else if tool_name == "purchase_split"{
+                        mod_ledger::purchase_split_handler(params, request.id).await
+                    } else if tool_name == "settle_up"{
+                        mod_ledger::settle_up_handler(params, request.id).await
+                    } else if tool_name == "exchange_rate_set"{
+                        mod_currency::exchange_rate_set_handler(params, request.id).await
+                    } else if tool_name == "exchange_rate_import_csv"{
+                        mod_currency::exchange_rate_import_csv_handler(params, request.id).await
+                    } else if tool_name == "exchange_rate_list"{
+                        mod_currency::exchange_rate_list_handler(params, request.id).await
+                    } else if tool_name == "purchase_summary"{
+                        mod_currency::purchase_summary_handler(params, request.id).await
                     } else if tool_name == "diary_add"{
                         mod_diary::diary_add_handler(params, request.id).await
+                    } else if tool_name == "diary_get"{
+                        mod_diary::diary_get_handler(params, request.id).await
+                    } else if tool_name == "diary_update"{
+                        mod_diary::diary_update_handler(params, request.id).await
+                    } else if tool_name == "diary_delete"{
+                        mod_diary::diary_delete_handler(params, request.id).await
+                    } else if tool_name == "diary_search"{
+                        mod_diary_search::diary_search_handler(params, request.id).await
+                    } else if tool_name == "diary_recall"{
+                        mod_diary_embed::diary_recall_handler(params, request.id).await
+                    } else if tool_name == "diary_export"{
+                        mod_diary_note::diary_export_handler(params, request.id).await
+                    } else if tool_name == "diary_import"{
+                        mod_diary_note::diary_import_handler(params, request.id).await
+                    } else if tool_name == "diary_digest"{
+                        mod_diary_digest::diary_digest_handler(params, request.id).await
                     } else if tool_name == "diary_list"{
                         mod_diary::diary_list_handler(params, request.id).await
                     } else {
@@ -211,10 +1052,17 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if mod_diary_note::run_cli(&args[1..]).await {
+        return;
+    }
+
     let stdin = io::stdin();
     let mut stdout = io::stdout();
     
     eprintln!("MCP Server started. Waiting for requests...");
+    tokio::spawn(mod_recurring::catchup_on_start());
+    tokio::spawn(mod_diary::migrate_on_start());
 
     for line in stdin.lock().lines() {
         match line {
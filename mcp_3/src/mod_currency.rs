@@ -0,0 +1,319 @@
+use libsql::Connection;
+use libsql::params;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use dotenvy::dotenv;
+use super::{connect, error_response, text_response};
+
+// currency の無い購入データは、円建て (price) として扱う
+pub const LEGACY_CURRENCY: &str = "JPY";
+
+/**
+* 基準通貨。BASE_CURRENCY (既定 JPY)
+*/
+pub fn base_currency() -> String {
+    dotenv().ok();
+    let currency = env::var("BASE_CURRENCY").unwrap_or(LEGACY_CURRENCY.to_string()).to_uppercase();
+    if is_currency_code(&currency) { currency } else { LEGACY_CURRENCY.to_string() }
+}
+
+/**
+* ISO 4217 形式 (英大文字3桁) の通貨コードか
+*/
+pub fn is_currency_code(currency: &str) -> bool {
+    currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/**
+* item_price の行の通貨の SQL 式。通貨の無い行は LEGACY_CURRENCY とする。
+*
+* @return
+*/
+pub fn currency_sql() -> String {
+    format!("COALESCE(json_extract(item_price.data, '$.currency'), '{}')", LEGACY_CURRENCY)
+}
+
+/**
+* item_price の行の、通貨での金額の SQL 式。amount の無い行は price とする。
+* price は整数で保存されているため、REAL にして f64 で読めるようにする。
+*
+* @return
+*/
+pub fn amount_sql() -> String {
+    "CAST(COALESCE(json_extract(item_price.data, '$.amount'), json_extract(item_price.data, '$.price')) AS REAL)".to_string()
+}
+
+/**
+* item_price の行を、購入日のレートで基準通貨に換算する SQL 式。
+* レートは購入日以前で最も新しいものを使い、基準通貨以外でレートが無い場合は NULL (レート未登録) とする。
+* 通貨の無い行は LEGACY_CURRENCY の金額として換算する。
+*
+* @return
+*/
+pub fn base_price_sql() -> String {
+    let base = base_currency();
+    format!("(CASE WHEN COALESCE(json_extract(item_price.data, '$.currency'), '{legacy}') = '{base}'
+    THEN CAST(ROUND(COALESCE(json_extract(item_price.data, '$.amount'), json_extract(item_price.data, '$.price'))) AS INTEGER)
+    ELSE CAST(ROUND(
+        COALESCE(json_extract(item_price.data, '$.amount'), json_extract(item_price.data, '$.price'))
+        * (SELECT r.rate FROM exchange_rate r
+            WHERE r.currency = COALESCE(json_extract(item_price.data, '$.currency'), '{legacy}')
+            AND r.rate_date <= COALESCE(json_extract(item_price.data, '$.date'), date(item_price.created_at))
+            ORDER BY r.rate_date DESC LIMIT 1)
+    ) AS INTEGER)
+    END)", legacy = LEGACY_CURRENCY, base = base)
+}
+
+/**
+* date 以前で最も新しいレート (1 currency = rate 基準通貨)
+*
+* @param conn
+* @param currency 通貨コード
+* @param date YYYY-MM-DD
+*
+* @return
+*/
+pub async fn find_rate(conn: &Connection, currency: &str, date: &str) -> Result<Option<f64>, libsql::Error> {
+    if currency == base_currency() {
+        return Ok(Some(1.0));
+    }
+    let mut rows = conn.query(
+        "SELECT rate FROM exchange_rate WHERE currency = ?1 AND rate_date <= ?2 ORDER BY rate_date DESC LIMIT 1",
+        params![currency, date],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+async fn set_rate(conn: &Connection, currency: &str, date: &str, rate: f64) -> Result<(), libsql::Error> {
+    conn.execute(
+        "INSERT INTO exchange_rate (currency, rate_date, rate) VALUES (?1, ?2, ?3)
+        ON CONFLICT(currency, rate_date) DO UPDATE SET rate = excluded.rate, updated_at = CURRENT_TIMESTAMP",
+        params![currency, date, rate],
+    ).await?;
+    Ok(())
+}
+
+/**
+* exchange_rate_set: 為替レートを登録する。同じ通貨、日付のレートは上書きする。
+*
+* @param
+*
+* @return
+*/
+pub async fn exchange_rate_set_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RateSetParams {
+        currency: String,
+        date: String,
+        rate: f64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<RateSetParams>(arguments.clone()) {
+            Ok(rate_params) => {
+                let currency = rate_params.currency.to_uppercase();
+                if !is_currency_code(&currency) || rate_params.rate <= 0.0 {
+                    return error_response(request_id, -32602,
+                        format!("Invalid parameters, currency={} , rate={}", rate_params.currency, rate_params.rate));
+                }
+                let date = match super::mod_import::parse_date(&rate_params.date) {
+                    Some(date) => date,
+                    None => return error_response(request_id, -32602, format!("Invalid parameters, date={}", rate_params.date)),
+                };
+                let conn = connect().await;
+                set_rate(&conn, &currency, &date, rate_params.rate).await.unwrap();
+                return text_response(request_id,
+                    format!("Complete exchange_rate_set, {} {} = {} {}", date, currency, rate_params.rate, base_currency()));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* exchange_rate_import_csv: 日付 , 通貨 , レート の CSV から、為替レートを登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn exchange_rate_import_csv_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RateImportParams {
+        csv: Option<String>,
+        path: Option<String>,
+        #[serde(default = "default_true")]
+        has_header: bool,
+    }
+    fn default_true() -> bool {
+        true
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<RateImportParams>(arguments.clone()) {
+            Ok(import_params) => {
+                let csv_text = match (&import_params.csv, &import_params.path) {
+                    (Some(csv_text), _) => csv_text.clone(),
+                    (None, Some(path)) => match std::fs::read_to_string(path) {
+                        Ok(csv_text) => csv_text,
+                        Err(e) => return error_response(request_id, -32602, format!("File read error: {}, path={}", e, path)),
+                    },
+                    (None, None) => {
+                        return error_response(request_id, -32602, "Invalid parameters: csv or path is required".to_string());
+                    }
+                };
+                let mut reader = csv::ReaderBuilder::new()
+                    .has_headers(import_params.has_header)
+                    .flexible(true)
+                    .from_reader(csv_text.trim_start_matches('\u{FEFF}').as_bytes());
+
+                let mut rates: Vec<(String, String, f64)> = Vec::new();
+                let mut errors: Vec<String> = Vec::new();
+                let first_line = if import_params.has_header { 2 } else { 1 };
+                for (i, record) in reader.records().enumerate() {
+                    let line_no = first_line + i;
+                    let record = match record {
+                        Ok(record) => record,
+                        Err(e) => {
+                            errors.push(format!("line {}: CSV error: {}", line_no, e));
+                            continue;
+                        }
+                    };
+                    let date = super::mod_import::parse_date(record.get(0).unwrap_or(""));
+                    let currency = record.get(1).unwrap_or("").trim().to_uppercase();
+                    let rate = super::mod_import::to_half_width(record.get(2).unwrap_or("")).trim().replace(',', "").parse::<f64>();
+                    match (date, rate) {
+                        (Some(date), Ok(rate)) if is_currency_code(&currency) && rate > 0.0 => rates.push((date, currency, rate)),
+                        _ => errors.push(format!("line {}: error , {}", line_no, record.iter().collect::<Vec<&str>>().join(","))),
+                    }
+                }
+
+                let conn = connect().await;
+                let tx = conn.transaction().await.unwrap();
+                for (date, currency, rate) in &rates {
+                    if let Err(e) = set_rate(&tx, currency, date, *rate).await {
+                        tx.rollback().await.ok();
+                        return error_response(request_id, -32603, format!("Insert error: {}", e));
+                    }
+                }
+                tx.commit().await.unwrap();
+                let mut out_str = String::new();
+                for e in &errors {
+                    out_str.push_str(e);
+                    out_str.push('\n');
+                }
+                out_str.push_str(&format!("insert: {} , error: {}", rates.len(), errors.len()));
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* exchange_rate_list: 為替レートの一覧
+*
+* @param
+*
+* @return
+*/
+pub async fn exchange_rate_list_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RateListParams {
+        currency: Option<String>,
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let list_params = match serde_json::from_value::<RateListParams>(arguments) {
+        Ok(list_params) => list_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let conn = connect().await;
+    let mut rows = conn.query(
+        "SELECT currency, rate_date, rate FROM exchange_rate
+        WHERE ?1 IS NULL OR currency = ?1
+        ORDER BY currency, rate_date DESC LIMIT 100",
+        params![list_params.currency.map(|c| c.to_uppercase())],
+    ).await.unwrap();
+    let mut out_str = format!("基準通貨: {}\n", base_currency());
+    while let Some(row) = rows.next().await.unwrap() {
+        let currency: String = row.get(0).unwrap();
+        let rate_date: String = row.get(1).unwrap();
+        let rate: f64 = row.get(2).unwrap();
+        out_str.push_str(&format!("{} , {} , {}\n", rate_date, currency, rate));
+    }
+    text_response(request_id, out_str)
+}
+
+/**
+* purchase_summary: 期間内の購入を通貨ごとに集計し、基準通貨に換算した合計を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_summary_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct SummaryParams {
+        from: Option<String>,
+        to: Option<String>,
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let summary_params = match serde_json::from_value::<SummaryParams>(arguments) {
+        Ok(summary_params) => summary_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let date_sql = super::mod_purchase::PURCHASE_DATE_SQL;
+    let sql = format!("SELECT {currency}, {amount}, {base_price}
+    FROM item_price
+    WHERE (?1 IS NULL OR {date} >= ?1) AND (?2 IS NULL OR {date} <= ?2)
+    ", currency = currency_sql(), amount = amount_sql(), base_price = base_price_sql(), date = date_sql);
+    let conn = connect().await;
+    let mut rows = conn.query(&sql, params![summary_params.from.clone(), summary_params.to.clone()]).await.unwrap();
+
+    // 通貨ごとの (件数, 金額, 換算額, 換算できない件数)
+    let mut by_currency: BTreeMap<String, (i64, f64, i64, i64)> = BTreeMap::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        let currency: String = row.get(0).unwrap();
+        let amount: f64 = row.get::<Option<f64>>(1).unwrap().unwrap_or(0.0);
+        let base_price: Option<i64> = row.get(2).unwrap();
+        let entry = by_currency.entry(currency).or_insert((0, 0.0, 0, 0));
+        entry.0 += 1;
+        entry.1 += amount;
+        match base_price {
+            Some(base_price) => entry.2 += base_price,
+            None => entry.3 += 1,
+        }
+    }
+
+    let base = base_currency();
+    let mut out_str = format!("期間: {} - {}\n",
+        summary_params.from.unwrap_or("-".to_string()), summary_params.to.unwrap_or("-".to_string()));
+    let mut total = 0;
+    for (currency, (count, amount, base_price, missing)) in &by_currency {
+        out_str.push_str(&format!("{} , 件数 {} , 合計 {} {} , 換算 {} {}", currency, count, amount, currency, base_price, base));
+        if *missing > 0 {
+            out_str.push_str(&format!(" (レート未登録 {} 件)", missing));
+        }
+        out_str.push('\n');
+        total += base_price;
+    }
+    out_str.push_str(&format!("合計 {} {}", total, base));
+    text_response(request_id, out_str)
+}
@@ -1,185 +1,407 @@
-use libsql::Database;
-use libsql::Builder;
 use libsql::Connection;
 use libsql::params;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::env;
-use std::io::{self, BufRead, Write};
 use dotenvy::dotenv;
+use super::{connect, error_response, text_response};
 
-#[derive(Debug, Deserialize)]
-struct AddTenParams {
-    value: i32,
+/// mcp_diary.data の内容。旧形式 {"text": ...} は body として読み込む
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiaryEntry {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, alias = "text")]
+    pub body: String,
+    // 日記の日付 (YYYY-MM-DD)。過去の日付も指定できる
+    #[serde(default)]
+    pub entry_date: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub mood: Option<String>,
+    pub rating: Option<i64>,
 }
 
-#[derive(Debug, Deserialize,Serialize)]
-struct DiaryParams {
-    text: String,
+// 日記の日付: data.entry_date が無い行は登録日とする
+pub const DIARY_DATE_SQL: &str = "COALESCE(json_extract(data, '$.entry_date'), date(created_at))";
+
+/**
+* タグを整形する。前後の空白と先頭の # を除き、重複を除く。
+*
+* @param tags
+*
+* @return
+*/
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').trim().to_string();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Item {
-    id: i64,
-    data: String,
-    created_at: String,
-    updated_at: String,
+/**
+* 日記の1件を表示用の文字列にする。
+*
+* @param id
+* @param entry
+*
+* @return
+*/
+pub fn format_entry(id: i64, entry: &DiaryEntry) -> String {
+    let mut out_str = format!("id: {} , date= {}", id, entry.entry_date);
+    if !entry.title.is_empty() {
+        out_str.push_str(&format!(" , title= {}", entry.title));
+    }
+    if !entry.tags.is_empty() {
+        out_str.push_str(&format!(" , tags= {}", entry.tags.join(",")));
+    }
+    if let Some(mood) = &entry.mood {
+        out_str.push_str(&format!(" , mood= {}", mood));
+    }
+    if let Some(rating) = entry.rating {
+        out_str.push_str(&format!(" , rating= {}", rating));
+    }
+    format!("{}\n{}\n", out_str, entry.body)
+}
+
+fn check_rating(rating: Option<i64>) -> Result<(), String> {
+    match rating {
+        Some(rating) if !(1..=5).contains(&rating) => Err(format!("rating={} (1 - 5)", rating)),
+        _ => Ok(()),
+    }
+}
+
+fn parse_entry_date(date: Option<&String>) -> Result<String, String> {
+    match date {
+        Some(date) => super::mod_import::parse_date(date).ok_or(format!("date={}", date)),
+        None => Ok(chrono::Local::now().format("%Y-%m-%d").to_string()),
+    }
 }
-#[derive(Debug, Deserialize,Serialize)]
-struct PurchaseDeleteParams {
-    id: i32,
+
+/**
+* id の日記を読み込む。旧形式の行は、登録日を entry_date とする。
+*
+* @param conn
+* @param id
+*
+* @return
+*/
+pub async fn find_entry(conn: &Connection, id: i64) -> Result<Option<DiaryEntry>, libsql::Error> {
+    let sql = format!("SELECT data, {} FROM mcp_diary WHERE id = ?1", DIARY_DATE_SQL);
+    let mut rows = conn.query(&sql, params![id]).await?;
+    match rows.next().await? {
+        Some(row) => {
+            let data: String = row.get(0)?;
+            let mut entry: DiaryEntry = serde_json::from_str(&data).unwrap_or_default();
+            entry.entry_date = row.get(1)?;
+            Ok(Some(entry))
+        }
+        None => Ok(None),
+    }
 }
 
+/**
+* 旧形式 {"text": ...} の行を、title , body , entry_date , tags , mood の形式に変換する。
+* 1行目を title とし、entry_date は登録日とする。
+*
+* @param conn
+*
+* @return 変換した件数
+*/
+pub async fn migrate(conn: &Connection) -> Result<usize, libsql::Error> {
+    let mut rows = conn.query(
+        "SELECT id, data, date(created_at) FROM mcp_diary WHERE json_valid(data) = 0 OR json_extract(data, '$.entry_date') IS NULL",
+        (),
+    ).await?;
+    let mut targets: Vec<(i64, DiaryEntry)> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id: i64 = row.get(0)?;
+        let data: String = row.get(1)?;
+        let mut entry: DiaryEntry = serde_json::from_str(&data).unwrap_or(DiaryEntry {
+            body: data.clone(),
+            ..Default::default()
+        });
+        if entry.title.is_empty() {
+            entry.title = entry.body.lines().next().unwrap_or("").chars().take(40).collect();
+        }
+        entry.entry_date = row.get(2)?;
+        targets.push((id, entry));
+    }
+    let tx = conn.transaction().await?;
+    for (id, entry) in &targets {
+        tx.execute(
+            "UPDATE mcp_diary SET data = ?1 WHERE id = ?2",
+            params![serde_json::to_string(entry).expect("JSON convert error"), *id],
+        ).await?;
+    }
+    tx.commit().await?;
+    Ok(targets.len())
+}
 
-pub fn purchase(product_name: String, price: i32) -> String {
-    format!("「{}」を{}円で購入しました。", product_name, price)
+/**
+* 起動時に、旧形式の日記を変換し、全文検索の索引と embedding を作成する。
+*/
+pub async fn migrate_on_start() {
+    dotenv().ok();
+    if env::var("TURSO_DATABASE_URL").is_err() || env::var("TURSO_AUTH_TOKEN").is_err() {
+        return;
+    }
+    let conn = connect().await;
+    match migrate(&conn).await {
+        Ok(count) => eprintln!("diary migrate: {} entries", count),
+        Err(e) => eprintln!("diary migrate error: {}", e),
+    }
+    match super::mod_diary_search::ensure_index(&conn).await {
+        Ok(count) => eprintln!("diary search index: {} entries", count),
+        Err(e) => eprintln!("diary search index error: {}", e),
+    }
+    match super::mod_diary_embed::ensure_embeddings(&conn).await {
+        Ok(count) => eprintln!("diary embedding: {} entries", count),
+        Err(e) => eprintln!("diary embedding error: {}", e),
+    }
 }
 
 
+/**
+* diary_add: 日記を登録する。date 省略時は今日の日付とする。
+*
+* @param
+*
+* @return
+*/
 pub async fn diary_add_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
 {
+    #[derive(Debug, Deserialize)]
+    struct DiaryAddParams {
+        #[serde(default)]
+        title: String,
+        #[serde(alias = "text")]
+        body: String,
+        date: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        mood: Option<String>,
+        rating: Option<i64>,
+    }
 
-    if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
-        if tool_name == "diary_add" {
-            dotenv().ok();
-            let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
-            let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
-            //let url = TURSO_DATABASE_URL.to_string();
-            //let token = TURSO_AUTH_TOKEN.to_string();
-            println!("TURSO_DATABASE_URL={}", url);
-            let db = Builder::new_remote(url, token).build().await.unwrap();
-            let conn = db.connect().unwrap();    
-
-            if let Some(arguments) = params.get("arguments") {
-                match serde_json::from_value::<DiaryParams>(arguments.clone()) {
-                    Ok(purchase_params) => {
-                        let post_data = DiaryParams {
-                            text: purchase_params.text.clone(),
-                        };    
-                        let json_string_variable = serde_json::to_string(&post_data).expect("JSON convert error");
-                        println!("変換されたJSON文字列: {}", json_string_variable); 
-                        let sql = format!("INSERT INTO mcp_diary (data) VALUES ('{}')", &json_string_variable);
-                        let mut result = conn
-                            .execute(&sql, ())
-                            .await
-                            .unwrap();
-
-                        return super::JsonRpcResponse {
-                            jsonrpc: "2.0".to_string(),
-                            id: request_id,
-                            result: Some(json!({
-                                "content": [
-                                    {
-                                        "type": "text",
-                                        "text": "OK".to_string()
-                                    }
-                                ]
-                            })),
-                            error: None,
-                        };                       
-
-                    }
-                    Err(e) => {
-                        return super::JsonRpcResponse {
-                            jsonrpc: "2.0".to_string(),
-                            id: request_id,
-                            result: None,
-                            error: Some(super::JsonRpcError {
-                                code: -32602,
-                                message: format!("Invalid parameters: {}", e),
-                            }),
-                        };
-                    }
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryAddParams>(arguments.clone()) {
+            Ok(add_params) => {
+                if let Err(e) = check_rating(add_params.rating) {
+                    return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
                 }
+                let entry_date = match parse_entry_date(add_params.date.as_ref()) {
+                    Ok(entry_date) => entry_date,
+                    Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+                };
+                let entry = DiaryEntry {
+                    title: add_params.title,
+                    body: add_params.body,
+                    entry_date,
+                    tags: normalize_tags(&add_params.tags),
+                    mood: add_params.mood,
+                    rating: add_params.rating,
+                };
+                let conn = connect().await;
+                conn.execute(
+                    "INSERT INTO mcp_diary (data) VALUES (?1)",
+                    params![serde_json::to_string(&entry).expect("JSON convert error")],
+                ).await.unwrap();
+                let id = conn.last_insert_rowid();
+                if let Err(e) = super::mod_diary_embed::embed_entry(&conn, id, &entry).await {
+                    eprintln!("diary embedding error: id={} , {}", id, e);
+                }
+                return text_response(request_id, format!("OK, id={} , date= {}", id, entry.entry_date));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
             }
         }
     }
-    super::JsonRpcResponse {
-        jsonrpc: "2.0".to_string(),
-        id: request_id,
-        result: None,
-        error: Some(super::JsonRpcError {
-            code: -32601,
-            message: "Tool not found".to_string(),
-        }),
-    }
+    error_response(request_id, -32601, "Tool not found".to_string())
 }
 
+/**
+* diary_get: id の日記を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_get_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryIdParams {
+        id: i64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryIdParams>(arguments.clone()) {
+            Ok(id_params) => {
+                let conn = connect().await;
+                return match find_entry(&conn, id_params.id).await.unwrap() {
+                    Some(entry) => text_response(request_id, format_entry(id_params.id, &entry)),
+                    None => error_response(request_id, -32602, format!("Invalid parameters, id={}", id_params.id)),
+                };
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
 
 /**
+* diary_update: id の日記を更新する。指定した項目のみ変更する。
 *
 * @param
 *
 * @return
 */
-pub async fn diary_list_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+pub async fn diary_update_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
 {
     #[derive(Debug, Deserialize)]
-    struct ItemData {
-        text: String,
-    }
-
-    let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
-    let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
-    //let url = TURSO_DATABASE_URL.to_string();
-    //let token = TURSO_AUTH_TOKEN.to_string();
-    println!("TURSO_DATABASE_URL={}", url);            
-    let db = Builder::new_remote(url, token).build().await.unwrap();
-    let conn = db.connect().unwrap();  
-    
-    if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
-        if tool_name == "diary_list" {
-            let order_sql = "ORDER BY created_at DESC LIMIT 5;";
-            let sql = format!("SELECT id, data ,created_at, updated_at 
-            FROM mcp_diary
-            {}
-            "
-            , order_sql
-            );
-            println!("sql={}", sql);
-            let mut rows = conn.query(&sql,
-                (),  // 引数なし
-            ).await.unwrap();
-            let mut todos: Vec<Item> = Vec::new();
-            let mut out_str: String = "".to_string();
-            while let Some(row) = rows.next().await.unwrap() {
-                let id: i64 = row.get(0).unwrap();
-                let data: String = row.get(1).unwrap();
-                todos.push(Item {
-                    id: id,
-                    data: data.clone(),
-                    created_at: row.get(2).unwrap(),
-                    updated_at: row.get(3).unwrap(),        
-                });  
-                let row_item: ItemData = serde_json::from_str(&data).expect("data JSON decord error");
-                println!("デコードされた構造体: {:?}", row_item);  
-                let row_str: String = format!("id: {}\n{}\n", id, row_item.text);    
-                println!("row_str: {:?}", row_str); 
-                out_str = format!("{}{}", &out_str, &row_str); 
+    struct DiaryUpdateParams {
+        id: i64,
+        title: Option<String>,
+        #[serde(alias = "text")]
+        body: Option<String>,
+        date: Option<String>,
+        tags: Option<Vec<String>>,
+        mood: Option<String>,
+        rating: Option<i64>,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryUpdateParams>(arguments.clone()) {
+            Ok(update_params) => {
+                if let Err(e) = check_rating(update_params.rating) {
+                    return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+                }
+                let conn = connect().await;
+                let mut entry = match find_entry(&conn, update_params.id).await.unwrap() {
+                    Some(entry) => entry,
+                    None => return error_response(request_id, -32602, format!("Invalid parameters, id={}", update_params.id)),
+                };
+                if update_params.date.is_some() {
+                    entry.entry_date = match parse_entry_date(update_params.date.as_ref()) {
+                        Ok(entry_date) => entry_date,
+                        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+                    };
+                }
+                if let Some(title) = update_params.title {
+                    entry.title = title;
+                }
+                if let Some(body) = update_params.body {
+                    entry.body = body;
+                }
+                if let Some(tags) = update_params.tags {
+                    entry.tags = normalize_tags(&tags);
+                }
+                if update_params.mood.is_some() {
+                    entry.mood = update_params.mood;
+                }
+                if update_params.rating.is_some() {
+                    entry.rating = update_params.rating;
+                }
+                conn.execute(
+                    "UPDATE mcp_diary SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+                    params![serde_json::to_string(&entry).expect("JSON convert error"), update_params.id],
+                ).await.unwrap();
+                if let Err(e) = super::mod_diary_embed::embed_entry(&conn, update_params.id, &entry).await {
+                    eprintln!("diary embedding error: id={} , {}", update_params.id, e);
+                }
+                return text_response(request_id, format!("Complete update\n{}", format_entry(update_params.id, &entry)));
             }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
 
-            return super::JsonRpcResponse {
-                jsonrpc: "2.0".to_string(),
-                id: request_id,
-                result: Some(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": out_str.to_string()
-                        }
-                    ]
-                })),
-                error: None,
-            };    
+/**
+* diary_delete: id の日記を削除する。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_delete_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryIdParams {
+        id: i64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryIdParams>(arguments.clone()) {
+            Ok(id_params) => {
+                let conn = connect().await;
+                let count = conn.execute("DELETE FROM mcp_diary WHERE id = ?1", params![id_params.id]).await.unwrap();
+                if count == 0 {
+                    return error_response(request_id, -32602, format!("Invalid parameters, id={}", id_params.id));
+                }
+                return text_response(request_id, format!("Complete delete , id={}", id_params.id));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
         }
     }
-    return super::JsonRpcResponse {
-        jsonrpc: "2.0".to_string(),
-        id: request_id,
-        result: None,
-        error: Some(super::JsonRpcError {
-            code: -32601,
-            message: "Tool not found".to_string(),
-        }),
-    };    
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+
+/**
+* diary_list: 日記の一覧。日付の範囲、タグで絞り込む。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_list_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryListParams {
+        from: Option<String>,
+        to: Option<String>,
+        tag: Option<String>,
+        #[serde(default = "default_limit")]
+        limit: i64,
+    }
+    fn default_limit() -> i64 {
+        5
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let list_params = match serde_json::from_value::<DiaryListParams>(arguments) {
+        Ok(list_params) => list_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let tag = list_params.tag.map(|tag| tag.trim().trim_start_matches('#').to_string());
+    let sql = format!("SELECT id, data, {date}
+    FROM mcp_diary
+    WHERE (?1 IS NULL OR {date} >= ?1) AND (?2 IS NULL OR {date} <= ?2)
+    AND (?3 IS NULL OR EXISTS (SELECT 1 FROM json_each(data, '$.tags') WHERE value = ?3))
+    ORDER BY 3 DESC, id DESC
+    LIMIT ?4
+    ", date = DIARY_DATE_SQL);
+    let conn = connect().await;
+    let mut rows = conn.query(&sql, params![list_params.from, list_params.to, tag, list_params.limit]).await.unwrap();
+    let mut out_str: String = "".to_string();
+    while let Some(row) = rows.next().await.unwrap() {
+        let id: i64 = row.get(0).unwrap();
+        let data: String = row.get(1).unwrap();
+        let mut entry: DiaryEntry = serde_json::from_str(&data).unwrap_or_default();
+        entry.entry_date = row.get(2).unwrap();
+        out_str.push_str(&format_entry(id, &entry));
+    }
+    text_response(request_id, out_str)
 }
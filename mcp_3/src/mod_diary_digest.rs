@@ -0,0 +1,316 @@
+use chrono::{Datelike, Duration, Local, Months, NaiveDate};
+use libsql::Connection;
+use libsql::params;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use dotenvy::dotenv;
+
+use super::mod_diary::{DiaryEntry, DIARY_DATE_SQL, format_entry};
+use super::{connect, error_response, text_response};
+
+// 期間ごとの要約のキャッシュ。source_hash は要約した日記の内容のハッシュで、日記が変わると作り直す
+pub const DIARY_DIGEST_SQL: &str = "
+CREATE TABLE IF NOT EXISTS mcp_diary_digest (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  from_date TEXT NOT NULL,
+  to_date TEXT NOT NULL,
+  source_hash TEXT NOT NULL,
+  summary TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(from_date, to_date)
+);
+";
+
+const OLLAMA_GENERATE_URL: &str = "http://localhost:11434/api/generate";
+const GEMINI_GENERATE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent";
+
+#[derive(Serialize)]
+struct OllamaRequest {
+    model: String,
+    prompt: String,
+    stream: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaResponse {
+    response: String,
+}
+
+/**
+* 要約に使う LLM。GEMINI_API_KEY があれば Gemini、MODEL_GENERATE_NAME があれば Ollama を使う。
+*/
+enum LlmProvider {
+    Gemini(String),
+    Ollama(String),
+}
+
+fn llm_provider() -> Option<LlmProvider> {
+    dotenv().ok();
+    if let Ok(api_key) = env::var("GEMINI_API_KEY") {
+        return Some(LlmProvider::Gemini(api_key));
+    }
+    env::var("MODEL_GENERATE_NAME").ok().map(LlmProvider::Ollama)
+}
+
+/**
+* LLM に prompt を送信し、回答の文字列を返す。
+*
+* @param provider
+* @param prompt
+*
+* @return
+*/
+async fn generate(provider: &LlmProvider, prompt: &str) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    match provider {
+        LlmProvider::Gemini(api_key) => {
+            let body = json!({
+                "contents": [
+                    {
+                        "parts": [
+                            {
+                                "text": prompt
+                            }
+                        ]
+                    }
+                ]
+            });
+            let mut headers = HeaderMap::new();
+            headers.insert("x-goog-api-key", HeaderValue::from_str(api_key).map_err(|e| e.to_string())?);
+            let res = client.post(GEMINI_GENERATE_URL).headers(headers).json(&body).send().await
+                .map_err(|e| e.to_string())?;
+            if !res.status().is_success() {
+                return Err(format!("Gemini {}: {}", res.status(), res.text().await.unwrap_or_default()));
+            }
+            let response_body: Value = res.json().await.map_err(|e| e.to_string())?;
+            response_body["candidates"][0]["content"]["parts"][0]["text"].as_str()
+                .map(|text| text.trim().to_string())
+                .ok_or("Gemini: candidates none".to_string())
+        }
+        LlmProvider::Ollama(model) => {
+            let body = OllamaRequest {
+                model: model.to_string(),
+                prompt: prompt.to_string(),
+                stream: false,
+            };
+            let res = client.post(OLLAMA_GENERATE_URL).json(&body).send().await
+                .map_err(|e| e.to_string())?;
+            if !res.status().is_success() {
+                return Err(format!("Ollama {}: {}", res.status(), res.text().await.unwrap_or_default()));
+            }
+            let response_body: OllamaResponse = res.json().await.map_err(|e| e.to_string())?;
+            Ok(remove_think_tags(&response_body.response))
+        }
+    }
+}
+
+/**
+* <think> ... </think> を除く。
+*/
+fn remove_think_tags(text: &str) -> String {
+    let mut out_str = text.to_string();
+    while let (Some(start), Some(end)) = (out_str.find("<think>"), out_str.find("</think>")) {
+        if end < start {
+            break;
+        }
+        out_str.replace_range(start..end + "</think>".len(), "");
+    }
+    out_str.trim().to_string()
+}
+
+/**
+* FNV-1a (64bit)。Rust のバージョンが変わっても同じ値になるため、キャッシュのキーに使う。
+*/
+fn fnv1a_hash(text: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/**
+* 期間を求める。from , to の指定が無い場合は、date を含む週 (月曜始まり) または月とする。
+*
+* @param period week | month
+* @param date 省略時は今日
+* @param from
+* @param to
+*
+* @return (from, to)
+*/
+fn resolve_period(period: &str, date: Option<&String>, from: Option<&String>, to: Option<&String>) -> Result<(String, String), String> {
+    let parse = |value: &String| super::mod_import::parse_date(value).ok_or(format!("date={}", value));
+    match (from, to) {
+        (Some(from), Some(to)) => return Ok((parse(from)?, parse(to)?)),
+        (Some(_), None) | (None, Some(_)) => return Err("from and to are required together".to_string()),
+        (None, None) => {}
+    }
+    let base = match date {
+        Some(date) => NaiveDate::parse_from_str(&parse(date)?, "%Y-%m-%d").map_err(|e| e.to_string())?,
+        None => Local::now().date_naive(),
+    };
+    let (start, end) = match period {
+        "week" => {
+            let start = base - Duration::days(base.weekday().num_days_from_monday() as i64);
+            (start, start + Duration::days(6))
+        }
+        "month" => {
+            let start = base.with_day(1).unwrap();
+            (start, start + Months::new(1) - Duration::days(1))
+        }
+        _ => return Err(format!("period={} (week , month)", period)),
+    };
+    Ok((start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string()))
+}
+
+fn count_str(counts: &BTreeMap<String, i64>) -> String {
+    let mut items: Vec<(&String, &i64)> = counts.iter().collect();
+    items.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    items.iter().map(|(name, count)| format!("{}: {}", name, count)).collect::<Vec<String>>().join(" , ")
+}
+
+/**
+* 期間の要約を返す。同じ期間、同じ内容の要約があればキャッシュを使う。
+*
+* @param conn
+* @param from
+* @param to
+* @param source 要約する日記
+* @param refresh true の場合、キャッシュを使わない
+*
+* @return (要約, キャッシュを使った場合 true)
+*/
+async fn summarize(conn: &Connection, from: &str, to: &str, source: &str, refresh: bool) -> Result<(String, bool), String> {
+    conn.execute_batch(DIARY_DIGEST_SQL).await.map_err(|e| e.to_string())?;
+    let source_hash = fnv1a_hash(source);
+    if !refresh {
+        let mut rows = conn.query(
+            "SELECT summary FROM mcp_diary_digest WHERE from_date = ?1 AND to_date = ?2 AND source_hash = ?3",
+            params![from, to, source_hash.clone()],
+        ).await.map_err(|e| e.to_string())?;
+        if let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+            return Ok((row.get(0).map_err(|e| e.to_string())?, true));
+        }
+    }
+    let provider = llm_provider().ok_or("GEMINI_API_KEY or MODEL_GENERATE_NAME must be set".to_string())?;
+    let prompt = format!("日本語で、回答して欲しい。\n以下は {} から {} の日記です。出来事、気分の傾向を、箇条書きで簡潔に要約して欲しい。\n\n{}", from, to, source);
+    let summary = generate(&provider, &prompt).await?;
+    conn.execute(
+        "INSERT INTO mcp_diary_digest (from_date, to_date, source_hash, summary) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT(from_date, to_date) DO UPDATE SET source_hash = excluded.source_hash, summary = excluded.summary,
+        updated_at = CURRENT_TIMESTAMP",
+        params![from, to, source_hash, summary.clone()],
+    ).await.map_err(|e| e.to_string())?;
+    Ok((summary, false))
+}
+
+/**
+* diary_digest: 期間の日記を日付ごとにまとめ、件数、タグ、気分を集計する。
+* summarize が true の場合、LLM で期間を要約する。要約はキャッシュし、日記が変わった場合のみ作り直す。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_digest_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryDigestParams {
+        #[serde(default = "default_period")]
+        period: String,
+        date: Option<String>,
+        from: Option<String>,
+        to: Option<String>,
+        #[serde(default)]
+        summarize: bool,
+        #[serde(default)]
+        refresh: bool,
+    }
+    fn default_period() -> String {
+        "week".to_string()
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let digest_params = match serde_json::from_value::<DiaryDigestParams>(arguments) {
+        Ok(digest_params) => digest_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let (from, to) = match resolve_period(&digest_params.period, digest_params.date.as_ref(),
+        digest_params.from.as_ref(), digest_params.to.as_ref()) {
+        Ok(range) => range,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+
+    let sql = format!("SELECT id, data, {date}
+    FROM mcp_diary
+    WHERE {date} BETWEEN ?1 AND ?2
+    ORDER BY 3, id
+    ", date = DIARY_DATE_SQL);
+    let conn = connect().await;
+    let mut rows = conn.query(&sql, params![from.clone(), to.clone()]).await.unwrap();
+    let mut days: BTreeMap<String, Vec<(i64, DiaryEntry)>> = BTreeMap::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        let id: i64 = row.get(0).unwrap();
+        let data: String = row.get(1).unwrap();
+        let mut entry: DiaryEntry = serde_json::from_str(&data).unwrap_or_default();
+        entry.entry_date = row.get(2).unwrap();
+        days.entry(entry.entry_date.clone()).or_default().push((id, entry));
+    }
+
+    let mut out_str = format!("期間: {} - {}\n", from, to);
+    let mut tag_counts: BTreeMap<String, i64> = BTreeMap::new();
+    let mut mood_counts: BTreeMap<String, i64> = BTreeMap::new();
+    let mut ratings: Vec<i64> = Vec::new();
+    let mut source = String::new();
+    let mut entry_count = 0;
+    for (date, entries) in &days {
+        let titles: Vec<String> = entries.iter().map(|(id, entry)| {
+            if entry.title.is_empty() {
+                format!("id={}", id)
+            } else {
+                entry.title.clone()
+            }
+        }).collect();
+        out_str.push_str(&format!("{} ({} 件): {}\n", date, entries.len(), titles.join(" , ")));
+        for (id, entry) in entries {
+            for tag in &entry.tags {
+                *tag_counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+            if let Some(mood) = &entry.mood {
+                *mood_counts.entry(mood.clone()).or_insert(0) += 1;
+            }
+            if let Some(rating) = entry.rating {
+                ratings.push(rating);
+            }
+            source.push_str(&format_entry(*id, entry));
+            entry_count += 1;
+        }
+    }
+    out_str.push_str(&format!("日数: {} , 件数: {}\n", days.len(), entry_count));
+    if !tag_counts.is_empty() {
+        out_str.push_str(&format!("タグ: {}\n", count_str(&tag_counts)));
+    }
+    if !mood_counts.is_empty() {
+        out_str.push_str(&format!("気分: {}\n", count_str(&mood_counts)));
+    }
+    if !ratings.is_empty() {
+        let average = ratings.iter().sum::<i64>() as f64 / ratings.len() as f64;
+        out_str.push_str(&format!("評価の平均: {:.1}\n", average));
+    }
+
+    if digest_params.summarize && entry_count > 0 {
+        match summarize(&conn, &from, &to, &source, digest_params.refresh).await {
+            Ok((summary, cached)) => {
+                out_str.push_str(&format!("\n要約{}:\n{}\n", if cached { " (cache)" } else { "" }, summary));
+            }
+            Err(e) => return error_response(request_id, -32603, format!("Summary error: {}", e)),
+        }
+    }
+    text_response(request_id, out_str)
+}
@@ -0,0 +1,372 @@
+use libsql::Connection;
+use libsql::params;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+use dotenvy::dotenv;
+use unicode_segmentation::UnicodeSegmentation;
+use super::{connect, error_response, text_response};
+
+// 日記のチャンクと embedding。日記の削除は trigger で反映する。
+// 次元数は mcp_19 の documents (vector(1024)) と同じ。
+pub const DIARY_EMBED_SQL: &str = "
+CREATE TABLE IF NOT EXISTS mcp_diary_embedding (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  diary_id INTEGER NOT NULL,
+  chunk_index INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  embedding F32_BLOB(1024) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_mcp_diary_embedding_diary_id ON mcp_diary_embedding (diary_id);
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_embedding_delete AFTER DELETE ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_embedding WHERE diary_id = old.id;
+END;
+";
+
+const EMBED_DIMENSIONS: usize = 1024;
+const OLLAMA_EMBED_URL: &str = "http://localhost:11434/api/embeddings";
+const GEMINI_EMBED_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/gemini-embedding-001:embedContent";
+
+#[derive(Serialize)]
+struct EmbeddingRequest {
+    model: String,
+    prompt: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+/// テキストをチャンクに分割する構造体
+pub struct TextSplitter {
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl TextSplitter {
+    pub fn new(chunk_size: usize, chunk_overlap: usize) -> Self {
+        assert!(chunk_overlap < chunk_size, "オーバーラップはチャンクサイズより小さくする必要があります");
+        Self {
+            chunk_size,
+            chunk_overlap,
+        }
+    }
+
+    /// 基本的な文字ベース分割
+    pub fn split_text(&self, text: &str) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let chars: Vec<&str> = text.graphemes(true).collect();
+
+        let mut start = 0;
+        while start < chars.len() {
+            let end = std::cmp::min(start + self.chunk_size, chars.len());
+            let chunk: String = chars[start..end].iter().copied().collect();
+            chunks.push(chunk);
+
+            if end >= chars.len() {
+                break;
+            }
+            start += self.chunk_size - self.chunk_overlap;
+        }
+
+        chunks
+    }
+
+    /// 再帰的分割（段落 -> 文 -> 単語の順）
+    pub fn recursive_split(&self, text: &str) -> Vec<String> {
+        let separators = vec!["\n\n", "\n", "。", ".", " "];
+        self.recursive_split_with_separators(text, &separators)
+    }
+
+    fn recursive_split_with_separators(&self, text: &str, separators: &[&str]) -> Vec<String> {
+        if separators.is_empty() {
+            return self.split_text(text);
+        }
+
+        let separator = separators[0];
+        let remaining_separators = &separators[1..];
+
+        let parts: Vec<&str> = text.split(separator).collect();
+        let mut chunks = Vec::new();
+        let mut current_chunk = String::new();
+
+        for part in parts {
+            let part_len = part.graphemes(true).count();
+            let current_len = current_chunk.graphemes(true).count();
+
+            if current_len + part_len + separator.len() <= self.chunk_size {
+                if !current_chunk.is_empty() {
+                    current_chunk.push_str(separator);
+                }
+                current_chunk.push_str(part);
+            } else {
+                if !current_chunk.is_empty() {
+                    chunks.push(current_chunk.clone());
+                    current_chunk.clear();
+                }
+
+                if part_len > self.chunk_size {
+                    // さらに細かく分割
+                    let sub_chunks = self.recursive_split_with_separators(part, remaining_separators);
+                    chunks.extend(sub_chunks);
+                } else {
+                    current_chunk = part.to_string();
+                }
+            }
+        }
+
+        if !current_chunk.is_empty() {
+            chunks.push(current_chunk);
+        }
+
+        chunks
+    }
+}
+
+/**
+* embedding の送信先。GEMINI_API_KEY があれば Gemini、MODEL_EMBED_NAME があれば Ollama を使う。
+* どちらも無い場合は None とし、日記の embedding を行わない。
+*/
+enum EmbedProvider {
+    Gemini(String),
+    Ollama(String),
+}
+
+fn embed_provider() -> Option<EmbedProvider> {
+    dotenv().ok();
+    if let Ok(api_key) = env::var("GEMINI_API_KEY") {
+        return Some(EmbedProvider::Gemini(api_key));
+    }
+    env::var("MODEL_EMBED_NAME").ok().map(EmbedProvider::Ollama)
+}
+
+/**
+* テキストの embedding を取得する。
+*
+* @param client
+* @param provider
+* @param text
+*
+* @return
+*/
+async fn embed_text(client: &reqwest::Client, provider: &EmbedProvider, text: &str) -> Result<Vec<f32>, String> {
+    let embedding = match provider {
+        EmbedProvider::Gemini(api_key) => {
+            let body = json!({
+                "model": "models/gemini-embedding-001",
+                "content": {"parts": [{"text": text}]},
+                "output_dimensionality": EMBED_DIMENSIONS
+            });
+            let mut headers = HeaderMap::new();
+            headers.insert("x-goog-api-key", HeaderValue::from_str(api_key).map_err(|e| e.to_string())?);
+            let res = client.post(GEMINI_EMBED_URL).headers(headers).json(&body).send().await
+                .map_err(|e| e.to_string())?;
+            if !res.status().is_success() {
+                return Err(format!("Gemini {}: {}", res.status(), res.text().await.unwrap_or_default()));
+            }
+            let response_body: Value = res.json().await.map_err(|e| e.to_string())?;
+            response_body["embedding"]["values"].as_array()
+                .ok_or("Gemini: embedding.values none".to_string())?
+                .iter()
+                .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+                .collect::<Vec<f32>>()
+        }
+        EmbedProvider::Ollama(model) => {
+            let request = EmbeddingRequest {
+                model: model.to_string(),
+                prompt: text.to_string(),
+            };
+            let res = client.post(OLLAMA_EMBED_URL).json(&request).send().await
+                .map_err(|e| e.to_string())?;
+            if !res.status().is_success() {
+                return Err(format!("Ollama {}: {}", res.status(), res.text().await.unwrap_or_default()));
+            }
+            let response_body: EmbeddingResponse = res.json().await.map_err(|e| e.to_string())?;
+            response_body.embedding
+        }
+    };
+    if embedding.len() != EMBED_DIMENSIONS {
+        return Err(format!("dimensions={} (expected {})", embedding.len(), EMBED_DIMENSIONS));
+    }
+    Ok(embedding)
+}
+
+fn vector_text(embedding: &[f32]) -> String {
+    serde_json::to_string(embedding).expect("JSON convert error")
+}
+
+/**
+* 日記をチャンクに分割して embedding を登録する。既存のチャンクは置き換える。
+* embedding に失敗した場合も既存のチャンクは削除し、次回起動時に再登録する。
+*
+* @param conn
+* @param id
+* @param entry
+*
+* @return 登録したチャンク数。embedding が未設定の場合は 0
+*/
+pub async fn embed_entry(conn: &Connection, id: i64, entry: &super::mod_diary::DiaryEntry) -> Result<usize, String> {
+    let Some(provider) = embed_provider() else {
+        return Ok(0);
+    };
+    let text = if entry.title.is_empty() {
+        entry.body.clone()
+    } else {
+        format!("{}\n{}", entry.title, entry.body)
+    };
+    let splitter = TextSplitter::new(500, 100);
+    let chunks: Vec<String> = splitter.recursive_split(&text).into_iter()
+        .filter(|chunk| !chunk.trim().is_empty())
+        .collect();
+
+    let client = reqwest::Client::new();
+    let mut embeddings: Vec<Vec<f32>> = Vec::new();
+    let mut embed_error: Option<String> = None;
+    for chunk in &chunks {
+        match embed_text(&client, &provider, chunk).await {
+            Ok(embedding) => embeddings.push(embedding),
+            Err(e) => {
+                embed_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let tx = conn.transaction().await.map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM mcp_diary_embedding WHERE diary_id = ?1", params![id]).await
+        .map_err(|e| e.to_string())?;
+    if embed_error.is_none() {
+        for (index, (chunk, embedding)) in chunks.iter().zip(&embeddings).enumerate() {
+            tx.execute(
+                "INSERT INTO mcp_diary_embedding (diary_id, chunk_index, content, embedding) VALUES (?1, ?2, ?3, vector32(?4))",
+                params![id, index as i64, chunk.clone(), vector_text(embedding)],
+            ).await.map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    match embed_error {
+        Some(e) => Err(e),
+        None => Ok(chunks.len()),
+    }
+}
+
+/**
+* embedding の table と trigger を作成し、チャンクの無い日記を登録する。
+*
+* @param conn
+*
+* @return 登録した日記の件数
+*/
+pub async fn ensure_embeddings(conn: &Connection) -> Result<usize, String> {
+    conn.execute_batch(DIARY_EMBED_SQL).await.map_err(|e| e.to_string())?;
+    if embed_provider().is_none() {
+        return Ok(0);
+    }
+    let mut rows = conn.query(
+        "SELECT id FROM mcp_diary WHERE id NOT IN (SELECT diary_id FROM mcp_diary_embedding) ORDER BY id",
+        (),
+    ).await.map_err(|e| e.to_string())?;
+    let mut ids: Vec<i64> = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        ids.push(row.get(0).map_err(|e| e.to_string())?);
+    }
+    let mut count = 0;
+    for id in ids {
+        if let Some(entry) = super::mod_diary::find_entry(conn, id).await.map_err(|e| e.to_string())? {
+            embed_entry(conn, id, &entry).await?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/**
+* diary_recall: 質問文に意味の近い日記を、コサイン距離の近い順に日付と該当チャンクを表示する。
+* 最後に、該当した日記のうち最も新しい日付を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_recall_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryRecallParams {
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        max_distance: Option<f64>,
+        #[serde(default = "default_limit")]
+        limit: i64,
+    }
+    fn default_limit() -> i64 {
+        5
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryRecallParams>(arguments.clone()) {
+            Ok(recall_params) => {
+                if recall_params.query.trim().is_empty() {
+                    return error_response(request_id, -32602, "Invalid parameters: query is empty".to_string());
+                }
+                let Some(provider) = embed_provider() else {
+                    return error_response(request_id, -32603, "GEMINI_API_KEY or MODEL_EMBED_NAME must be set".to_string());
+                };
+                let client = reqwest::Client::new();
+                let query_vec = match embed_text(&client, &provider, &recall_params.query).await {
+                    Ok(embedding) => embedding,
+                    Err(e) => return error_response(request_id, -32603, format!("Embedding error: {}", e)),
+                };
+                // MIN() と同じ行の content が返る (SQLite の bare column)
+                let sql = format!("SELECT mcp_diary_embedding.diary_id, {date},
+                COALESCE(json_extract(mcp_diary.data, '$.title'), ''), mcp_diary_embedding.content,
+                MIN(vector_distance_cos(mcp_diary_embedding.embedding, vector32(?1))) AS distance
+                FROM mcp_diary_embedding JOIN mcp_diary ON mcp_diary.id = mcp_diary_embedding.diary_id
+                WHERE (?2 IS NULL OR {date} >= ?2) AND (?3 IS NULL OR {date} <= ?3)
+                GROUP BY mcp_diary_embedding.diary_id
+                HAVING ?4 IS NULL OR distance <= ?4
+                ORDER BY distance
+                LIMIT ?5
+                ", date = super::mod_diary::DIARY_DATE_SQL);
+
+                let conn = connect().await;
+                let mut rows = match conn.query(&sql, params![
+                    vector_text(&query_vec), recall_params.from, recall_params.to,
+                    recall_params.max_distance, recall_params.limit,
+                ]).await {
+                    Ok(rows) => rows,
+                    Err(e) => return error_response(request_id, -32603, format!("Recall error: {}", e)),
+                };
+                let mut out_str = String::new();
+                let mut latest: Option<(String, i64)> = None;
+                let mut count = 0;
+                while let Some(row) = rows.next().await.unwrap() {
+                    let id: i64 = row.get(0).unwrap();
+                    let entry_date: String = row.get(1).unwrap();
+                    let title: String = row.get(2).unwrap();
+                    let content: String = row.get(3).unwrap();
+                    let distance: f64 = row.get(4).unwrap();
+                    out_str.push_str(&format!("id: {} , date= {} , title= {} , distance= {:.3}\n{}\n",
+                        id, entry_date, title, distance, content));
+                    if latest.as_ref().is_none_or(|(date, _)| entry_date > *date) {
+                        latest = Some((entry_date, id));
+                    }
+                    count += 1;
+                }
+                if let Some((date, id)) = latest {
+                    out_str.push_str(&format!("最新: {} (id={})\n", date, id));
+                }
+                out_str.push_str(&format!("{} 件", count));
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
@@ -0,0 +1,514 @@
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::mod_diary::{DiaryEntry, DIARY_DATE_SQL, find_entry, normalize_tags};
+use super::{connect, error_response, text_response};
+
+/// Obsidian の daily note (YYYY-MM-DD.md) の frontmatter
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct NoteFrontmatter {
+    #[serde(default)]
+    date: String,
+    // その日の日記のタグ、気分をまとめたもの (Obsidian の properties 用)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "string_or_list")]
+    mood: Vec<String>,
+    // 日記ごとの id と項目。本文は <!-- diary:id --> の後に書く
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    diary: Vec<NoteEntry>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct NoteEntry {
+    id: i64,
+    #[serde(default)]
+    title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mood: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rating: Option<i64>,
+}
+
+#[derive(Debug)]
+enum NoteStatus {
+    Insert(Option<i64>, DiaryEntry),
+    Update(i64, DiaryEntry),
+    Unchanged(i64),
+    Duplicate(DiaryEntry),
+    Error(String),
+}
+
+// 手で書いた note は mood: 良い のように1つだけ書くことが多い
+fn string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        One(String),
+        List(Vec<String>),
+    }
+    Ok(match Option::<StringOrList>::deserialize(deserializer)? {
+        Some(StringOrList::One(value)) => vec![value],
+        Some(StringOrList::List(values)) => values,
+        None => Vec::new(),
+    })
+}
+
+fn marker(id: i64) -> String {
+    format!("<!-- diary:{} -->", id)
+}
+
+fn parse_marker(line: &str) -> Option<i64> {
+    line.trim().strip_prefix("<!-- diary:")?.strip_suffix("-->")?.trim().parse().ok()
+}
+
+/**
+* diary_export が出力した note か。frontmatter の diary , または <!-- diary:id --> がある note とする。
+*
+* @param text
+*
+* @return
+*/
+fn is_exported_note(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{FEFF}').replace("\r\n", "\n");
+    if text.lines().any(|line| parse_marker(line).is_some()) {
+        return true;
+    }
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return false;
+    };
+    let Some(end) = rest.find("\n---") else {
+        return false;
+    };
+    serde_yaml::from_str::<NoteFrontmatter>(&rest[..end]).is_ok_and(|frontmatter| !frontmatter.diary.is_empty())
+}
+
+/**
+* 1日分の日記を daily note の Markdown にする。
+*
+* @param date
+* @param entries (id, 日記)
+*
+* @return
+*/
+fn render_note(date: &str, entries: &[(i64, DiaryEntry)]) -> String {
+    let mut frontmatter = NoteFrontmatter {
+        date: date.to_string(),
+        ..Default::default()
+    };
+    for (id, entry) in entries {
+        for tag in &entry.tags {
+            if !frontmatter.tags.contains(tag) {
+                frontmatter.tags.push(tag.clone());
+            }
+        }
+        if let Some(mood) = &entry.mood
+            && !frontmatter.mood.contains(mood) {
+            frontmatter.mood.push(mood.clone());
+        }
+        frontmatter.diary.push(NoteEntry {
+            id: *id,
+            title: entry.title.clone(),
+            tags: entry.tags.clone(),
+            mood: entry.mood.clone(),
+            rating: entry.rating,
+        });
+    }
+    let mut out_str = format!("---\n{}---\n", serde_yaml::to_string(&frontmatter).expect("YAML convert error"));
+    for (id, entry) in entries {
+        out_str.push_str(&format!("{}\n", marker(*id)));
+        if !entry.title.is_empty() {
+            out_str.push_str(&format!("## {}\n\n", entry.title));
+        }
+        out_str.push_str(&format!("{}\n\n", entry.body.trim_end()));
+    }
+    out_str
+}
+
+/**
+* daily note を読み込み、日記の一覧にする。id の無い日記は新規とする。
+* frontmatter が無い、または <!-- diary:id --> の無い note は、全体を1件の日記とする。
+*
+* @param file_date ファイル名の日付
+* @param text
+*
+* @return (id, 日記)
+*/
+fn parse_note(file_date: &str, text: &str) -> Result<Vec<(Option<i64>, DiaryEntry)>, String> {
+    let text = text.trim_start_matches('\u{FEFF}').replace("\r\n", "\n");
+    let (frontmatter, body) = match text.strip_prefix("---\n") {
+        Some(rest) => {
+            let end = rest.find("\n---").ok_or("frontmatter is not closed".to_string())?;
+            let frontmatter: NoteFrontmatter = if rest[..end].trim().is_empty() {
+                NoteFrontmatter::default()
+            } else {
+                serde_yaml::from_str(&rest[..end]).map_err(|e| format!("frontmatter error: {}", e))?
+            };
+            let body = rest[end + 4..].split_once('\n').map(|(_, body)| body).unwrap_or("");
+            (frontmatter, body.to_string())
+        }
+        None => (NoteFrontmatter::default(), text.clone()),
+    };
+    let date = if frontmatter.date.is_empty() { file_date.to_string() } else { frontmatter.date.clone() };
+    let entry_date = super::mod_import::parse_date(&date).ok_or(format!("date={}", date))?;
+
+    let mut sections: Vec<(Option<i64>, Vec<&str>)> = vec![(None, Vec::new())];
+    for line in body.lines() {
+        match parse_marker(line) {
+            Some(id) => sections.push((Some(id), Vec::new())),
+            None => sections.last_mut().unwrap().1.push(line),
+        }
+    }
+
+    let mut entries: Vec<(Option<i64>, DiaryEntry)> = Vec::new();
+    for (id, lines) in sections {
+        let section = lines.join("\n");
+        if id.is_none() && section.trim().is_empty() {
+            continue;
+        }
+        let note_entry = id.and_then(|id| frontmatter.diary.iter().find(|note_entry| note_entry.id == id));
+        let mut entry = match note_entry {
+            Some(note_entry) => DiaryEntry {
+                title: note_entry.title.clone(),
+                tags: normalize_tags(&note_entry.tags),
+                mood: note_entry.mood.clone(),
+                rating: note_entry.rating,
+                ..Default::default()
+            },
+            None => DiaryEntry {
+                tags: normalize_tags(&frontmatter.tags),
+                mood: frontmatter.mood.first().cloned(),
+                ..Default::default()
+            },
+        };
+        entry.entry_date = entry_date.clone();
+        // render_note が出力した ## title の行は、本文には含めない
+        let section = section.trim();
+        let (heading, rest) = section.split_once('\n').unwrap_or((section, ""));
+        if !entry.title.is_empty() && heading.trim_end() == format!("## {}", entry.title) {
+            entry.body = rest.trim().to_string();
+        } else {
+            entry.body = section.to_string();
+        }
+        entries.push((id, entry));
+    }
+    Ok(entries)
+}
+
+fn list_notes(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                files.extend(list_notes(&path)?);
+            } else if path.extension().is_some_and(|ext| ext == "md") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/**
+* diary_export: 日記を日付ごとに、vault のフォルダへ YYYY-MM-DD.md で出力する。
+* 既存の note は、diary_export が出力したもの (is_exported_note) のみ上書きし、それ以外は出力しない。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_export_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryExportParams {
+        path: String,
+        from: Option<String>,
+        to: Option<String>,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryExportParams>(arguments.clone()) {
+            Ok(export_params) => {
+                let sql = format!("SELECT id, data, {date}
+                FROM mcp_diary
+                WHERE (?1 IS NULL OR {date} >= ?1) AND (?2 IS NULL OR {date} <= ?2)
+                ORDER BY 3, id
+                ", date = DIARY_DATE_SQL);
+                let conn = connect().await;
+                let mut rows = conn.query(&sql, params![export_params.from, export_params.to]).await.unwrap();
+                let mut days: BTreeMap<String, Vec<(i64, DiaryEntry)>> = BTreeMap::new();
+                while let Some(row) = rows.next().await.unwrap() {
+                    let id: i64 = row.get(0).unwrap();
+                    let data: String = row.get(1).unwrap();
+                    let mut entry: DiaryEntry = serde_json::from_str(&data).unwrap_or_default();
+                    entry.entry_date = row.get(2).unwrap();
+                    days.entry(entry.entry_date.clone()).or_default().push((id, entry));
+                }
+
+                let folder = Path::new(&export_params.path);
+                if let Err(e) = fs::create_dir_all(folder) {
+                    return error_response(request_id, -32602, format!("Folder error: {}, path={}", e, export_params.path));
+                }
+                let mut out_str = String::new();
+                let mut entry_count = 0;
+                let mut skipped = 0;
+                for (date, entries) in &days {
+                    let file_path = folder.join(format!("{}.md", date));
+                    // 手で書いた daily note は上書きしない
+                    if file_path.exists() && !fs::read_to_string(&file_path).is_ok_and(|text| is_exported_note(&text)) {
+                        out_str.push_str(&format!("{} : skip (diary_export の出力ではない note) , {} 件\n", file_path.display(), entries.len()));
+                        skipped += 1;
+                        continue;
+                    }
+                    if let Err(e) = fs::write(&file_path, render_note(date, entries)) {
+                        return error_response(request_id, -32603, format!("File write error: {}, path={}", e, file_path.display()));
+                    }
+                    out_str.push_str(&format!("{} : {} 件\n", file_path.display(), entries.len()));
+                    entry_count += entries.len();
+                }
+                out_str.push_str(&format!("note: {} , diary: {} , skip: {}", days.len() - skipped, entry_count, skipped));
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* diary_import: vault のフォルダの YYYY-MM-DD.md を読み込み、日記を登録する。
+* frontmatter の id が既存の日記は更新し、id の無い日記は同じ日付、本文の日記が無ければ登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_import_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DiaryImportParams {
+        path: String,
+        from: Option<String>,
+        to: Option<String>,
+        #[serde(default)]
+        dry_run: bool,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiaryImportParams>(arguments.clone()) {
+            Ok(import_params) => {
+                let files = match list_notes(Path::new(&import_params.path)) {
+                    Ok(files) if !files.is_empty() => files,
+                    Ok(_) => return error_response(request_id, -32602, format!("Invalid parameters, no notes in path={}", import_params.path)),
+                    Err(e) => return error_response(request_id, -32602, format!("Folder read error: {}, path={}", e, import_params.path)),
+                };
+
+                let conn = connect().await;
+                let mut results: Vec<(String, NoteStatus)> = Vec::new();
+                for file in &files {
+                    let file_date = match file.file_stem().and_then(|stem| super::mod_import::parse_date(&stem.to_string_lossy())) {
+                        Some(file_date) => file_date,
+                        None => continue,
+                    };
+                    if import_params.from.as_ref().is_some_and(|from| file_date < *from)
+                        || import_params.to.as_ref().is_some_and(|to| file_date > *to) {
+                        continue;
+                    }
+                    let name = file.display().to_string();
+                    let parsed = fs::read_to_string(file)
+                        .map_err(|e| format!("File read error: {}", e))
+                        .and_then(|text| parse_note(&file_date, &text));
+                    let entries = match parsed {
+                        Ok(entries) => entries,
+                        Err(e) => {
+                            results.push((name, NoteStatus::Error(e)));
+                            continue;
+                        }
+                    };
+                    for (id, entry) in entries {
+                        let status = match id {
+                            Some(id) => match find_entry(&conn, id).await.unwrap() {
+                                Some(current) if serde_json::to_value(&current).ok() == serde_json::to_value(&entry).ok() => NoteStatus::Unchanged(id),
+                                Some(_) => NoteStatus::Update(id, entry),
+                                // 別の DB から出力した note も、id を変えずに登録する
+                                None => NoteStatus::Insert(Some(id), entry),
+                            },
+                            None => {
+                                let sql = format!("SELECT COUNT(*) FROM mcp_diary
+                                WHERE {} = ?1 AND COALESCE(json_extract(data, '$.body'), json_extract(data, '$.text')) = ?2
+                                ", DIARY_DATE_SQL);
+                                let mut rows = conn.query(&sql, params![entry.entry_date.clone(), entry.body.clone()]).await.unwrap();
+                                let count: i64 = rows.next().await.unwrap().map(|row| row.get(0).unwrap()).unwrap_or(0);
+                                if count > 0 { NoteStatus::Duplicate(entry) } else { NoteStatus::Insert(None, entry) }
+                            }
+                        };
+                        results.push((name.clone(), status));
+                    }
+                }
+
+                if !import_params.dry_run {
+                    for (_, status) in results.iter_mut() {
+                        let saved = match status {
+                            NoteStatus::Insert(id, entry) => {
+                                let data = serde_json::to_string(entry).expect("JSON convert error");
+                                conn.execute("INSERT INTO mcp_diary (id, data) VALUES (?1, ?2)", params![*id, data]).await
+                                    .map(|_| (conn.last_insert_rowid(), entry.clone()))
+                            }
+                            NoteStatus::Update(id, entry) => {
+                                let data = serde_json::to_string(entry).expect("JSON convert error");
+                                conn.execute("UPDATE mcp_diary SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2", params![data, *id]).await
+                                    .map(|_| (*id, entry.clone()))
+                            }
+                            _ => continue,
+                        };
+                        match saved {
+                            Ok((id, entry)) => {
+                                if let NoteStatus::Insert(insert_id, _) = status {
+                                    *insert_id = Some(id);
+                                }
+                                if let Err(e) = super::mod_diary_embed::embed_entry(&conn, id, &entry).await {
+                                    eprintln!("diary embedding error: id={} , {}", id, e);
+                                }
+                            }
+                            Err(e) => *status = NoteStatus::Error(format!("Save error: {}", e)),
+                        }
+                    }
+                }
+
+                let (mut insert_count, mut update_count, mut unchanged_count, mut duplicate_count, mut error_count) = (0, 0, 0, 0, 0);
+                let mut out_str = if import_params.dry_run { "dry_run: 登録は行いません\n".to_string() } else { String::new() };
+                for (name, status) in &results {
+                    let row_str = match status {
+                        NoteStatus::Insert(id, entry) => {
+                            insert_count += 1;
+                            let id = id.map(|id| id.to_string()).unwrap_or("-".to_string());
+                            format!("{}: insert , id={} , {} , {}\n", name, id, entry.entry_date, entry.title)
+                        }
+                        NoteStatus::Update(id, entry) => {
+                            update_count += 1;
+                            format!("{}: update , id={} , {} , {}\n", name, id, entry.entry_date, entry.title)
+                        }
+                        NoteStatus::Unchanged(id) => {
+                            unchanged_count += 1;
+                            format!("{}: unchanged , id={}\n", name, id)
+                        }
+                        NoteStatus::Duplicate(entry) => {
+                            duplicate_count += 1;
+                            format!("{}: duplicate , {} , {}\n", name, entry.entry_date, entry.title)
+                        }
+                        NoteStatus::Error(e) => {
+                            error_count += 1;
+                            format!("{}: error , {}\n", name, e)
+                        }
+                    };
+                    out_str.push_str(&row_str);
+                }
+                out_str.push_str(&format!("insert: {} , update: {} , unchanged: {} , duplicate: {} , error: {}",
+                    insert_count, update_count, unchanged_count, duplicate_count, error_count));
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* CLI: rust_mcp_server_3 diary_export <path> [from] [to]
+*      rust_mcp_server_3 diary_import <path> [--dry-run]
+*
+* @param args 実行ファイルのパスを除いた引数
+*
+* @return CLI として実行した場合 true
+*/
+pub async fn run_cli(args: &[String]) -> bool {
+    let (tool_name, arguments) = match args {
+        [command, path, rest @ ..] if command == "diary_export" => (command, json!({
+            "path": path,
+            "from": rest.first(),
+            "to": rest.get(1),
+        })),
+        [command, path, rest @ ..] if command == "diary_import" => (command, json!({
+            "path": path,
+            "dry_run": rest.iter().any(|arg| arg == "--dry-run"),
+        })),
+        _ => return false,
+    };
+    let params = json!({ "arguments": arguments });
+    let response = if tool_name == "diary_export" {
+        diary_export_handler(params, None).await
+    } else {
+        diary_import_handler(params, None).await
+    };
+    match (response.result, response.error) {
+        (Some(result), _) => println!("{}", result["content"][0]["text"].as_str().unwrap_or("")),
+        (_, Some(error)) => eprintln!("error: {}", error.message),
+        _ => {}
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, body: &str) -> DiaryEntry {
+        DiaryEntry {
+            title: title.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exported_title_heading_is_not_part_of_body() {
+        let text = render_note("2025-10-01", &[(1, entry("朝", "散歩した"))]);
+        let entries = parse_note("2025-10-01", &text).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, Some(1));
+        assert_eq!(entries[0].1.title, "朝");
+        assert_eq!(entries[0].1.body, "散歩した");
+    }
+
+    #[test]
+    fn body_heading_is_kept_when_title_is_empty() {
+        let text = render_note("2025-10-01", &[(1, entry("", "# 見出し\n本文")), (2, entry("夜", "## 見出し\n本文"))]);
+        let entries = parse_note("2025-10-01", &text).unwrap();
+        assert_eq!(entries[0].1.title, "");
+        assert_eq!(entries[0].1.body, "# 見出し\n本文");
+        assert_eq!(entries[1].1.title, "夜");
+        assert_eq!(entries[1].1.body, "## 見出し\n本文");
+    }
+
+    #[test]
+    fn hand_written_note_is_one_entry() {
+        let entries = parse_note("2025-10-01", "# メモ\r\n買い物\r\n").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, None);
+        assert_eq!(entries[0].1.entry_date, "2025-10-01");
+        assert_eq!(entries[0].1.body, "# メモ\n買い物");
+    }
+
+    #[test]
+    fn exported_note_is_detected() {
+        assert!(is_exported_note(&render_note("2025-10-01", &[(1, entry("", "本文"))])));
+        assert!(is_exported_note("<!-- diary:3 -->\n本文\n"));
+        assert!(!is_exported_note("# メモ\n買い物\n"));
+        assert!(!is_exported_note("---\ntags: [daily]\n---\n本文\n"));
+    }
+}
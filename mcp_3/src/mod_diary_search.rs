@@ -0,0 +1,205 @@
+use libsql::Connection;
+use serde::Deserialize;
+use serde_json::Value;
+use super::{connect, error_response, text_response};
+
+// 日本語は空白で区切られないため、trigram で索引を作る。
+// mcp_diary の追加、更新、削除は trigger で索引に反映する。
+pub const DIARY_FTS_SQL: &str = "
+CREATE VIRTUAL TABLE IF NOT EXISTS mcp_diary_fts USING fts5(title, body, tags, tokenize = 'trigram');
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_insert AFTER INSERT ON mcp_diary BEGIN
+  INSERT INTO mcp_diary_fts (rowid, title, body, tags) VALUES (new.id,
+    json_extract(new.data, '$.title'),
+    COALESCE(json_extract(new.data, '$.body'), json_extract(new.data, '$.text')),
+    (SELECT group_concat(value, ' ') FROM json_each(new.data, '$.tags')));
+END;
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_update AFTER UPDATE OF data ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_fts WHERE rowid = old.id;
+  INSERT INTO mcp_diary_fts (rowid, title, body, tags) VALUES (new.id,
+    json_extract(new.data, '$.title'),
+    COALESCE(json_extract(new.data, '$.body'), json_extract(new.data, '$.text')),
+    (SELECT group_concat(value, ' ') FROM json_each(new.data, '$.tags')));
+END;
+
+CREATE TRIGGER IF NOT EXISTS mcp_diary_fts_delete AFTER DELETE ON mcp_diary BEGIN
+  DELETE FROM mcp_diary_fts WHERE rowid = old.id;
+END;
+";
+
+// trigram は3文字未満の語を MATCH できないため、短い語は LIKE で絞り込む
+const TRIGRAM_MIN_CHARS: usize = 3;
+
+/**
+* 全文検索の索引 (mcp_diary_fts) と trigger を作成し、索引に無い日記を登録する。
+*
+* @param conn
+*
+* @return 索引に登録した件数
+*/
+pub async fn ensure_index(conn: &Connection) -> Result<u64, libsql::Error> {
+    conn.execute_batch(DIARY_FTS_SQL).await?;
+    conn.execute(
+        "INSERT INTO mcp_diary_fts (rowid, title, body, tags)
+        SELECT id, json_extract(data, '$.title'),
+        COALESCE(json_extract(data, '$.body'), json_extract(data, '$.text')),
+        (SELECT group_concat(value, ' ') FROM json_each(data, '$.tags'))
+        FROM mcp_diary
+        WHERE json_valid(data) AND id NOT IN (SELECT rowid FROM mcp_diary_fts)",
+        (),
+    ).await
+}
+
+/**
+* FTS5 の phrase として、語を " で囲む。
+*/
+fn fts_phrase(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+/**
+* MATCH を使わない場合の snippet。最初に見つかった語の前後を切り出し、【】で囲む。
+*
+* @param text 本文
+* @param terms 検索語
+* @param width 前後の文字数
+*
+* @return
+*/
+fn like_snippet(text: &str, terms: &[String], width: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let found = terms.iter()
+        .filter_map(|term| {
+            let term: Vec<char> = term.chars().collect();
+            find_chars(&chars, &term).map(|pos| (pos, term.len()))
+        })
+        .min();
+    let Some((start, len)) = found else {
+        return chars.iter().take(width * 2).collect();
+    };
+    let end = (start + len).min(chars.len());
+    let from = start.saturating_sub(width);
+    let to = (end + width).min(chars.len());
+    format!("{}{}【{}】{}{}",
+        if from > 0 { "…" } else { "" },
+        chars[from..start].iter().collect::<String>(),
+        chars[start..end].iter().collect::<String>(),
+        chars[end..to].iter().collect::<String>(),
+        if to < chars.len() { "…" } else { "" })
+}
+
+/**
+* 大文字小文字を区別せずに term を探す。小文字にすると文字数が変わる文字があるため、1 文字ずつ比べる。
+*
+* @param chars 本文
+* @param term 検索語
+*
+* @return 本文の文字の位置
+*/
+fn find_chars(chars: &[char], term: &[char]) -> Option<usize> {
+    if term.is_empty() || term.len() > chars.len() {
+        return None;
+    }
+    (0..=chars.len() - term.len()).find(|&pos| {
+        chars[pos..pos + term.len()].iter().zip(term)
+            .all(|(c, t)| c.to_lowercase().eq(t.to_lowercase()))
+    })
+}
+
+/**
+* diary_search: 日記を全文検索し、関連度の高い順に日付と snippet を表示する。
+* 空白区切りの語は、すべて含む日記を検索する (AND)。
+*
+* @param
+*
+* @return
+*/
+pub async fn diary_search_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DiarySearchParams {
+        query: String,
+        from: Option<String>,
+        to: Option<String>,
+        #[serde(default = "default_limit")]
+        limit: i64,
+    }
+    fn default_limit() -> i64 {
+        10
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<DiarySearchParams>(arguments.clone()) {
+            Ok(search_params) => {
+                let terms: Vec<String> = search_params.query.split_whitespace().map(|term| term.to_string()).collect();
+                if terms.is_empty() {
+                    return error_response(request_id, -32602, "Invalid parameters: query is empty".to_string());
+                }
+                let (long_terms, short_terms): (Vec<&String>, Vec<&String>) =
+                    terms.iter().partition(|term| term.chars().count() >= TRIGRAM_MIN_CHARS);
+
+                let mut where_list: Vec<String> = Vec::new();
+                let mut values: Vec<libsql::Value> = Vec::new();
+                if !long_terms.is_empty() {
+                    let match_query: Vec<String> = long_terms.iter().map(|term| fts_phrase(term)).collect();
+                    values.push(libsql::Value::Text(match_query.join(" AND ")));
+                    where_list.push(format!("mcp_diary_fts MATCH ?{}", values.len()));
+                }
+                for term in &short_terms {
+                    values.push(libsql::Value::Text(format!("%{}%", term)));
+                    where_list.push(format!("(mcp_diary_fts.title LIKE ?{n} OR mcp_diary_fts.body LIKE ?{n} OR mcp_diary_fts.tags LIKE ?{n})",
+                        n = values.len()));
+                }
+                let date_sql = super::mod_diary::DIARY_DATE_SQL;
+                if let Some(from) = &search_params.from {
+                    values.push(libsql::Value::Text(from.clone()));
+                    where_list.push(format!("{} >= ?{}", date_sql, values.len()));
+                }
+                if let Some(to) = &search_params.to {
+                    values.push(libsql::Value::Text(to.clone()));
+                    where_list.push(format!("{} <= ?{}", date_sql, values.len()));
+                }
+                values.push(libsql::Value::Integer(search_params.limit));
+                let (snippet_sql, order_sql) = if long_terms.is_empty() {
+                    ("NULL", format!("{} DESC", date_sql))
+                } else {
+                    ("snippet(mcp_diary_fts, -1, '【', '】', '…', 16)", "bm25(mcp_diary_fts)".to_string())
+                };
+                let sql = format!("SELECT mcp_diary.id, {date}, COALESCE(mcp_diary_fts.title, ''), {snippet},
+                COALESCE(mcp_diary_fts.body, '')
+                FROM mcp_diary_fts JOIN mcp_diary ON mcp_diary.id = mcp_diary_fts.rowid
+                WHERE {where_sql}
+                ORDER BY {order}
+                LIMIT ?{limit}
+                ", date = date_sql, snippet = snippet_sql, where_sql = where_list.join(" AND "),
+                order = order_sql, limit = values.len());
+
+                let conn = connect().await;
+                let mut rows = match conn.query(&sql, values).await {
+                    Ok(rows) => rows,
+                    Err(e) => return error_response(request_id, -32603, format!("Search error: {}", e)),
+                };
+                let mut out_str = String::new();
+                let mut count = 0;
+                while let Some(row) = rows.next().await.unwrap() {
+                    let id: i64 = row.get(0).unwrap();
+                    let entry_date: String = row.get(1).unwrap();
+                    let title: String = row.get(2).unwrap();
+                    let snippet: String = match row.get::<Option<String>>(3).unwrap() {
+                        Some(snippet) => snippet,
+                        None => like_snippet(&row.get::<String>(4).unwrap(), &terms, 16),
+                    };
+                    out_str.push_str(&format!("id: {} , date= {} , title= {}\n{}\n", id, entry_date, title, snippet));
+                    count += 1;
+                }
+                out_str.push_str(&format!("{} 件", count));
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
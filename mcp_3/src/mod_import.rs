@@ -0,0 +1,342 @@
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use super::{connect, error_response};
+
+#[derive(Debug, Deserialize)]
+struct ImportParams {
+    csv: Option<String>,
+    path: Option<String>,
+    delimiter: Option<String>,
+    #[serde(default = "default_true")]
+    has_header: bool,
+    #[serde(default)]
+    columns: ColumnMap,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// 列の指定: 0 始まりの列番号、またはヘッダー名
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct ColumnMap {
+    date: ColumnRef,
+    name: ColumnRef,
+    price: ColumnRef,
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        ColumnMap {
+            date: ColumnRef::Index(0),
+            name: ColumnRef::Index(1),
+            price: ColumnRef::Index(2),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+struct ImportData {
+    name: String,
+    price: i64,
+    date: String,
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_id: Option<i64>,
+}
+
+#[derive(Debug)]
+enum ImportStatus {
+    Insert(ImportData),
+    Duplicate(ImportData),
+    Error(String),
+}
+
+/**
+* 全角の英数字・記号を半角に変換する。
+*
+* @param value
+*
+* @return
+*/
+pub fn to_half_width(value: &str) -> String {
+    value.chars().map(|c| match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        '\u{FFE5}' => '¥',
+        _ => c,
+    }).collect()
+}
+
+/**
+* 日付文字列を YYYY-MM-DD に変換する。
+* 2025/10/03 , 2025-10-3 , 2025.10.03 , 2025年10月3日 , 20251003 と、時刻付きの値に対応。
+*
+* @param value
+*
+* @return
+*/
+pub fn parse_date(value: &str) -> Option<String> {
+    let value = to_half_width(value);
+    let value = value.split_whitespace().next().unwrap_or("");
+    let normalized: String = value
+        .trim_end_matches('日')
+        .chars()
+        .map(|c| if matches!(c, '年' | '月' | '-' | '.') { '/' } else { c })
+        .collect();
+    let parts: Vec<&str> = normalized.split('/').collect();
+    let (year, month, day) = if parts.len() == 3 {
+        (parts[0].parse().ok()?, parts[1].parse().ok()?, parts[2].parse().ok()?)
+    } else if normalized.len() == 8 && normalized.chars().all(|c| c.is_ascii_digit()) {
+        (normalized[0..4].parse().ok()?, normalized[4..6].parse().ok()?, normalized[6..8].parse().ok()?)
+    } else {
+        return None;
+    };
+    NaiveDate::from_ymd_opt(year, month, day).map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/**
+* 金額文字列を数値に変換する。
+* ¥ , ￥ , 円 , 桁区切り , 全角数字と、△ ▲ による負数表記に対応。
+*
+* @param value
+*
+* @return
+*/
+pub fn parse_amount(value: &str) -> Option<i64> {
+    let value = to_half_width(value);
+    let mut negative = false;
+    let mut digits = String::new();
+    for c in value.trim().chars() {
+        match c {
+            '-' | '△' | '▲' => negative = true,
+            '0'..='9' => digits.push(c),
+            '¥' | '\\' | '円' | ',' | ' ' | '+' => {}
+            _ => return None,
+        }
+    }
+    let amount: i64 = digits.parse().ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+fn column_index(column: &ColumnRef, headers: &[String]) -> Result<usize, String> {
+    match column {
+        ColumnRef::Index(index) => Ok(*index),
+        ColumnRef::Name(name) => headers
+            .iter()
+            .position(|header| header.trim() == name.as_str())
+            .ok_or(format!("column not found: {}", name)),
+    }
+}
+
+/**
+* カード / 銀行明細の CSV を読み込み、購入データとして登録する。
+* 既存データと 日付、品名、金額 が一致する行は重複として登録しない。
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_import_csv_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<ImportParams>(arguments.clone()) {
+            Ok(import_params) => {
+                let csv_text = match (&import_params.csv, &import_params.path) {
+                    (Some(csv_text), _) => csv_text.clone(),
+                    (None, Some(path)) => match std::fs::read_to_string(path) {
+                        Ok(csv_text) => csv_text,
+                        Err(e) => {
+                            return error_response(request_id, -32602, format!("File read error: {}, path={}", e, path));
+                        }
+                    },
+                    (None, None) => {
+                        return error_response(request_id, -32602, "Invalid parameters: csv or path is required".to_string());
+                    }
+                };
+                let delimiter = match import_params.delimiter.as_deref() {
+                    None => b',',
+                    Some("\\t") | Some("\t") => b'\t',
+                    Some(d) if d.len() == 1 => d.as_bytes()[0],
+                    Some(d) => {
+                        return error_response(request_id, -32602, format!("Invalid parameters, delimiter={}", d));
+                    }
+                };
+
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .has_headers(import_params.has_header)
+                    .flexible(true)
+                    .from_reader(csv_text.trim_start_matches('\u{FEFF}').as_bytes());
+                let headers: Vec<String> = if import_params.has_header {
+                    match reader.headers() {
+                        Ok(headers) => headers.iter().map(|h| h.to_string()).collect(),
+                        Err(e) => {
+                            return error_response(request_id, -32602, format!("CSV header error: {}", e));
+                        }
+                    }
+                } else {
+                    Vec::new()
+                };
+                let columns = &import_params.columns;
+                let (date_index, name_index, price_index) = match (
+                    column_index(&columns.date, &headers),
+                    column_index(&columns.name, &headers),
+                    column_index(&columns.price, &headers),
+                ) {
+                    (Ok(d), Ok(n), Ok(p)) => (d, n, p),
+                    (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                        return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+                    }
+                };
+
+                let first_line = if import_params.has_header { 2 } else { 1 };
+                let mut parsed: Vec<(usize, Result<ImportData, String>)> = Vec::new();
+                for (i, record) in reader.records().enumerate() {
+                    let line_no = first_line + i;
+                    let record = match record {
+                        Ok(record) => record,
+                        Err(e) => {
+                            parsed.push((line_no, Err(format!("CSV error: {}", e))));
+                            continue;
+                        }
+                    };
+                    if record.iter().all(|field| field.trim().is_empty()) {
+                        continue;
+                    }
+                    let field = |index: usize| record.get(index).unwrap_or("").trim().to_string();
+                    let date = parse_date(&field(date_index));
+                    let price = parse_amount(&field(price_index));
+                    let name = field(name_index);
+                    let row = match (date, price) {
+                        (None, _) => Err(format!("date parse error: {}", field(date_index))),
+                        (_, None) => Err(format!("amount parse error: {}", field(price_index))),
+                        (_, _) if name.is_empty() => Err("name is empty".to_string()),
+                        (Some(date), Some(price)) => Ok(ImportData {
+                            name,
+                            price,
+                            date,
+                            source: "csv".to_string(),
+                            item_id: None,
+                        }),
+                    };
+                    parsed.push((line_no, row));
+                }
+
+                let conn = connect().await;
+
+                // 既存データの (日付, 品名, 金額) ごとの件数。同じ明細が複数ある場合も件数で照合する
+                let mut existing: HashMap<(String, String, i64), i64> = HashMap::new();
+                let dates: Vec<&String> = parsed.iter()
+                    .filter_map(|(_, row)| row.as_ref().ok().map(|data| &data.date))
+                    .collect();
+                if let (Some(min_date), Some(max_date)) = (dates.iter().min(), dates.iter().max()) {
+                    let sql = format!("SELECT {date}, json_extract(data, '$.name'), json_extract(data, '$.price')
+                    FROM item_price
+                    WHERE {date} BETWEEN ?1 AND ?2
+                    ", date = super::mod_purchase::PURCHASE_DATE_SQL);
+                    let mut rows = conn.query(&sql, params![min_date.as_str(), max_date.as_str()]).await.unwrap();
+                    while let Some(row) = rows.next().await.unwrap() {
+                        let key: (String, String, i64) = (
+                            row.get(0).unwrap_or_default(),
+                            row.get(1).unwrap_or_default(),
+                            row.get(2).unwrap_or_default(),
+                        );
+                        *existing.entry(key).or_insert(0) += 1;
+                    }
+                }
+
+                let mut results: Vec<(usize, ImportStatus)> = Vec::new();
+                for (line_no, row) in parsed {
+                    let status = match row {
+                        Err(e) => ImportStatus::Error(e),
+                        Ok(data) => {
+                            let key = (data.date.clone(), data.name.clone(), data.price);
+                            match existing.get_mut(&key) {
+                                Some(count) if *count > 0 => {
+                                    *count -= 1;
+                                    ImportStatus::Duplicate(data)
+                                }
+                                _ => ImportStatus::Insert(data),
+                            }
+                        }
+                    };
+                    results.push((line_no, status));
+                }
+
+                if !import_params.dry_run {
+                    let tx = conn.transaction().await.unwrap();
+                    for (_, status) in results.iter_mut() {
+                        if let ImportStatus::Insert(data) = status {
+                            match super::mod_item::resolve_item_id(&tx, &data.name).await {
+                                Ok(item_id) => data.item_id = Some(item_id),
+                                Err(e) => {
+                                    tx.rollback().await.ok();
+                                    return error_response(request_id, -32603, format!("Item error: {}", e));
+                                }
+                            }
+                            let json_string_variable = serde_json::to_string(data).expect("JSON convert error");
+                            if let Err(e) = tx.execute("INSERT INTO item_price (data) VALUES (?1)", params![json_string_variable]).await {
+                                tx.rollback().await.ok();
+                                return error_response(request_id, -32603, format!("Insert error: {}", e));
+                            }
+                        }
+                    }
+                    if let Err(e) = tx.commit().await {
+                        return error_response(request_id, -32603, format!("Commit error: {}", e));
+                    }
+                }
+
+                let (mut insert_count, mut duplicate_count, mut error_count) = (0, 0, 0);
+                let mut out_str = if import_params.dry_run { "dry_run: 登録は行いません\n".to_string() } else { String::new() };
+                for (line_no, status) in &results {
+                    let row_str = match status {
+                        ImportStatus::Insert(data) => {
+                            insert_count += 1;
+                            format!("line {}: insert , {} , {} , {}\n", line_no, data.date, data.name, data.price)
+                        }
+                        ImportStatus::Duplicate(data) => {
+                            duplicate_count += 1;
+                            format!("line {}: duplicate , {} , {} , {}\n", line_no, data.date, data.name, data.price)
+                        }
+                        ImportStatus::Error(e) => {
+                            error_count += 1;
+                            format!("line {}: error , {}\n", line_no, e)
+                        }
+                    };
+                    out_str.push_str(&row_str);
+                }
+                out_str.push_str(&format!("insert: {} , duplicate: {} , error: {}", insert_count, duplicate_count, error_count));
+                return super::JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: request_id,
+                    result: Some(json!({
+                        "content": [
+                            {
+                                "type": "text",
+                                "text": out_str
+                            }
+                        ]
+                    })),
+                    error: None,
+                };
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
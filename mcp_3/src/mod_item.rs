@@ -0,0 +1,389 @@
+use libsql::Connection;
+use libsql::params;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+use super::{connect, error_response, text_response};
+
+/**
+* 品名の表記ゆれを吸収するための正規化。
+* NFKC (全角英数 → 半角 , 半角カナ → 全角カナ) , カタカナ → ひらがな , 小文字化 , 空白の除去を行う。
+* 珈琲 / coffee のように文字種が異なるものは、別名 (alias) として登録する。
+*
+* @param name
+*
+* @return
+*/
+pub fn normalize_name(name: &str) -> String {
+    name.nfkc()
+        .map(|c| match c {
+            '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .flat_map(|c| c.to_lowercase())
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+async fn find_item_id(conn: &Connection, name: &str) -> Result<Option<i64>, libsql::Error> {
+    let mut rows = conn.query(
+        "SELECT item_id FROM item_alias WHERE normalized = ?1",
+        params![normalize_name(name)],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/**
+* 別名を登録する。同じ品目に登録済みの別名は何もしない。
+* 他の品目の別名は付け替えない (統合は item_merge で行う)。呼び出し側で find_item_id により確認する。
+*
+* @param conn
+* @param item_id
+* @param alias
+*
+* @return
+*/
+async fn add_alias(conn: &Connection, item_id: i64, alias: &str) -> Result<(), libsql::Error> {
+    conn.execute(
+        "INSERT INTO item_alias (item_id, alias, normalized) VALUES (?1, ?2, ?3)
+        ON CONFLICT(normalized) DO NOTHING",
+        params![item_id, alias, normalize_name(alias)],
+    ).await?;
+    Ok(())
+}
+
+async fn create_item(conn: &Connection, name: &str) -> Result<i64, libsql::Error> {
+    conn.execute("INSERT INTO item_master (name) VALUES (?1)", params![name]).await?;
+    let item_id = conn.last_insert_rowid();
+    add_alias(conn, item_id, name).await?;
+    Ok(item_id)
+}
+
+/**
+* 品名に対応するカタログ品目の ID を返す。未登録の場合は品目を作成する。
+*
+* @param conn
+* @param name 品名
+*
+* @return item_master.id
+*/
+pub async fn resolve_item_id(conn: &Connection, name: &str) -> Result<i64, libsql::Error> {
+    match find_item_id(conn, name).await? {
+        Some(item_id) => Ok(item_id),
+        None => create_item(conn, name).await,
+    }
+}
+
+/**
+* item_add: カタログ品目と別名を登録する。name が既存品目の別名の場合は、その品目に別名を追加する。
+* 別名が他の品目に登録済みの場合は Conflict とする。
+*
+* @param
+*
+* @return
+*/
+pub async fn item_add_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct ItemAddParams {
+        name: String,
+        #[serde(default)]
+        aliases: Vec<String>,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<ItemAddParams>(arguments.clone()) {
+            Ok(item_params) => {
+                let conn = connect().await;
+                // 他の品目の別名は付け替えない
+                let found_id = find_item_id(&conn, &item_params.name).await.unwrap();
+                for alias in &item_params.aliases {
+                    if let Some(alias_item_id) = find_item_id(&conn, alias).await.unwrap()
+                        && Some(alias_item_id) != found_id {
+                        return error_response(request_id, -32009,
+                            format!("Conflict, alias={} is registered to item id={} , use item_merge", alias, alias_item_id));
+                    }
+                }
+                let item_id = resolve_item_id(&conn, &item_params.name).await.unwrap();
+                for alias in &item_params.aliases {
+                    add_alias(&conn, item_id, alias).await.unwrap();
+                }
+                // 別名が一致する、品目未設定の購入データを紐付ける
+                let relinked = relink_purchases(&conn).await.unwrap();
+                return text_response(request_id,
+                    format!("Complete item_add, id={} , aliases={} , relinked={}", item_id, item_params.aliases.len(), relinked));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* item_id の無い購入データのうち、品名が登録済みの品目 (別名を含む) に一致するものを紐付ける。
+* 一致しない品名は品目を作成せず、そのままにする。
+*
+* @return 紐付けた件数
+*/
+async fn relink_purchases(conn: &Connection) -> Result<usize, libsql::Error> {
+    let mut rows = conn.query(
+        "SELECT id, json_extract(data, '$.name') FROM item_price WHERE json_extract(data, '$.item_id') IS NULL",
+        (),
+    ).await?;
+    let mut targets: Vec<(i64, String)> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        targets.push((row.get(0)?, row.get::<Option<String>>(1)?.unwrap_or_default()));
+    }
+    let mut count = 0;
+    for (id, name) in targets {
+        if name.is_empty() {
+            continue;
+        }
+        let Some(item_id) = find_item_id(conn, &name).await? else {
+            continue;
+        };
+        conn.execute(
+            "UPDATE item_price SET data = json_set(data, '$.item_id', ?1) WHERE id = ?2",
+            params![item_id, id],
+        ).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/**
+* item_list: カタログ品目と別名の一覧
+*
+* @param
+*
+* @return
+*/
+pub async fn item_list_handler(_params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let conn = connect().await;
+    let mut rows = conn.query(
+        "SELECT m.id, m.name, group_concat(a.alias, ' , ')
+        FROM item_master m LEFT JOIN item_alias a ON a.item_id = m.id
+        GROUP BY m.id ORDER BY m.name",
+        (),
+    ).await.unwrap();
+    let mut out_str = String::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        let id: i64 = row.get(0).unwrap();
+        let name: String = row.get(1).unwrap();
+        let aliases: Option<String> = row.get(2).unwrap();
+        out_str.push_str(&format!("id: {} , name= {} , aliases= {}\n", id, name, aliases.unwrap_or_default()));
+    }
+    text_response(request_id, out_str)
+}
+
+/**
+* item_merge: 品目 id を into_id に統合する。別名と購入データの紐付けを移して、id を削除する。
+*
+* @param
+*
+* @return
+*/
+pub async fn item_merge_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct ItemMergeParams {
+        id: i64,
+        into_id: i64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<ItemMergeParams>(arguments.clone()) {
+            Ok(merge_params) => {
+                if merge_params.id == merge_params.into_id {
+                    return error_response(request_id, -32602, "Invalid parameters: id and into_id are same".to_string());
+                }
+                let conn = connect().await;
+                let mut rows = conn.query(
+                    "SELECT count(*) FROM item_master WHERE id IN (?1, ?2)",
+                    params![merge_params.id, merge_params.into_id],
+                ).await.unwrap();
+                let count: i64 = rows.next().await.unwrap().map(|row| row.get(0).unwrap()).unwrap_or(0);
+                if count != 2 {
+                    return error_response(request_id, -32602,
+                        format!("Invalid parameters, id={} , into_id={}", merge_params.id, merge_params.into_id));
+                }
+                let tx = conn.transaction().await.unwrap();
+                let result = async {
+                    tx.execute("UPDATE item_alias SET item_id = ?1 WHERE item_id = ?2",
+                        params![merge_params.into_id, merge_params.id]).await?;
+                    tx.execute("UPDATE item_price SET data = json_set(data, '$.item_id', ?1) WHERE json_extract(data, '$.item_id') = ?2",
+                        params![merge_params.into_id, merge_params.id]).await?;
+                    tx.execute("DELETE FROM item_master WHERE id = ?1", params![merge_params.id]).await?;
+                    Ok::<(), libsql::Error>(())
+                }.await;
+                if let Err(e) = result {
+                    tx.rollback().await.ok();
+                    return error_response(request_id, -32603, format!("Update error: {}", e));
+                }
+                tx.commit().await.unwrap();
+                return text_response(request_id,
+                    format!("Complete merge, id={} into_id={}", merge_params.id, merge_params.into_id));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+fn median(prices: &[i64]) -> Option<i64> {
+    if prices.is_empty() {
+        return None;
+    }
+    let mut sorted = prices.to_vec();
+    sorted.sort();
+    let mid = sorted.len() / 2;
+    Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2 } else { sorted[mid] })
+}
+
+/**
+* item_price_history: 品目の価格推移を、全体と店舗別に表示する。
+* 今日の価格 (price 指定時はその値) が、通常の価格 (過去の中央値) より高い場合に知らせる。
+*
+* @param
+*
+* @return
+*/
+pub async fn item_price_history_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct HistoryParams {
+        name: String,
+        store: Option<String>,
+        price: Option<i64>,
+    }
+    struct HistoryRow {
+        date: String,
+        store: String,
+        unit_price: i64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<HistoryParams>(arguments.clone()) {
+            Ok(history_params) => {
+                let conn = connect().await;
+                let item_id = match find_item_id(&conn, &history_params.name).await.unwrap() {
+                    Some(item_id) => item_id,
+                    None => {
+                        return error_response(request_id, -32602, format!("Item not found, name={}", history_params.name));
+                    }
+                };
+                let sql = format!("SELECT {}, COALESCE(json_extract(data, '$.store'), ''),
+                {}, COALESCE(json_extract(data, '$.quantity'), 1)
+                FROM item_price
+                WHERE json_extract(data, '$.item_id') = ?1
+                ORDER BY 1, id
+                ", super::mod_purchase::PURCHASE_DATE_SQL, super::mod_currency::base_price_sql());
+                let mut rows = conn.query(&sql, params![item_id]).await.unwrap();
+                let mut history: Vec<HistoryRow> = Vec::new();
+                while let Some(row) = rows.next().await.unwrap() {
+                    // 為替レート未登録の外貨は、比較できないため除く
+                    let Some(price) = row.get::<Option<i64>>(2).unwrap() else {
+                        continue;
+                    };
+                    let quantity: i64 = row.get(3).unwrap();
+                    let store: String = row.get(1).unwrap();
+                    let store_matched = match &history_params.store {
+                        Some(filter_store) => normalize_name(filter_store) == normalize_name(&store),
+                        None => true,
+                    };
+                    if !store_matched {
+                        continue;
+                    }
+                    history.push(HistoryRow {
+                        date: row.get(0).unwrap(),
+                        store,
+                        unit_price: if quantity > 0 { price / quantity } else { price },
+                    });
+                }
+                if history.is_empty() {
+                    return text_response(request_id, format!("{}: 購入データがありません", history_params.name));
+                }
+
+                let mut out_str = format!("{} (item_id={})\n", history_params.name, item_id);
+                for row in &history {
+                    let store = if row.store.is_empty() { "-" } else { row.store.as_str() };
+                    out_str.push_str(&format!("{} , {} , {}円\n", row.date, store, row.unit_price));
+                }
+                let mut by_store: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+                for row in &history {
+                    by_store.entry(if row.store.is_empty() { "-" } else { row.store.as_str() })
+                        .or_default()
+                        .push(row.unit_price);
+                }
+                out_str.push_str("店舗別:\n");
+                for (store, prices) in &by_store {
+                    out_str.push_str(&format!("{} , 件数 {} , 最安 {}円 , 最高 {}円 , 通常 {}円\n",
+                        store, prices.len(), prices.iter().min().unwrap(), prices.iter().max().unwrap(),
+                        median(prices).unwrap()));
+                }
+
+                let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                let past: Vec<i64> = history.iter().filter(|row| row.date < today).map(|row| row.unit_price).collect();
+                let today_prices: Vec<i64> = match history_params.price {
+                    Some(price) => vec![price],
+                    None => history.iter().filter(|row| row.date == today).map(|row| row.unit_price).collect(),
+                };
+                if let (Some(usual), Some(today_price)) = (median(&past), today_prices.iter().max()) {
+                    if *today_price > usual {
+                        out_str.push_str(&format!("注意: 今日の価格 {}円 は、通常の価格 {}円 より高いです。", today_price, usual));
+                    } else {
+                        out_str.push_str(&format!("今日の価格 {}円 は、通常の価格 {}円 以下です。", today_price, usual));
+                    }
+                }
+                return text_response(request_id, out_str);
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn katakana_and_hiragana_are_same() {
+        assert_eq!(normalize_name("コーヒー"), "こーひー");
+        assert_eq!(normalize_name("こーひー"), normalize_name("コーヒー"));
+    }
+
+    #[test]
+    fn half_width_kana_is_normalized() {
+        assert_eq!(normalize_name("ｺｰﾋｰ"), normalize_name("コーヒー"));
+        assert_eq!(normalize_name("ｶﾞﾑ"), "がむ");
+    }
+
+    #[test]
+    fn full_width_alphanumerics_are_lowercased() {
+        assert_eq!(normalize_name("ＣＯＦＦＥＥ１"), "coffee1");
+        assert_eq!(normalize_name("Coffee"), "coffee");
+    }
+
+    #[test]
+    fn whitespace_is_removed() {
+        assert_eq!(normalize_name(" 牛乳 1L "), "牛乳1l");
+        assert_eq!(normalize_name("牛乳　１Ｌ"), "牛乳1l");
+    }
+
+    #[test]
+    fn different_scripts_are_not_merged() {
+        assert_ne!(normalize_name("珈琲"), normalize_name("コーヒー"));
+    }
+}
@@ -0,0 +1,406 @@
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use super::{connect, error_response, text_response};
+
+/// 割り勘の方法。values は ratio: 比率 , exact: 金額
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SplitParams {
+    #[serde(rename = "type", default = "default_split_type")]
+    split_type: String,
+    #[serde(default)]
+    values: BTreeMap<String, f64>,
+}
+
+fn default_split_type() -> String {
+    "equal".to_string()
+}
+
+// split を省略した場合は均等に割る
+impl Default for SplitParams {
+    fn default() -> Self {
+        SplitParams {
+            split_type: default_split_type(),
+            values: BTreeMap::new(),
+        }
+    }
+}
+
+/**
+* 支払額を参加者ごとの負担額に分ける。負担額の合計は必ず price に一致する。
+* equal / ratio の端数は、最大剰余法で1円ずつ配分する。
+*
+* @param price 支払額
+* @param participants 参加者
+* @param split 割り勘の方法
+*
+* @return 参加者ごとの負担額
+*/
+pub fn compute_shares(price: i64, participants: &[String], split: &SplitParams) -> Result<BTreeMap<String, i64>, String> {
+    let weights: Vec<(String, f64)> = match split.split_type.as_str() {
+        "equal" => {
+            if participants.is_empty() {
+                return Err("participants is empty".to_string());
+            }
+            participants.iter().map(|user| (user.clone(), 1.0)).collect()
+        }
+        "ratio" => {
+            if split.values.is_empty() || split.values.values().any(|v| *v < 0.0) {
+                return Err("split.values must be positive ratios".to_string());
+            }
+            split.values.iter().map(|(user, v)| (user.clone(), *v)).collect()
+        }
+        "exact" => {
+            let mut shares: BTreeMap<String, i64> = BTreeMap::new();
+            for (user, amount) in &split.values {
+                if *amount < 0.0 {
+                    return Err(format!("split.values must not be negative, {}={}", user, amount));
+                }
+                shares.insert(user.clone(), amount.round() as i64);
+            }
+            let total: i64 = shares.values().sum();
+            if total != price {
+                return Err(format!("split.values total {} does not match price {}", total, price));
+            }
+            return Ok(shares);
+        }
+        split_type => return Err(format!("split.type={}", split_type)),
+    };
+    let weight_sum: f64 = weights.iter().map(|(_, w)| w).sum();
+    if weight_sum <= 0.0 {
+        return Err("split.values total is 0".to_string());
+    }
+
+    let mut shares: BTreeMap<String, i64> = BTreeMap::new();
+    let mut remainders: Vec<(f64, String)> = Vec::new();
+    for (user, weight) in &weights {
+        let exact = price as f64 * weight / weight_sum;
+        let share = exact.floor() as i64;
+        *shares.entry(user.clone()).or_insert(0) += share;
+        remainders.push((exact - share as f64, user.clone()));
+    }
+    let mut rest = price - shares.values().sum::<i64>();
+    remainders.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, user) in remainders.iter().cycle() {
+        if rest <= 0 {
+            break;
+        }
+        *shares.get_mut(user).unwrap() += 1;
+        rest -= 1;
+    }
+    Ok(shares)
+}
+
+/**
+* 貸し借りの残高から、精算に必要な送金の一覧を作る。
+* 残高の合計が 0 になる組にできるだけ多く分けると、送金は (人数 - 組の数) 回で最小になる。
+* 組の中は、最も多く立て替えた人と最も多く負担が残っている人を順に組み合わせる。
+* 人数が SETTLE_EXACT_MAX を超える場合は組に分けず、送金は最大 (人数 - 1) 回になる。
+*
+* @param balances 立て替え額 - 負担額 (正: 受け取る , 負: 支払う)
+*
+* @return (支払う人, 受け取る人, 金額)
+*/
+pub fn settle_transfers(balances: &BTreeMap<String, i64>) -> Vec<(String, String, i64)> {
+    let members: Vec<(String, i64)> = balances.iter().filter(|(_, b)| **b != 0).map(|(u, b)| (u.clone(), *b)).collect();
+    let mut transfers: Vec<(String, String, i64)> = Vec::new();
+    for group in zero_sum_groups(&members) {
+        transfers.extend(settle_group(&group));
+    }
+    transfers
+}
+
+// 組に分ける人数の上限 (2^n の組み合わせを調べる)
+const SETTLE_EXACT_MAX: usize = 16;
+
+/**
+* 残高を、合計が 0 になる組にできるだけ多く分ける。
+* groups[mask] = mask の人を分けたときの組の数の最大 として、1 人ずつ取り除いて求める。
+*
+* @param members (人, 残高)
+*
+* @return 組の一覧
+*/
+fn zero_sum_groups(members: &[(String, i64)]) -> Vec<Vec<(String, i64)>> {
+    let count = members.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    if count > SETTLE_EXACT_MAX {
+        return vec![members.to_vec()];
+    }
+    let full = (1usize << count) - 1;
+    let mut sums = vec![0i64; full + 1];
+    let mut groups = vec![0usize; full + 1];
+    for mask in 1..=full {
+        let lowest = mask.trailing_zeros() as usize;
+        sums[mask] = sums[mask & (mask - 1)] + members[lowest].1;
+        let best = (0..count)
+            .filter(|i| mask & (1 << i) != 0)
+            .map(|i| groups[mask ^ (1 << i)])
+            .max()
+            .unwrap_or(0);
+        groups[mask] = best + usize::from(sums[mask] == 0);
+    }
+    // 取り除いた順にたどり、合計が 0 になった所で組を区切る
+    let mut result: Vec<Vec<(String, i64)>> = Vec::new();
+    let mut current: Vec<(String, i64)> = Vec::new();
+    let mut mask = full;
+    while mask != 0 {
+        let removed = usize::from(sums[mask] == 0);
+        let i = (0..count)
+            .find(|i| mask & (1 << i) != 0 && groups[mask ^ (1 << i)] + removed == groups[mask])
+            .unwrap();
+        current.push(members[i].clone());
+        mask ^= 1 << i;
+        if sums[mask] == 0 {
+            result.push(std::mem::take(&mut current));
+        }
+    }
+    result
+}
+
+fn settle_group(group: &[(String, i64)]) -> Vec<(String, String, i64)> {
+    let mut creditors: Vec<(String, i64)> = group.iter().filter(|(_, b)| *b > 0).cloned().collect();
+    let mut debtors: Vec<(String, i64)> = group.iter().filter(|(_, b)| *b < 0).map(|(u, b)| (u.clone(), -*b)).collect();
+    let mut transfers: Vec<(String, String, i64)> = Vec::new();
+    loop {
+        creditors.sort_by_key(|c| std::cmp::Reverse(c.1));
+        debtors.sort_by_key(|d| std::cmp::Reverse(d.1));
+        let (Some(creditor), Some(debtor)) = (creditors.first_mut(), debtors.first_mut()) else {
+            break;
+        };
+        if creditor.1 == 0 || debtor.1 == 0 {
+            break;
+        }
+        let amount = creditor.1.min(debtor.1);
+        transfers.push((debtor.0.clone(), creditor.0.clone(), amount));
+        creditor.1 -= amount;
+        debtor.1 -= amount;
+    }
+    transfers
+}
+
+/**
+* purchase_split: 登録済みの購入データに、支払者と割り勘を設定する。
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_split_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct PurchaseSplitParams {
+        id: i64,
+        payer: String,
+        #[serde(default)]
+        participants: Vec<String>,
+        #[serde(default)]
+        split: SplitParams,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<PurchaseSplitParams>(arguments.clone()) {
+            Ok(split_params) => {
+                let conn = connect().await;
+                let sql = format!("SELECT {} FROM item_price WHERE id = ?1", super::mod_currency::base_price_sql());
+                let mut rows = conn.query(&sql, params![split_params.id]).await.unwrap();
+                let price: i64 = match rows.next().await.unwrap().map(|row| row.get::<Option<i64>>(0).unwrap()) {
+                    Some(Some(price)) => price,
+                    Some(None) => {
+                        return error_response(request_id, -32602, format!("Exchange rate is not registered, id={}", split_params.id));
+                    }
+                    None => {
+                        return error_response(request_id, -32602, format!("Invalid parameters, id={}", split_params.id));
+                    }
+                };
+                let participants = if split_params.participants.is_empty() {
+                    vec![split_params.payer.clone()]
+                } else {
+                    split_params.participants.clone()
+                };
+                let shares = match compute_shares(price, &participants, &split_params.split) {
+                    Ok(shares) => shares,
+                    Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+                };
+                conn.execute(
+                    "UPDATE item_price SET data = json_set(data, '$.payer', ?1, '$.shares', json(?2)),
+                    updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+                    params![split_params.payer.as_str(), json!(shares).to_string(), split_params.id],
+                ).await.unwrap();
+                let shares_str: Vec<String> = shares.iter().map(|(user, share)| format!("{}: {}円", user, share)).collect();
+                return text_response(request_id, format!(
+                    "Complete split, id={} , payer= {} , {}", split_params.id, split_params.payer, shares_str.join(" , ")
+                ));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* settle_up: 期間内の立て替えを集計し、精算に必要な送金を表示する。
+* 割り勘を設定した購入データの支払額は、負担額 ($.shares) の合計とする。
+* 負担額は設定時の為替レートで換算して保存しているため、支払と負担を同じ金額で集計する。
+*
+* @param
+*
+* @return
+*/
+pub async fn settle_up_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct SettleUpParams {
+        from: Option<String>,
+        to: Option<String>,
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let settle_params = match serde_json::from_value::<SettleUpParams>(arguments) {
+        Ok(settle_params) => settle_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let date_sql = super::mod_purchase::PURCHASE_DATE_SQL;
+    let sql = format!("SELECT json_extract(data, '$.payer'), {base_price}, json_extract(data, '$.shares')
+    FROM item_price
+    WHERE json_extract(data, '$.payer') IS NOT NULL
+    AND (?1 IS NULL OR {date} >= ?1) AND (?2 IS NULL OR {date} <= ?2)
+    ", base_price = super::mod_currency::base_price_sql(), date = date_sql);
+    let conn = connect().await;
+    let mut rows = conn.query(&sql, params![settle_params.from.clone(), settle_params.to.clone()]).await.unwrap();
+
+    let mut paid: BTreeMap<String, i64> = BTreeMap::new();
+    let mut owed: BTreeMap<String, i64> = BTreeMap::new();
+    let mut count = 0;
+    while let Some(row) = rows.next().await.unwrap() {
+        let payer: String = row.get(0).unwrap();
+        let shares_json: Option<String> = row.get(2).unwrap();
+        let shares: BTreeMap<String, i64> = match shares_json.and_then(|s| serde_json::from_str(&s).ok()) {
+            Some(shares) => shares,
+            None => match row.get::<Option<i64>>(1).unwrap() {
+                Some(price) => BTreeMap::from([(payer.clone(), price)]),
+                None => continue,
+            },
+        };
+        *paid.entry(payer).or_insert(0) += shares.values().sum::<i64>();
+        for (user, share) in shares {
+            *owed.entry(user).or_insert(0) += share;
+        }
+        count += 1;
+    }
+
+    let mut balances: BTreeMap<String, i64> = BTreeMap::new();
+    for (user, amount) in &paid {
+        *balances.entry(user.clone()).or_insert(0) += amount;
+    }
+    for (user, amount) in &owed {
+        *balances.entry(user.clone()).or_insert(0) -= amount;
+    }
+
+    let mut out_str = format!("期間: {} - {} , 対象 {} 件\n",
+        settle_params.from.unwrap_or("-".to_string()), settle_params.to.unwrap_or("-".to_string()), count);
+    for (user, balance) in &balances {
+        out_str.push_str(&format!("{}: 支払 {}円 , 負担 {}円 , 差額 {}円\n",
+            user, paid.get(user).unwrap_or(&0), owed.get(user).unwrap_or(&0), balance));
+    }
+    let transfers = settle_transfers(&balances);
+    if transfers.is_empty() {
+        out_str.push_str("精算は不要です。");
+    } else {
+        out_str.push_str("精算:\n");
+        for (from, to, amount) in &transfers {
+            out_str.push_str(&format!("{} → {} : {}円\n", from, to, amount));
+        }
+    }
+    text_response(request_id, out_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn split(split_type: &str, values: &[(&str, f64)]) -> SplitParams {
+        SplitParams {
+            split_type: split_type.to_string(),
+            values: values.iter().map(|(user, v)| (user.to_string(), *v)).collect(),
+        }
+    }
+
+    fn balances(values: &[(&str, i64)]) -> BTreeMap<String, i64> {
+        values.iter().map(|(user, b)| (user.to_string(), *b)).collect()
+    }
+
+    #[test]
+    fn equal_split_allocates_remainder() {
+        let shares = compute_shares(1000, &users(&["a", "b", "c"]), &SplitParams::default()).unwrap();
+        assert_eq!(shares, balances(&[("a", 334), ("b", 333), ("c", 333)]));
+    }
+
+    #[test]
+    fn ratio_split_allocates_remainder_by_largest_fraction() {
+        let shares = compute_shares(1000, &[], &split("ratio", &[("a", 2.0), ("b", 1.0)])).unwrap();
+        assert_eq!(shares, balances(&[("a", 667), ("b", 333)]));
+        let shares = compute_shares(101, &[], &split("ratio", &[("a", 1.0), ("b", 1.0), ("c", 1.0), ("d", 1.0)])).unwrap();
+        assert_eq!(shares.values().sum::<i64>(), 101);
+    }
+
+    #[test]
+    fn exact_split_must_match_price() {
+        let shares = compute_shares(1000, &[], &split("exact", &[("a", 700.0), ("b", 300.0)])).unwrap();
+        assert_eq!(shares, balances(&[("a", 700), ("b", 300)]));
+        assert!(compute_shares(1000, &[], &split("exact", &[("a", 700.0), ("b", 200.0)])).is_err());
+    }
+
+    #[test]
+    fn exact_split_rejects_negative_amount() {
+        assert!(compute_shares(1000, &[], &split("exact", &[("a", 1200.0), ("b", -200.0)])).is_err());
+    }
+
+    #[test]
+    fn invalid_split_is_rejected() {
+        assert!(compute_shares(1000, &[], &split("equal", &[])).is_err());
+        assert!(compute_shares(1000, &[], &split("ratio", &[("a", 0.0)])).is_err());
+        assert!(compute_shares(1000, &users(&["a"]), &split("weight", &[])).is_err());
+    }
+
+    #[test]
+    fn zero_sum_groups_are_maximized() {
+        let members: Vec<(String, i64)> = balances(&[("a", 3), ("b", 4), ("c", -2), ("d", -2), ("e", -3)]).into_iter().collect();
+        let groups = zero_sum_groups(&members);
+        assert_eq!(groups.len(), 2);
+        for group in &groups {
+            assert_eq!(group.iter().map(|(_, b)| b).sum::<i64>(), 0);
+        }
+    }
+
+    #[test]
+    fn settle_transfers_uses_minimal_count() {
+        // 最も多い人同士を組み合わせるだけだと 4 回になる
+        let before = balances(&[("a", 3), ("b", 4), ("c", -2), ("d", -2), ("e", -3)]);
+        let transfers = settle_transfers(&before);
+        assert_eq!(transfers.len(), 3);
+        let mut after = before.clone();
+        for (from, to, amount) in &transfers {
+            assert!(*amount > 0);
+            *after.get_mut(from).unwrap() += amount;
+            *after.get_mut(to).unwrap() -= amount;
+        }
+        assert!(after.values().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn settle_transfers_skips_settled_members() {
+        assert!(settle_transfers(&balances(&[("a", 0), ("b", 0)])).is_empty());
+        assert_eq!(settle_transfers(&balances(&[("a", 500), ("b", -500), ("c", 0)])),
+            vec![("b".to_string(), "a".to_string(), 500)]);
+    }
+}
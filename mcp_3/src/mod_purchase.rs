@@ -17,7 +17,15 @@ struct AddTenParams {
 #[derive(Debug, Deserialize,Serialize)]
 struct PurchaseParams {
     name: String,
-    price: i32,
+    price: Option<i32>,
+    // 外貨の場合は、通貨コードと金額 (小数可)
+    currency: Option<String>,
+    amount: Option<f64>,
+    payer: Option<String>,
+    #[serde(default)]
+    participants: Vec<String>,
+    #[serde(default)]
+    split: super::mod_ledger::SplitParams,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,11 +40,86 @@ struct PurchaseDeleteParams {
     id: i32,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct PurchaseFilterParams {
+    from: Option<String>,
+    to: Option<String>,
+    name: Option<String>,
+    user: Option<String>,
+    limit: Option<i64>,
+}
+
+// 購入日: data.date が無い行は登録日とする
+pub const PURCHASE_DATE_SQL: &str = "COALESCE(json_extract(data, '$.date'), date(created_at))";
+
+/**
+* purchase_list , purchase_export 共通の検索条件 SQL を作成する。
+*
+* @param filter 検索条件
+* @param default_limit limit 未指定時の件数 (None: 全件)
+*
+* @return WHERE , ORDER BY , LIMIT 句と bind 値
+*/
+pub fn purchase_filter_sql(filter: &PurchaseFilterParams, default_limit: Option<i64>) -> (String, Vec<libsql::Value>) {
+    let mut where_list: Vec<String> = Vec::new();
+    let mut values: Vec<libsql::Value> = Vec::new();
+    if let Some(from) = &filter.from {
+        values.push(libsql::Value::Text(from.clone()));
+        where_list.push(format!("{} >= ?{}", PURCHASE_DATE_SQL, values.len()));
+    }
+    if let Some(to) = &filter.to {
+        values.push(libsql::Value::Text(to.clone()));
+        where_list.push(format!("{} <= ?{}", PURCHASE_DATE_SQL, values.len()));
+    }
+    if let Some(name) = &filter.name {
+        values.push(libsql::Value::Text(format!("%{}%", name)));
+        where_list.push(format!("json_extract(data, '$.name') LIKE ?{}", values.len()));
+    }
+    if let Some(user) = &filter.user {
+        // 支払者、または割り勘の参加者
+        values.push(libsql::Value::Text(user.clone()));
+        where_list.push(format!("(json_extract(data, '$.payer') = ?{n}
+            OR EXISTS (SELECT 1 FROM json_each(data, '$.shares') WHERE key = ?{n}))", n = values.len()));
+    }
+    let mut sql = String::new();
+    if !where_list.is_empty() {
+        sql = format!("WHERE {} ", where_list.join(" AND "));
+    }
+    sql.push_str("ORDER BY created_at DESC");
+    if let Some(limit) = filter.limit.or(default_limit) {
+        values.push(libsql::Value::Integer(limit));
+        sql.push_str(&format!(" LIMIT ?{}", values.len()));
+    }
+    (sql, values)
+}
+
 
 pub fn purchase(product_name: String, price: i32) -> String {
     format!("「{}」を{}円で購入しました。", product_name, price)
 }
 
+/**
+* 購入金額を、基準通貨の価格と元の通貨の金額に分ける。
+* 外貨は今日のレートで換算し、レートが未登録の場合は価格無し (None) とする。
+*
+* @return (通貨, 金額, 基準通貨の価格)
+*/
+async fn purchase_amount(conn: &Connection, purchase_params: &PurchaseParams) -> Result<(String, f64, Option<i64>), String> {
+    let base = super::mod_currency::base_currency();
+    let currency = purchase_params.currency.clone().unwrap_or(base.clone()).to_uppercase();
+    if !super::mod_currency::is_currency_code(&currency) {
+        return Err(format!("currency={}", currency));
+    }
+    let amount = match (purchase_params.amount, purchase_params.price) {
+        (Some(amount), _) => amount,
+        (None, Some(price)) if currency == base => price as f64,
+        _ => return Err("price or amount is required".to_string()),
+    };
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let rate = super::mod_currency::find_rate(conn, &currency, &today).await.map_err(|e| e.to_string())?;
+    Ok((currency, amount, rate.map(|rate| (amount * rate).round() as i64)))
+}
+
 
 pub async fn purchase_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
 {
@@ -56,19 +139,75 @@ pub async fn purchase_handler(params: Value, request_id: Option<Value>) -> super
             if let Some(arguments) = params.get("arguments") {
                 match serde_json::from_value::<PurchaseParams>(arguments.clone()) {
                     Ok(purchase_params) => {
-                        let post_data = PurchaseParams {
-                            name: purchase_params.name.clone(),
-                            price: purchase_params.price
-                        };    
+                        let (currency, amount, price) = match purchase_amount(&conn, &purchase_params).await {
+                            Ok(purchase_amount) => purchase_amount,
+                            Err(e) => {
+                                return super::JsonRpcResponse {
+                                    jsonrpc: "2.0".to_string(),
+                                    id: request_id,
+                                    result: None,
+                                    error: Some(super::JsonRpcError {
+                                        code: -32602,
+                                        message: format!("Invalid parameters: {}", e),
+                                    }),
+                                };
+                            }
+                        };
+                        let item_id = super::mod_item::resolve_item_id(&conn, &purchase_params.name).await.unwrap();
+                        let mut post_data = json!({
+                            "name": purchase_params.name.clone(),
+                            "price": price,
+                            "currency": currency,
+                            "amount": amount,
+                            "item_id": item_id
+                        });    
+                        if let Some(payer) = &purchase_params.payer {
+                            let Some(price) = price else {
+                                return super::JsonRpcResponse {
+                                    jsonrpc: "2.0".to_string(),
+                                    id: request_id,
+                                    result: None,
+                                    error: Some(super::JsonRpcError {
+                                        code: -32602,
+                                        message: format!("Invalid parameters: exchange rate for {} is not registered", currency),
+                                    }),
+                                };
+                            };
+                            let participants = if purchase_params.participants.is_empty() {
+                                vec![payer.clone()]
+                            } else {
+                                purchase_params.participants.clone()
+                            };
+                            match super::mod_ledger::compute_shares(price, &participants, &purchase_params.split) {
+                                Ok(shares) => {
+                                    post_data["payer"] = json!(payer);
+                                    post_data["shares"] = json!(shares);
+                                }
+                                Err(e) => {
+                                    return super::JsonRpcResponse {
+                                        jsonrpc: "2.0".to_string(),
+                                        id: request_id,
+                                        result: None,
+                                        error: Some(super::JsonRpcError {
+                                            code: -32602,
+                                            message: format!("Invalid parameters: {}", e),
+                                        }),
+                                    };
+                                }
+                            }
+                        }
                         let json_string_variable = serde_json::to_string(&post_data).expect("JSON convert error");
                         println!("変換されたJSON文字列: {}", json_string_variable); 
-                        let sql = format!("INSERT INTO item_price (data) VALUES ('{}')", &json_string_variable);
                         let mut result = conn
-                            .execute(&sql, ())
+                            .execute("INSERT INTO item_price (data) VALUES (?1)", params![json_string_variable])
                             .await
                             .unwrap();
 
-                        let result = purchase(purchase_params.name, purchase_params.price);
+                        let result = match price {
+                            Some(price) if currency == super::mod_currency::base_currency() => purchase(purchase_params.name, price as i32),
+                            Some(price) => format!("「{}」を {} {} ({}円) で購入しました。", purchase_params.name, amount, currency, price),
+                            None => format!("「{}」を {} {} で購入しました。(為替レート未登録)", purchase_params.name, amount, currency),
+                        };
                         return super::JsonRpcResponse {
                             jsonrpc: "2.0".to_string(),
                             id: request_id,
@@ -121,7 +260,11 @@ pub async fn purchase_list_handler(params: Value, request_id: Option<Value>) ->
     #[derive(Debug, Deserialize)]
     struct ItemData {
         name: String,
-        price: i32,
+        price: Option<i64>,
+        currency: Option<String>,
+        amount: Option<f64>,
+        payer: Option<String>,
+        shares: Option<std::collections::BTreeMap<String, i64>>,
     }
 
     let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
@@ -134,16 +277,33 @@ pub async fn purchase_list_handler(params: Value, request_id: Option<Value>) ->
     
     if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
         if tool_name == "purchase_list" {
-            let order_sql = "ORDER BY created_at DESC LIMIT 5;";
+            let filter = match params.get("arguments") {
+                Some(arguments) => match serde_json::from_value::<PurchaseFilterParams>(arguments.clone()) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        return super::JsonRpcResponse {
+                            jsonrpc: "2.0".to_string(),
+                            id: request_id,
+                            result: None,
+                            error: Some(super::JsonRpcError {
+                                code: -32602,
+                                message: format!("Invalid parameters: {}", e),
+                            }),
+                        };
+                    }
+                },
+                None => PurchaseFilterParams::default(),
+            };
+            let (filter_sql, filter_values) = purchase_filter_sql(&filter, Some(5));
             let sql = format!("SELECT id, data ,created_at, updated_at 
             FROM item_price
             {}
             "
-            , order_sql
+            , filter_sql
             );
             println!("sql={}", sql);
             let mut rows = conn.query(&sql,
-                (),  // 引数なし
+                filter_values,
             ).await.unwrap();
             let mut todos: Vec<Item> = Vec::new();
             let mut out_str: String = "".to_string();
@@ -158,7 +318,19 @@ pub async fn purchase_list_handler(params: Value, request_id: Option<Value>) ->
                 });  
                 let row_item: ItemData = serde_json::from_str(&data).expect("data JSON decord error");
                 println!("デコードされた構造体: {:?}", row_item);  
-                let row_str: String = format!("id: {} , name= {} price= {}\n", id, row_item.name, row_item.price);    
+                let price_str = row_item.price.map(|price| price.to_string()).unwrap_or("-".to_string());
+                let mut row_str: String = format!("id: {} , name= {} price= {}", id, row_item.name, price_str);    
+                if let (Some(currency), Some(amount)) = (&row_item.currency, row_item.amount)
+                    && *currency != super::mod_currency::base_currency() {
+                    row_str.push_str(&format!(" ({} {})", amount, currency));
+                }
+                if let Some(payer) = &row_item.payer {
+                    row_str.push_str(&format!(" payer= {}", payer));
+                }
+                if let (Some(user), Some(shares)) = (&filter.user, &row_item.shares) {
+                    row_str.push_str(&format!(" share= {}", shares.get(user).unwrap_or(&0)));
+                }
+                row_str.push('\n');
                 println!("row_str: {:?}", row_str); 
                 out_str = format!("{}{}", &out_str, &row_str); 
             }
@@ -288,3 +460,140 @@ pub async fn purchase_delete_handler(params: Value, request_id: Option<Value>) -
         }),
     }
 }
+
+/**
+* purchase_list と同じ検索条件で、購入データを CSV / JSON に出力する。
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_export_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+{
+    #[derive(Debug, Deserialize)]
+    struct ExportParams {
+        #[serde(default = "default_export_format")]
+        format: String,
+        path: Option<String>,
+        #[serde(flatten)]
+        filter: PurchaseFilterParams,
+    }
+    #[derive(Debug, Serialize)]
+    struct ExportRow {
+        id: i64,
+        date: String,
+        name: String,
+        // 基準通貨に換算した価格 (レート未登録の場合は空)
+        price: Option<i64>,
+        currency: String,
+        amount: Option<f64>,
+        store: String,
+        created_at: String,
+    }
+    fn default_export_format() -> String {
+        "csv".to_string()
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let export_params = match serde_json::from_value::<ExportParams>(arguments) {
+        Ok(export_params) => export_params,
+        Err(e) => {
+            return super::JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: request_id,
+                result: None,
+                error: Some(super::JsonRpcError {
+                    code: -32602,
+                    message: format!("Invalid parameters: {}", e),
+                }),
+            };
+        }
+    };
+    if export_params.format != "csv" && export_params.format != "json" {
+        return super::JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: request_id,
+            result: None,
+            error: Some(super::JsonRpcError {
+                code: -32602,
+                message: format!("Invalid parameters, format={}", export_params.format),
+            }),
+        };
+    }
+
+    dotenv().ok();
+    let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
+    let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
+    let db = Builder::new_remote(url, token).build().await.unwrap();
+    let conn = db.connect().unwrap();
+
+    let (filter_sql, filter_values) = purchase_filter_sql(&export_params.filter, None);
+    let sql = format!("SELECT id, {} ,COALESCE(json_extract(data, '$.name'), ''), {},
+    COALESCE(json_extract(data, '$.store'), ''), created_at,
+    {}, {}
+    FROM item_price
+    {}
+    "
+    , PURCHASE_DATE_SQL, super::mod_currency::base_price_sql(),
+    super::mod_currency::currency_sql(), super::mod_currency::amount_sql(), filter_sql
+    );
+    let mut rows = conn.query(&sql, filter_values).await.unwrap();
+    let mut export_rows: Vec<ExportRow> = Vec::new();
+    while let Some(row) = rows.next().await.unwrap() {
+        export_rows.push(ExportRow {
+            id: row.get(0).unwrap(),
+            date: row.get(1).unwrap(),
+            name: row.get(2).unwrap(),
+            price: row.get(3).unwrap(),
+            currency: row.get(6).unwrap(),
+            amount: row.get(7).unwrap(),
+            store: row.get(4).unwrap(),
+            created_at: row.get(5).unwrap(),
+        });
+    }
+
+    let out_str = if export_params.format == "json" {
+        serde_json::to_string(&export_rows).expect("JSON convert error")
+    } else {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        // 0 件の場合も header は出力する (serialize は最初の行で header を書く)
+        if export_rows.is_empty() {
+            writer.write_record(["id", "date", "name", "price", "currency", "amount", "store", "created_at"]).expect("CSV convert error");
+        }
+        for export_row in &export_rows {
+            writer.serialize(export_row).expect("CSV convert error");
+        }
+        String::from_utf8(writer.into_inner().expect("CSV convert error")).expect("CSV convert error")
+    };
+
+    let resp = match &export_params.path {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, &out_str) {
+                return super::JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: request_id,
+                    result: None,
+                    error: Some(super::JsonRpcError {
+                        code: -32603,
+                        message: format!("File write error: {}", e),
+                    }),
+                };
+            }
+            format!("Complete export, {} rows, path={}", export_rows.len(), path)
+        }
+        None => out_str,
+    };
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": resp
+                }
+            ]
+        })),
+        error: None,
+    }
+}
@@ -0,0 +1,288 @@
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use super::{connect, error_response};
+
+#[derive(Debug, Deserialize)]
+struct ReceiptParams {
+    store: String,
+    date: String,
+    items: Vec<ReceiptItemParams>,
+    #[serde(default)]
+    tax_included: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReceiptItemParams {
+    name: String,
+    #[serde(default = "default_quantity")]
+    quantity: i32,
+    unit_price: i32,
+    #[serde(default = "default_tax_rate")]
+    tax_rate: i32,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+// 軽減税率 (食料品) を既定とする
+fn default_tax_rate() -> i32 {
+    8
+}
+
+#[derive(Debug, Serialize)]
+struct ReceiptData {
+    store: String,
+    date: String,
+    tax_included: bool,
+    subtotal: i64,
+    tax_8: i64,
+    tax_10: i64,
+    total: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct ReceiptLineData {
+    name: String,
+    price: i64,
+    receipt_id: i64,
+    item_id: i64,
+    store: String,
+    date: String,
+    quantity: i32,
+    unit_price: i32,
+    tax_rate: i32,
+    tax: i64,
+}
+
+#[derive(Debug)]
+struct ReceiptLine {
+    name: String,
+    quantity: i32,
+    unit_price: i32,
+    tax_rate: i32,
+    tax: i64,
+    price: i64,
+}
+
+#[derive(Debug, Default)]
+struct ReceiptTotal {
+    subtotal: i64,
+    tax_8: i64,
+    tax_10: i64,
+    total: i64,
+}
+
+/**
+* 税率ごとの消費税額を計算し、明細行へ配分する。
+* 端数処理は税率ごとに1回の切り捨て (インボイス方式) とし、
+* 明細行の税額の合計がレシートの税額と一致するように最大剰余法で配分する。
+*
+* @param items 明細行
+* @param tax_included 単価が税込の場合 true
+*
+* @return 明細行と合計
+*/
+fn calc_receipt(items: &[ReceiptItemParams], tax_included: bool) -> (Vec<ReceiptLine>, ReceiptTotal) {
+    let mut lines: Vec<ReceiptLine> = items.iter().map(|item| ReceiptLine {
+        name: item.name.clone(),
+        quantity: item.quantity,
+        unit_price: item.unit_price,
+        tax_rate: item.tax_rate,
+        tax: 0,
+        price: 0,
+    }).collect();
+    let mut total = ReceiptTotal::default();
+
+    for rate in [8_i64, 10_i64] {
+        let divisor = if tax_included { 100 + rate } else { 100 };
+        let indexes: Vec<usize> = (0..lines.len())
+            .filter(|i| lines[*i].tax_rate as i64 == rate)
+            .collect();
+        if indexes.is_empty() {
+            continue;
+        }
+        let amount_sum: i64 = indexes.iter()
+            .map(|i| lines[*i].unit_price as i64 * lines[*i].quantity as i64)
+            .sum();
+        let rate_tax = amount_sum * rate / divisor;
+
+        let mut remainders: Vec<(usize, i64)> = Vec::new();
+        let mut allocated = 0;
+        for i in &indexes {
+            let amount = lines[*i].unit_price as i64 * lines[*i].quantity as i64;
+            lines[*i].tax = amount * rate / divisor;
+            allocated += lines[*i].tax;
+            remainders.push((*i, amount * rate % divisor));
+        }
+        remainders.sort_by_key(|r| std::cmp::Reverse(r.1));
+        for (i, _) in remainders.iter().take((rate_tax - allocated) as usize) {
+            lines[*i].tax += 1;
+        }
+
+        for i in &indexes {
+            let amount = lines[*i].unit_price as i64 * lines[*i].quantity as i64;
+            lines[*i].price = if tax_included { amount } else { amount + lines[*i].tax };
+            total.total += lines[*i].price;
+        }
+        if rate == 8 {
+            total.tax_8 = rate_tax;
+        } else {
+            total.tax_10 = rate_tax;
+        }
+    }
+    total.subtotal = total.total - total.tax_8 - total.tax_10;
+    (lines, total)
+}
+
+/**
+* 複数の明細行をもつレシートを、1トランザクションで登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn purchase_receipt_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<ReceiptParams>(arguments.clone()) {
+            Ok(receipt_params) => {
+                if receipt_params.items.is_empty() {
+                    return error_response(request_id, -32602, "Invalid parameters: items is empty".to_string());
+                }
+                for item in &receipt_params.items {
+                    if item.tax_rate != 8 && item.tax_rate != 10 {
+                        return error_response(request_id, -32602,
+                            format!("Invalid parameters: tax_rate must be 8 or 10, name={}", item.name));
+                    }
+                    if item.quantity <= 0 || item.unit_price < 0 {
+                        return error_response(request_id, -32602,
+                            format!("Invalid parameters: quantity / unit_price, name={}", item.name));
+                    }
+                }
+                let (lines, total) = calc_receipt(&receipt_params.items, receipt_params.tax_included);
+
+                let conn = connect().await;
+
+                let receipt_data = ReceiptData {
+                    store: receipt_params.store.clone(),
+                    date: receipt_params.date.clone(),
+                    tax_included: receipt_params.tax_included,
+                    subtotal: total.subtotal,
+                    tax_8: total.tax_8,
+                    tax_10: total.tax_10,
+                    total: total.total,
+                };
+                let tx = conn.transaction().await.unwrap();
+                let receipt_json = serde_json::to_string(&receipt_data).expect("JSON convert error");
+                if let Err(e) = tx.execute("INSERT INTO receipt (data) VALUES (?1)", params![receipt_json]).await {
+                    tx.rollback().await.ok();
+                    return error_response(request_id, -32603, format!("Insert error: {}", e));
+                }
+                let receipt_id = tx.last_insert_rowid();
+                for line in &lines {
+                    let item_id = match super::mod_item::resolve_item_id(&tx, &line.name).await {
+                        Ok(item_id) => item_id,
+                        Err(e) => {
+                            tx.rollback().await.ok();
+                            return error_response(request_id, -32603, format!("Item error: {}", e));
+                        }
+                    };
+                    let line_data = ReceiptLineData {
+                        name: line.name.clone(),
+                        price: line.price,
+                        receipt_id,
+                        item_id,
+                        store: receipt_params.store.clone(),
+                        date: receipt_params.date.clone(),
+                        quantity: line.quantity,
+                        unit_price: line.unit_price,
+                        tax_rate: line.tax_rate,
+                        tax: line.tax,
+                    };
+                    let line_json = serde_json::to_string(&line_data).expect("JSON convert error");
+                    if let Err(e) = tx.execute("INSERT INTO item_price (data) VALUES (?1)", params![line_json]).await {
+                        tx.rollback().await.ok();
+                        return error_response(request_id, -32603, format!("Insert error: {}", e));
+                    }
+                }
+                if let Err(e) = tx.commit().await {
+                    return error_response(request_id, -32603, format!("Commit error: {}", e));
+                }
+
+                let mut out_str = format!("レシート id={} , {} {}\n", receipt_id, receipt_params.store, receipt_params.date);
+                for line in &lines {
+                    out_str.push_str(&format!("{} {}円 x {} ({}%) = {}円\n",
+                        line.name, line.unit_price, line.quantity, line.tax_rate, line.price));
+                }
+                out_str.push_str(&format!("小計 {}円 , 消費税 8%: {}円 , 10%: {}円 , 合計 {}円",
+                    total.subtotal, total.tax_8, total.tax_10, total.total));
+                return super::JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: request_id,
+                    result: Some(json!({
+                        "content": [
+                            {
+                                "type": "text",
+                                "text": out_str
+                            }
+                        ]
+                    })),
+                    error: None,
+                };
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, quantity: i32, unit_price: i32, tax_rate: i32) -> ReceiptItemParams {
+        ReceiptItemParams {
+            name: name.to_string(),
+            quantity,
+            unit_price,
+            tax_rate,
+        }
+    }
+
+    #[test]
+    fn tax_is_floored_once_per_rate() {
+        // 明細ごとの切り捨ては 8 + 8 = 16 、税率ごとでは 224 * 8% = 17.92 -> 17
+        let items = vec![item("a", 1, 112, 8), item("b", 1, 112, 8), item("c", 1, 1000, 10)];
+        let (lines, total) = calc_receipt(&items, false);
+        assert_eq!(lines.iter().map(|line| line.tax).collect::<Vec<_>>(), vec![9, 8, 100]);
+        assert_eq!(lines.iter().map(|line| line.price).collect::<Vec<_>>(), vec![121, 120, 1100]);
+        assert_eq!(total.tax_8, 17);
+        assert_eq!(total.tax_10, 100);
+        assert_eq!(total.total, 1341);
+        assert_eq!(total.subtotal, 1224);
+    }
+
+    #[test]
+    fn tax_included_extracts_tax_from_price() {
+        let items = vec![item("a", 1, 100, 8), item("b", 1, 100, 8), item("c", 1, 100, 8), item("d", 2, 550, 10)];
+        let (lines, total) = calc_receipt(&items, true);
+        assert_eq!(lines.iter().map(|line| line.tax).collect::<Vec<_>>(), vec![8, 7, 7, 100]);
+        assert_eq!(lines.iter().map(|line| line.price).collect::<Vec<_>>(), vec![100, 100, 100, 1100]);
+        assert_eq!(total.tax_8, 22);
+        assert_eq!(total.tax_10, 100);
+        assert_eq!(total.total, 1400);
+        assert_eq!(total.subtotal, 1278);
+    }
+
+    #[test]
+    fn line_taxes_add_up_to_rate_tax() {
+        let items: Vec<ReceiptItemParams> = (1..=7).map(|i| item("x", i, 33 * i + 1, 8)).collect();
+        let (lines, total) = calc_receipt(&items, false);
+        assert_eq!(lines.iter().map(|line| line.tax).sum::<i64>(), total.tax_8);
+        assert_eq!(total.tax_10, 0);
+    }
+}
@@ -0,0 +1,422 @@
+use chrono::{Duration, Local, Months, NaiveDate};
+use libsql::Connection;
+use libsql::params;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use dotenvy::dotenv;
+use super::{connect, error_response, text_response};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone)]
+struct Recurring {
+    id: i64,
+    name: String,
+    price: i64,
+    schedule: String,
+    interval_days: i64,
+    start_date: NaiveDate,
+    next_due: NaiveDate,
+    end_date: Option<NaiveDate>,
+    active: bool,
+}
+
+/**
+* 開始日から数えて n 回目の支払日。
+* monthly / yearly は開始日の日付を基準にし、月末を超える場合はその月の末日とする。
+*
+* @param schedule monthly / yearly / days
+* @param interval_days schedule = days の間隔
+* @param start_date 開始日
+* @param n 回数 (0: 開始日)
+*
+* @return
+*/
+fn occurrence(schedule: &str, interval_days: i64, start_date: NaiveDate, n: u32) -> Option<NaiveDate> {
+    match schedule {
+        "monthly" => start_date.checked_add_months(Months::new(n)),
+        "yearly" => start_date.checked_add_months(Months::new(n.checked_mul(12)?)),
+        "days" => start_date.checked_add_signed(Duration::days(interval_days.checked_mul(n as i64)?)),
+        _ => None,
+    }
+}
+
+/**
+* date より後 (after = false の場合は date 以降) の最初の支払日。
+*/
+fn next_occurrence(schedule: &str, interval_days: i64, start_date: NaiveDate, date: NaiveDate, after: bool) -> Option<NaiveDate> {
+    let mut n = 0;
+    loop {
+        let due = occurrence(schedule, interval_days, start_date, n)?;
+        if due > date || (!after && due == date) {
+            return Some(due);
+        }
+        n += 1;
+    }
+}
+
+/**
+* 1か月あたりの金額に換算する。
+*/
+fn monthly_cost(recurring: &Recurring) -> f64 {
+    match recurring.schedule.as_str() {
+        "monthly" => recurring.price as f64,
+        "yearly" => recurring.price as f64 / 12.0,
+        _ => recurring.price as f64 * 365.25 / 12.0 / recurring.interval_days as f64,
+    }
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|e| format!("date parse error: {} , {}", value, e))
+}
+
+async fn load_recurring(conn: &Connection, only_active: bool) -> Result<Vec<Recurring>, libsql::Error> {
+    let sql = format!("SELECT id, name, price, schedule, interval_days, start_date, next_due, end_date, active
+    FROM recurring_purchase
+    {}
+    ORDER BY next_due, id
+    ", if only_active { "WHERE active = 1" } else { "" });
+    let mut rows = conn.query(&sql, ()).await?;
+    let mut list: Vec<Recurring> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let start_date: String = row.get(5)?;
+        let next_due: String = row.get(6)?;
+        let end_date: Option<String> = row.get(7)?;
+        list.push(Recurring {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            price: row.get(2)?,
+            schedule: row.get(3)?,
+            interval_days: row.get::<Option<i64>>(4)?.unwrap_or(0),
+            start_date: NaiveDate::parse_from_str(&start_date, DATE_FORMAT).unwrap_or_default(),
+            next_due: NaiveDate::parse_from_str(&next_due, DATE_FORMAT).unwrap_or_default(),
+            end_date: end_date.and_then(|d| NaiveDate::parse_from_str(&d, DATE_FORMAT).ok()),
+            active: row.get::<i64>(8)? == 1,
+        });
+    }
+    Ok(list)
+}
+
+/**
+* 支払日を過ぎた定期購入を、購入データ (item_price) として登録する。
+* (recurring_id, date) の一意インデックスにより、同じ支払日は2回登録されない。
+*
+* @param conn
+* @param today 基準日
+*
+* @return 登録した (品名, 支払日, 金額)
+*/
+pub async fn catchup(conn: &Connection, today: NaiveDate) -> Result<Vec<(String, NaiveDate, i64)>, libsql::Error> {
+    let mut created: Vec<(String, NaiveDate, i64)> = Vec::new();
+    for recurring in load_recurring(conn, true).await? {
+        if recurring.next_due > today {
+            continue;
+        }
+        let item_id = super::mod_item::resolve_item_id(conn, &recurring.name).await?;
+        let tx = conn.transaction().await?;
+        let mut due = recurring.next_due;
+        let mut active = true;
+        while due <= today {
+            if recurring.end_date.is_some_and(|end_date| due > end_date) {
+                active = false;
+                break;
+            }
+            let post_data = json!({
+                "name": recurring.name,
+                "price": recurring.price,
+                "date": due.format(DATE_FORMAT).to_string(),
+                "item_id": item_id,
+                "recurring_id": recurring.id
+            });
+            let count = tx.execute(
+                "INSERT OR IGNORE INTO item_price (data) VALUES (?1)",
+                params![post_data.to_string()],
+            ).await?;
+            if count > 0 {
+                created.push((recurring.name.clone(), due, recurring.price));
+            }
+            due = match next_occurrence(&recurring.schedule, recurring.interval_days, recurring.start_date, due, true) {
+                Some(next_due) => next_due,
+                None => {
+                    active = false;
+                    break;
+                }
+            };
+        }
+        if recurring.end_date.is_some_and(|end_date| due > end_date) {
+            active = false;
+        }
+        tx.execute(
+            "UPDATE recurring_purchase SET next_due = ?1, active = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+            params![due.format(DATE_FORMAT).to_string(), active as i64, recurring.id],
+        ).await?;
+        tx.commit().await?;
+    }
+    Ok(created)
+}
+
+/**
+* 起動時の catchup。DB の設定が無い場合は何もしない。
+*/
+pub async fn catchup_on_start() {
+    dotenv().ok();
+    if env::var("TURSO_DATABASE_URL").is_err() || env::var("TURSO_AUTH_TOKEN").is_err() {
+        return;
+    }
+    let conn = connect().await;
+    match catchup(&conn, Local::now().date_naive()).await {
+        Ok(created) => eprintln!("recurring catchup: {} purchases", created.len()),
+        Err(e) => eprintln!("recurring catchup error: {}", e),
+    }
+}
+
+/**
+* recurring_add: 定期購入 (サブスクリプション) を登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn recurring_add_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RecurringAddParams {
+        name: String,
+        price: i64,
+        schedule: String,
+        interval_days: Option<i64>,
+        start_date: String,
+        next_due: Option<String>,
+        end_date: Option<String>,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<RecurringAddParams>(arguments.clone()) {
+            Ok(add_params) => {
+                let interval_days = add_params.interval_days.unwrap_or(0);
+                if !matches!(add_params.schedule.as_str(), "monthly" | "yearly" | "days") {
+                    return error_response(request_id, -32602, format!("Invalid parameters, schedule={}", add_params.schedule));
+                }
+                if add_params.schedule == "days" && interval_days <= 0 {
+                    return error_response(request_id, -32602, "Invalid parameters: interval_days is required for days".to_string());
+                }
+                let start_date = match parse_date(&add_params.start_date) {
+                    Ok(date) => date,
+                    Err(e) => return error_response(request_id, -32602, e),
+                };
+                let end_date = match add_params.end_date.as_deref().map(parse_date).transpose() {
+                    Ok(date) => date,
+                    Err(e) => return error_response(request_id, -32602, e),
+                };
+                // next_due 省略時は、今日以降の最初の支払日 (過去分は登録しない)
+                let next_due = match add_params.next_due.as_deref() {
+                    Some(value) => match parse_date(value) {
+                        Ok(date) => date,
+                        Err(e) => return error_response(request_id, -32602, e),
+                    },
+                    None => next_occurrence(&add_params.schedule, interval_days, start_date,
+                        Local::now().date_naive().max(start_date), false).unwrap_or(start_date),
+                };
+
+                let conn = connect().await;
+                conn.execute(
+                    "INSERT INTO recurring_purchase (name, price, schedule, interval_days, start_date, next_due, end_date)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        add_params.name.as_str(),
+                        add_params.price,
+                        add_params.schedule.as_str(),
+                        interval_days,
+                        start_date.format(DATE_FORMAT).to_string(),
+                        next_due.format(DATE_FORMAT).to_string(),
+                        end_date.map(|date| date.format(DATE_FORMAT).to_string())
+                    ],
+                ).await.unwrap();
+                let id = conn.last_insert_rowid();
+                let created = catchup(&conn, Local::now().date_naive()).await.unwrap();
+                return text_response(request_id, format!(
+                    "Complete recurring_add, id={} , next_due={} , catchup={}",
+                    id, next_due.format(DATE_FORMAT), created.len()
+                ));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* recurring_list: 定期購入の一覧
+*
+* @param
+*
+* @return
+*/
+pub async fn recurring_list_handler(_params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let conn = connect().await;
+    let mut out_str = String::new();
+    for recurring in load_recurring(&conn, false).await.unwrap() {
+        let schedule = if recurring.schedule == "days" {
+            format!("{}日ごと", recurring.interval_days)
+        } else {
+            recurring.schedule.clone()
+        };
+        out_str.push_str(&format!(
+            "id: {} , name= {} , price= {} , schedule= {} , start= {} , next_due= {} , end= {} , {}\n",
+            recurring.id, recurring.name, recurring.price, schedule,
+            recurring.start_date.format(DATE_FORMAT), recurring.next_due.format(DATE_FORMAT),
+            recurring.end_date.map(|date| date.format(DATE_FORMAT).to_string()).unwrap_or("-".to_string()),
+            if recurring.active { "active" } else { "inactive" }
+        ));
+    }
+    text_response(request_id, out_str)
+}
+
+/**
+* recurring_delete: 定期購入を削除する。登録済みの購入データは残す。
+*
+* @param
+*
+* @return
+*/
+pub async fn recurring_delete_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RecurringDeleteParams {
+        id: i64,
+    }
+
+    if let Some(arguments) = params.get("arguments") {
+        match serde_json::from_value::<RecurringDeleteParams>(arguments.clone()) {
+            Ok(delete_params) => {
+                let conn = connect().await;
+                let count = conn.execute("DELETE FROM recurring_purchase WHERE id = ?1", params![delete_params.id]).await.unwrap();
+                if count == 0 {
+                    return error_response(request_id, -32602, format!("Invalid parameters, id={}", delete_params.id));
+                }
+                return text_response(request_id, format!("Complete delete, id={}", delete_params.id));
+            }
+            Err(e) => {
+                return error_response(request_id, -32602, format!("Invalid parameters: {}", e));
+            }
+        }
+    }
+    error_response(request_id, -32601, "Tool not found".to_string())
+}
+
+/**
+* recurring_catchup: 支払日を過ぎた定期購入を、購入データとして登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn recurring_catchup_handler(_params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let conn = connect().await;
+    match catchup(&conn, Local::now().date_naive()).await {
+        Ok(created) => {
+            let mut out_str = format!("catchup: {}\n", created.len());
+            for (name, date, price) in &created {
+                out_str.push_str(&format!("{} , {} , {}円\n", date.format(DATE_FORMAT), name, price));
+            }
+            text_response(request_id, out_str)
+        }
+        Err(e) => error_response(request_id, -32603, format!("Catchup error: {}", e)),
+    }
+}
+
+/**
+* recurring_upcoming: 今後 days 日間の支払予定と、1か月あたりの固定費を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn recurring_upcoming_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct UpcomingParams {
+        #[serde(default = "default_days")]
+        days: i64,
+    }
+    fn default_days() -> i64 {
+        30
+    }
+
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let upcoming_params = match serde_json::from_value::<UpcomingParams>(arguments) {
+        Ok(upcoming_params) => upcoming_params,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters: {}", e)),
+    };
+    let conn = connect().await;
+    let today = Local::now().date_naive();
+    catchup(&conn, today).await.unwrap();
+    let until = today + Duration::days(upcoming_params.days);
+
+    let list = load_recurring(&conn, true).await.unwrap();
+    let mut upcoming: Vec<(NaiveDate, String, i64)> = Vec::new();
+    for recurring in &list {
+        let mut due = Some(recurring.next_due);
+        while let Some(date) = due {
+            if date > until || recurring.end_date.is_some_and(|end_date| date > end_date) {
+                break;
+            }
+            upcoming.push((date, recurring.name.clone(), recurring.price));
+            due = next_occurrence(&recurring.schedule, recurring.interval_days, recurring.start_date, date, true);
+        }
+    }
+    upcoming.sort();
+
+    let mut out_str = format!("{} から {} までの支払予定:\n", today.format(DATE_FORMAT), until.format(DATE_FORMAT));
+    for (date, name, price) in &upcoming {
+        out_str.push_str(&format!("{} , {} , {}円\n", date.format(DATE_FORMAT), name, price));
+    }
+    let total: i64 = upcoming.iter().map(|(_, _, price)| price).sum();
+    let monthly: f64 = list.iter().map(monthly_cost).sum();
+    out_str.push_str(&format!("期間合計 {}円 , 固定費 (1か月あたり) {}円", total, monthly.round() as i64));
+    text_response(request_id, out_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        parse_date(value).unwrap()
+    }
+
+    #[test]
+    fn monthly_clamps_to_month_end_without_drift() {
+        let start = date("2025-01-31");
+        assert_eq!(occurrence("monthly", 0, start, 1), Some(date("2025-02-28")));
+        assert_eq!(occurrence("monthly", 0, start, 2), Some(date("2025-03-31")));
+        assert_eq!(occurrence("monthly", 0, start, 3), Some(date("2025-04-30")));
+        assert_eq!(occurrence("monthly", 0, date("2024-01-30"), 1), Some(date("2024-02-29")));
+    }
+
+    #[test]
+    fn yearly_leap_day_falls_back_to_february_28() {
+        let start = date("2024-02-29");
+        assert_eq!(occurrence("yearly", 0, start, 1), Some(date("2025-02-28")));
+        assert_eq!(occurrence("yearly", 0, start, 4), Some(date("2028-02-29")));
+    }
+
+    #[test]
+    fn days_uses_interval() {
+        assert_eq!(occurrence("days", 10, date("2025-01-25"), 1), Some(date("2025-02-04")));
+        assert_eq!(occurrence("weekly", 0, date("2025-01-25"), 1), None);
+    }
+
+    #[test]
+    fn next_occurrence_after_month_end() {
+        let start = date("2025-01-31");
+        assert_eq!(next_occurrence("monthly", 0, start, date("2025-02-28"), false), Some(date("2025-02-28")));
+        assert_eq!(next_occurrence("monthly", 0, start, date("2025-02-28"), true), Some(date("2025-03-31")));
+        assert_eq!(next_occurrence("monthly", 0, start, date("2025-01-01"), false), Some(start));
+    }
+}
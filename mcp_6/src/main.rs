@@ -59,6 +59,7 @@ static TURSO_DATABASE_URL: &str = "";
 static TURSO_AUTH_TOKEN: &str = "";
 
 mod mod_purchase;
+mod mod_excel_template;
 
 async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
     match request.method.as_str() {
@@ -116,8 +117,24 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                         "inputSchema": {
                             "type": "object",
                             "properties": {
+                                "template_purchase": {
+                                    "type": "string",
+                                    "description": "Excel テンプレートのパス"
+                                },
+                                "xls_out_dir": {
+                                    "type": "string",
+                                    "description": "出力先のディレクトリ"
+                                },
+                                "template_map": {
+                                    "type": "string",
+                                    "description": "テンプレートの設定 (JSON) のパス。省略した場合はテンプレートと同じ名前の .json"
+                                },
+                                "title": {
+                                    "type": "string",
+                                    "description": "{{title}} に埋め込むタイトル。省略した場合は設定の title"
+                                }
                             },
-                            "required": []
+                            "required": ["template_purchase", "xls_out_dir"]
                         }
                     },                                        
                     {
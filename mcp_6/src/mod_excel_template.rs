@@ -0,0 +1,411 @@
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use umya_spreadsheet::Spreadsheet;
+
+/**
+* Excel テンプレートの設定。テンプレートと同じ名前の .json (input.xlsx なら input.json) に置く。
+* 設定ファイルが無い場合は、Sheet1 の A2 から id , name , price を出力する。
+*
+* {
+*   "sheet": "購入一覧",
+*   "header_row": 3,
+*   "start_cell": "B4",
+*   "columns": {
+*     "id": "B",
+*     "created_at": { "column": "C", "header": "日付", "format": "%Y/%m/%d" },
+*     "name": "D",
+*     "price": "E",
+*     "tax_included": { "column": "F", "header": "税込", "expr": "price * 1.1" }
+*   },
+*   "title": "購入品リスト",
+*   "values": { "author": "総務部" }
+* }
+*/
+#[derive(Debug, Deserialize)]
+pub struct TemplateMap {
+    #[serde(default = "default_sheet")]
+    pub sheet: String,
+    // 見出しを書き込む行。省略した場合はテンプレートの見出しのままとする
+    pub header_row: Option<u32>,
+    // データの最初のセル。行はデータの開始行、列は columns を省略した場合の開始列
+    #[serde(default = "default_start_cell")]
+    pub start_cell: String,
+    // 項目名と列。項目は id , name , price , created_at , updated_at , row_no , data の項目 , expr の計算項目
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnMap>,
+    #[serde(default)]
+    pub title: String,
+    // 日付の書式 (created_at , updated_at , {{period}} , {{created}})
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    // {{name}} に埋め込む値
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// 列。"B" または { column , header , format , expr , decimals }
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnMap {
+    Column(String),
+    Detail {
+        column: String,
+        header: Option<String>,
+        // 日付の書式
+        format: Option<String>,
+        // 計算式 (数値の項目 , 数値 , + - * / , 括弧)
+        expr: Option<String>,
+        // 計算結果の小数点以下の桁数 (既定 0)
+        decimals: Option<u32>,
+    },
+}
+
+impl ColumnMap {
+    fn column(&self) -> &str {
+        match self {
+            ColumnMap::Column(column) => column,
+            ColumnMap::Detail { column, .. } => column,
+        }
+    }
+}
+
+fn default_sheet() -> String {
+    "Sheet1".to_string()
+}
+
+fn default_start_cell() -> String {
+    "A2".to_string()
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+/// 出力する1行 (item_price の1件)
+#[derive(Debug)]
+pub struct PurchaseRow {
+    pub id: i64,
+    pub data: Value,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// セルに書き込む値
+#[derive(Debug, Clone, PartialEq)]
+enum CellData {
+    Text(String),
+    Number(f64),
+}
+
+/**
+* 設定を読み込む。map_path を省略した場合は、テンプレートと同じ名前の .json を探す。
+*
+* @param template_path
+* @param map_path
+*
+* @return
+*/
+pub fn load_template_map(template_path: &str, map_path: Option<&str>) -> Result<TemplateMap, String> {
+    let default_path = Path::new(template_path).with_extension("json");
+    let path = match map_path {
+        Some(map_path) => Path::new(map_path).to_path_buf(),
+        None if default_path.exists() => default_path,
+        None => return default_template_map(),
+    };
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{} read error: {}", path.display(), e))?;
+    let mut map: TemplateMap = serde_json::from_str(&text).map_err(|e| format!("{} JSON error: {}", path.display(), e))?;
+    if map.columns.is_empty() {
+        map.columns = default_columns(&map.start_cell)?;
+    }
+    validate_template_map(&map)?;
+    Ok(map)
+}
+
+fn default_template_map() -> Result<TemplateMap, String> {
+    let mut map: TemplateMap = serde_json::from_str("{}").map_err(|e| e.to_string())?;
+    map.columns = default_columns(&map.start_cell)?;
+    Ok(map)
+}
+
+// start_cell の列から id , name , price
+fn default_columns(start_cell: &str) -> Result<BTreeMap<String, ColumnMap>, String> {
+    let (column, _) = parse_cell(start_cell)?;
+    let start = column_number(&column)?;
+    Ok(["id", "name", "price"].iter().enumerate()
+        .map(|(index, field)| (field.to_string(), ColumnMap::Column(column_letters(start + index as u32))))
+        .collect())
+}
+
+fn validate_template_map(map: &TemplateMap) -> Result<(), String> {
+    parse_cell(&map.start_cell)?;
+    format_date("2000-01-01", &map.date_format)?;
+    for (field, column_map) in &map.columns {
+        column_number(column_map.column()).map_err(|e| format!("{}: {}", field, e))?;
+        if let ColumnMap::Detail { format: Some(format), .. } = column_map {
+            format_date("2000-01-01", format).map_err(|e| format!("{}: {}", field, e))?;
+        }
+        if let ColumnMap::Detail { expr: Some(expr), .. } = column_map {
+            eval_expr(expr, &|_| Some(0.0)).map_err(|e| format!("{}: {}", field, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// "B12" を ("B" , 12) にする
+fn parse_cell(cell: &str) -> Result<(String, u32), String> {
+    let cell = cell.trim().to_uppercase();
+    let split = cell.find(|c: char| c.is_ascii_digit()).unwrap_or(cell.len());
+    let (column, row) = cell.split_at(split);
+    column_number(column).map_err(|_| format!("invalid cell: {}", cell))?;
+    match row.parse::<u32>() {
+        Ok(row) if row > 0 => Ok((column.to_string(), row)),
+        _ => Err(format!("invalid cell: {}", cell)),
+    }
+}
+
+/// "A" は 1 , "AA" は 27
+fn column_number(column: &str) -> Result<u32, String> {
+    let column = column.trim().to_uppercase();
+    if column.is_empty() || column.len() > 3 || !column.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(format!("invalid column: {}", column));
+    }
+    Ok(column.chars().fold(0, |number, c| number * 26 + (c as u32 - 'A' as u32 + 1)))
+}
+
+fn column_letters(number: u32) -> String {
+    let mut number = number;
+    let mut letters = Vec::new();
+    while number > 0 {
+        letters.push((b'A' + ((number - 1) % 26) as u8) as char);
+        number = (number - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/**
+* SQLite の日時 ("2025-10-28 09:30:00" , "2025-10-28") を書式にする。日時でない場合はそのままとする。
+*
+* @param value
+* @param format chrono の書式
+*
+* @return
+*/
+fn format_date(value: &str, format: &str) -> Result<String, String> {
+    let date_time = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)));
+    let Some(date_time) = date_time else {
+        return Ok(value.to_string());
+    };
+    let mut text = String::new();
+    write!(text, "{}", date_time.format(format)).map_err(|_| format!("invalid date format: {}", format))?;
+    Ok(text)
+}
+
+/**
+* 計算式を計算する。数値 , 項目名 , + - * / , 括弧のみ。
+*
+* @param expr
+* @param value 項目名の数値
+*
+* @return
+*/
+fn eval_expr(expr: &str, value: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+    struct ExprParser<'a> {
+        chars: Vec<char>,
+        index: usize,
+        value: &'a dyn Fn(&str) -> Option<f64>,
+    }
+    impl ExprParser<'_> {
+        fn peek(&mut self) -> Option<char> {
+            while self.chars.get(self.index).is_some_and(|c| c.is_whitespace()) {
+                self.index += 1;
+            }
+            self.chars.get(self.index).copied()
+        }
+        // 加減
+        fn sum(&mut self) -> Result<f64, String> {
+            let mut result = self.product()?;
+            while let Some(op @ ('+' | '-')) = self.peek() {
+                self.index += 1;
+                let right = self.product()?;
+                result = if op == '+' { result + right } else { result - right };
+            }
+            Ok(result)
+        }
+        // 乗除。0 で割った場合は 0 とする (セルに inf を書き込まない)
+        fn product(&mut self) -> Result<f64, String> {
+            let mut result = self.term()?;
+            while let Some(op @ ('*' | '/')) = self.peek() {
+                self.index += 1;
+                let right = self.term()?;
+                result = if op == '*' { result * right } else if right == 0.0 { 0.0 } else { result / right };
+            }
+            Ok(result)
+        }
+        fn term(&mut self) -> Result<f64, String> {
+            match self.peek() {
+                Some('-') => {
+                    self.index += 1;
+                    Ok(-self.term()?)
+                }
+                Some('(') => {
+                    self.index += 1;
+                    let result = self.sum()?;
+                    if self.peek() != Some(')') {
+                        return Err(") not found".to_string());
+                    }
+                    self.index += 1;
+                    Ok(result)
+                }
+                Some(c) if c.is_ascii_digit() || c == '.' => {
+                    let start = self.index;
+                    while self.chars.get(self.index).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                        self.index += 1;
+                    }
+                    let number: String = self.chars[start..self.index].iter().collect();
+                    number.parse::<f64>().map_err(|_| format!("invalid number: {}", number))
+                }
+                Some(c) if c.is_alphabetic() || c == '_' => {
+                    let start = self.index;
+                    while self.chars.get(self.index).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                        self.index += 1;
+                    }
+                    let name: String = self.chars[start..self.index].iter().collect();
+                    (self.value)(&name).ok_or(format!("unknown or non-numeric field: {}", name))
+                }
+                Some(c) => Err(format!("unexpected character: {}", c)),
+                None => Err("unexpected end".to_string()),
+            }
+        }
+    }
+    let mut parser = ExprParser { chars: expr.chars().collect(), index: 0, value };
+    let result = parser.sum()?;
+    if let Some(c) = parser.peek() {
+        return Err(format!("unexpected character: {}", c));
+    }
+    Ok(result)
+}
+
+/// data の項目 , 行の項目の値
+fn field_data(row: &PurchaseRow, row_no: usize, field: &str) -> CellData {
+    match field {
+        "id" => CellData::Number(row.id as f64),
+        "row_no" => CellData::Number(row_no as f64),
+        "created_at" => CellData::Text(row.created_at.clone()),
+        "updated_at" => CellData::Text(row.updated_at.clone()),
+        _ => match row.data.get(field) {
+            Some(Value::Number(number)) => CellData::Number(number.as_f64().unwrap_or(0.0)),
+            Some(Value::String(text)) => CellData::Text(text.clone()),
+            Some(Value::Null) | None => CellData::Text(String::new()),
+            Some(other) => CellData::Text(other.to_string()),
+        },
+    }
+}
+
+fn cell_value(map: &TemplateMap, row: &PurchaseRow, row_no: usize, field: &str, column_map: &ColumnMap) -> Result<CellData, String> {
+    match column_map {
+        ColumnMap::Detail { expr: Some(expr), decimals, .. } => {
+            let number = |name: &str| match field_data(row, row_no, name) {
+                CellData::Number(number) => Some(number),
+                CellData::Text(_) => None,
+            };
+            let result = eval_expr(expr, &number).map_err(|e| format!("{}: {}", field, e))?;
+            let scale = 10f64.powi(decimals.unwrap_or(0) as i32);
+            Ok(CellData::Number((result * scale).round() / scale))
+        }
+        ColumnMap::Detail { format: Some(format), .. } if field == "created_at" || field == "updated_at" => {
+            let CellData::Text(value) = field_data(row, row_no, field) else {
+                return Ok(CellData::Text(String::new()));
+            };
+            format_date(&value, format).map(CellData::Text)
+        }
+        _ if field == "created_at" || field == "updated_at" => {
+            let CellData::Text(value) = field_data(row, row_no, field) else {
+                return Ok(CellData::Text(String::new()));
+            };
+            format_date(&value, &map.date_format).map(CellData::Text)
+        }
+        _ => Ok(field_data(row, row_no, field)),
+    }
+}
+
+/**
+* {{name}} に埋め込む値。title , period (created_at の最初と最後) , count , total (price の合計) , created (出力日)
+*/
+fn placeholder_values(map: &TemplateMap, rows: &[PurchaseRow], title: Option<&str>) -> Result<BTreeMap<String, String>, String> {
+    let mut values = map.values.clone();
+    values.insert("title".to_string(), title.unwrap_or(&map.title).to_string());
+    let mut dates: Vec<&str> = rows.iter().map(|row| row.created_at.as_str()).collect();
+    dates.sort();
+    let period = match (dates.first(), dates.last()) {
+        (Some(first), Some(last)) => format!("{} - {}", format_date(first, &map.date_format)?, format_date(last, &map.date_format)?),
+        _ => String::new(),
+    };
+    values.insert("period".to_string(), period);
+    values.insert("count".to_string(), rows.len().to_string());
+    let total: f64 = rows.iter().filter_map(|row| row.data.get("price").and_then(|price| price.as_f64())).sum();
+    values.insert("total".to_string(), total.to_string());
+    let created = Local::now().naive_local().format("%Y-%m-%d %H:%M:%S").to_string();
+    values.insert("created".to_string(), format_date(&created, &map.date_format)?);
+    Ok(values)
+}
+
+/**
+* テンプレートの sheet に、見出し , 行 , {{name}} を書き込む。
+*
+* @param book
+* @param map
+* @param rows
+* @param title {{title}} (省略した場合は設定の title)
+*
+* @return
+*/
+pub fn fill_template(book: &mut Spreadsheet, map: &TemplateMap, rows: &[PurchaseRow], title: Option<&str>) -> Result<(), String> {
+    let values = placeholder_values(map, rows, title)?;
+    let sheet = book
+        .get_sheet_by_name_mut(&map.sheet)
+        .ok_or(format!("sheet not found: {}", map.sheet))?;
+
+    // {{name}} は設定に無い名前もそのまま残す
+    for cell in sheet.get_cell_collection_mut() {
+        let text = cell.get_value().to_string();
+        if !text.contains("{{") {
+            continue;
+        }
+        let mut replaced = text.clone();
+        for (name, value) in &values {
+            replaced = replaced.replace(&format!("{{{{{}}}}}", name), value);
+        }
+        if replaced != text {
+            cell.set_value(replaced);
+        }
+    }
+
+    if let Some(header_row) = map.header_row {
+        for (field, column_map) in &map.columns {
+            let header = match column_map {
+                ColumnMap::Detail { header: Some(header), .. } => header.clone(),
+                _ => field.clone(),
+            };
+            sheet.get_cell_mut(format!("{}{}", column_map.column().to_uppercase(), header_row)).set_value(header);
+        }
+    }
+
+    let (_, start_row) = parse_cell(&map.start_cell)?;
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = start_row + index as u32;
+        for (field, column_map) in &map.columns {
+            let cell = sheet.get_cell_mut(format!("{}{}", column_map.column().to_uppercase(), row_number));
+            match cell_value(map, row, index + 1, field, column_map)? {
+                CellData::Number(number) => cell.set_value_number(number),
+                CellData::Text(text) => cell.set_value(text),
+            };
+        }
+    }
+    Ok(())
+}
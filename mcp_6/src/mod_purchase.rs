@@ -177,6 +177,8 @@ pub async fn purchase_list_handler(params: Value, request_id: Option<Value>) ->
 }
 
 /**
+* purchase_list_excel: 購入品リストを Excel テンプレートに出力する。
+* 出力先のシート , 行 , 列 , {{name}} は、テンプレートの設定 (mod_excel_template) による。
 *
 * @param
 *
@@ -190,6 +192,8 @@ pub async fn purchase_list_excel_handler(
     struct ListExcelParams {
         template_purchase: String,
         xls_out_dir: String,
+        template_map: Option<String>,
+        title: Option<String>,
     }    
 
     #[derive(Debug, Deserialize)]
@@ -199,11 +203,15 @@ pub async fn purchase_list_excel_handler(
     }    
     let mut template_name : String = "".to_string();
     let mut out_dir : String = "".to_string();
+    let mut template_map_path : Option<String> = None;
+    let mut title : Option<String> = None;
     if let Some(arguments) = params.get("arguments") {
         match serde_json::from_value::<ListExcelParams>(arguments.clone()) {
             Ok(purchase_params) => {
                 template_name = purchase_params.template_purchase.clone();
                 out_dir = purchase_params.xls_out_dir.clone();
+                template_map_path = purchase_params.template_map.clone();
+                title = purchase_params.title.clone();
             },
             Err(e) => {
                 return super::JsonRpcResponse {
@@ -225,6 +233,20 @@ pub async fn purchase_list_excel_handler(
     //let path = Path::new("input.xlsx");
     let path = Path::new(&template_name);
     let mut book = reader::xlsx::read(path).unwrap();
+    let template_map = match super::mod_excel_template::load_template_map(&template_name, template_map_path.as_deref()) {
+        Ok(template_map) => template_map,
+        Err(e) => {
+            return super::JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: request_id,
+                result: None,
+                error: Some(super::JsonRpcError {
+                    code: -32602,
+                    message: format!("Invalid parameters, template_map: {}", e),
+                }),
+            };
+        }
+    };
 
     let url = super::TURSO_DATABASE_URL.to_string();
     let token = super::TURSO_AUTH_TOKEN.to_string();
@@ -273,34 +295,35 @@ pub async fn purchase_list_excel_handler(
     println!("out_filename={}", out_filename); 
     println!("out_file_path={}", out_file_path); 
     {
-        let sheet = book
-            .get_sheet_by_name_mut("Sheet1")
-            .ok_or("Sheet1 が見つかりません").unwrap();
-        let mut count = 2;
-        let mut a_col_str : String;
-        let mut b_col_str : String;
-        let mut c_col_str : String;
-        let mut a_val = "".to_string();
-        let mut c_val = "".to_string();
+        let mut purchase_rows: Vec<super::mod_excel_template::PurchaseRow> = Vec::new();
+        for item in &todos {
+            purchase_rows.push(super::mod_excel_template::PurchaseRow {
+                id: item.id,
+                data: serde_json::from_str(&item.data).expect("data JSON decord error"),
+                created_at: item.created_at.clone(),
+                updated_at: item.updated_at.clone(),
+            });
+        }
+        if let Err(e) = super::mod_excel_template::fill_template(&mut book, &template_map, &purchase_rows, title.as_deref()) {
+            return super::JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: request_id,
+                result: None,
+                error: Some(super::JsonRpcError {
+                    code: -32602,
+                    message: format!("Invalid parameters, template_map: {}", e),
+                }),
+            };
+        }
 
         for item in &todos {
             let row_item: ItemData = serde_json::from_str(&item.data).expect("data JSON decord error");
             println!("デコードされた構造体: {:?}", row_item);          
             println!("ID: {}, Name: {}", item.id, item.created_at);
-            // セル A1 に文字列
-            a_col_str = format!("A{}", count);
-            a_val = format!("{}", &item.id);
-            sheet.get_cell_mut(a_col_str).set_value(a_val);
-            b_col_str = format!("B{}", count);
-            sheet.get_cell_mut(b_col_str).set_value(&row_item.name);
-            c_col_str = format!("C{}", count);
-            c_val = format!("{}", &row_item.price);
-            sheet.get_cell_mut(c_col_str).set_value(c_val);
             // List-Data
             let row_str: String = format!("* id: {} , name= {} price= {}\n", &item.id, &row_item.name, &row_item.price);
             println!("row_str: {:?}", row_str); 
             out_str = format!("{}{}", &out_str, &row_str);            
-            count = count + 1;
         }
         out_str = format!("{}{}", &out_str, "***\n* 下記リンクをおすと、ダウンロードできます。\n");     
         out_str = format!("{}http://localhost:3000/data/{}", &out_str, out_filename);
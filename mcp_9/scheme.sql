@@ -5,6 +5,7 @@ CREATE TABLE IF NOT EXISTS todo (
   updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
+-- data: ER 図の JSON (entities , relations)
 CREATE TABLE IF NOT EXISTS er_chart (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
   data TEXT NOT NULL,
@@ -18,3 +19,48 @@ CREATE TABLE IF NOT EXISTS md_data (
   created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
   updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
+
+
+CREATE TABLE IF NOT EXISTS mcp_collection (
+  name TEXT PRIMARY KEY,
+  description TEXT NOT NULL DEFAULT '',
+  schema TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS mcp_data_history (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  collection TEXT NOT NULL,
+  record_id INTEGER NOT NULL,
+  version INTEGER NOT NULL,
+  action TEXT NOT NULL,
+  old_data TEXT,
+  new_data TEXT,
+  record_created_at TIMESTAMP,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mcp_data_history_record ON mcp_data_history (collection, record_id, version);
+
+CREATE TABLE IF NOT EXISTS mcp_webhook (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  url TEXT NOT NULL,
+  events TEXT NOT NULL,
+  collection TEXT,
+  secret TEXT NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS mcp_webhook_delivery (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  webhook_id INTEGER NOT NULL,
+  event TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_retry_at INTEGER NOT NULL,
+  last_status INTEGER,
+  last_error TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  delivered_at TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_mcp_webhook_delivery_status ON mcp_webhook_delivery (status, next_retry_at);
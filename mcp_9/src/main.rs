@@ -35,6 +35,79 @@ pub struct JsonRpcError {
     message: String,
 }
 
+/**
+* エラーの response。
+*
+* @param request_id
+* @param code
+* @param message
+*
+* @return
+*/
+pub fn error_response(request_id: Option<Value>, code: i32, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message,
+        }),
+    }
+}
+
+/**
+* text を1つ返す response。
+*
+* @param request_id
+* @param text
+*
+* @return
+*/
+pub fn text_response(request_id: Option<Value>, text: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": text
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
+/**
+* tools/call の arguments を読み込む。arguments が無い場合は Tool not found 、読み込めない場合は Invalid parameters の response を返す。
+*
+* @param params
+* @param request_id
+*
+* @return
+*/
+pub fn parse_arguments<T: for<'de> Deserialize<'de>>(params: &Value, request_id: &Option<Value>) -> Result<T, JsonRpcResponse> {
+    let Some(arguments) = params.get("arguments") else {
+        return Err(error_response(request_id.clone(), -32601, "Tool not found".to_string()));
+    };
+    serde_json::from_value::<T>(arguments.clone())
+        .map_err(|e| error_response(request_id.clone(), -32602, format!("Invalid parameters: {}", e)))
+}
+
+/**
+* Turso に接続する。
+*
+* @return
+*/
+pub async fn connect() -> Connection {
+    let url = TURSO_DATABASE_URL.to_string();
+    let token = TURSO_AUTH_TOKEN.to_string();
+    let db = Builder::new_remote(url, token).build().await.unwrap();
+    db.connect().unwrap()
+}
+
 #[derive(Debug, Deserialize)]
 struct AddTenParams {
     value: i32,
@@ -60,9 +133,713 @@ fn purchase(product_name: String, price: i32) -> String {
 
 mod mod_purchase;
 mod mod_data;
+mod mod_collection;
+mod mod_query;
+mod mod_patch;
+mod mod_collection_admin;
+mod mod_history;
+mod mod_er;
+mod mod_md;
+mod mod_webhook;
+
+/**
+* tools/list 用の collection の一覧。接続できない場合は scheme.sql の collection のみとする。
+*
+* @return
+*/
+async fn list_collections() -> Vec<mod_collection::Collection> {
+    let url = TURSO_DATABASE_URL.to_string();
+    let token = TURSO_AUTH_TOKEN.to_string();
+    match Builder::new_remote(url, token).build().await.and_then(|db| db.connect()) {
+        Ok(conn) => mod_collection::load_collections(&conn).await,
+        Err(e) => {
+            eprintln!("collection list not loaded: {}", e);
+            mod_collection::declared_collections()
+        }
+    }
+}
+
+/**
+* collection_* の tools/list。
+*
+* @return
+*/
+fn collection_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "collection_list",
+            "description": "collection の一覧と件数を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {},
+                "required": []
+            }
+        }),
+        json!({
+            "name": "collection_describe",
+            "description": "collection の件数、data の項目名 (新しい 100 件から集計)、JSON Schema、index を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "collection 名"
+                    }
+                },
+                "required": ["name"]
+            }
+        }),
+        json!({
+            "name": "collection_create",
+            "description": "collection (id , data , created_at , updated_at の table) を作成します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "collection 名 (英小文字で始まり、英小文字 , 数字 , _ のみ)"
+                    },
+                    "description": {
+                        "type": "string",
+                        "description": "説明"
+                    },
+                    "schema": {
+                        "type": "object",
+                        "description": "data の JSON Schema (type , enum , required , properties , additionalProperties , items)"
+                    },
+                    "indexes": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "index を作成する JSON パス (例: [\"$.status\"])。data_list の filter , sort で使われます"
+                    }
+                },
+                "required": ["name"]
+            }
+        }),
+        json!({
+            "name": "collection_drop",
+            "description": "collection_create で作成した collection を、データとともに削除します。confirm に collection 名を指定した場合のみ削除します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "collection 名"
+                    },
+                    "confirm": {
+                        "type": "string",
+                        "description": "確認のため、collection 名を指定します"
+                    }
+                },
+                "required": ["name", "confirm"]
+            }
+        }),
+    ]
+}
+
+/**
+* er_* の tools/list。
+*
+* @return
+*/
+fn er_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "er_render",
+            "description": "er_chart の id の ER 図を、Mermaid の erDiagram , SQLite / Postgres の CREATE TABLE にします。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "number",
+                        "description": "er_chart の id"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["mermaid", "sqlite", "postgres"],
+                        "description": "mermaid: erDiagram , sqlite / postgres: CREATE TABLE"
+                    }
+                },
+                "required": ["id", "format"]
+            }
+        }),
+        json!({
+            "name": "er_import",
+            "description": "CREATE TABLE の DDL を ER 図にして、er_chart に登録します。REFERENCES は relation になります。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ddl": {
+                        "type": "string",
+                        "description": "CREATE TABLE の DDL (SQLite , Postgres)。CREATE TABLE 以外の文は無視します"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "ER 図の名前"
+                    }
+                },
+                "required": ["ddl"]
+            }
+        }),
+    ]
+}
+
+/**
+* md_* の tools/list。
+*
+* @return
+*/
+fn md_tools() -> Vec<Value> {
+    let id = json!({
+        "type": "number",
+        "description": "md_data の id"
+    });
+    let path = json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": "見出し名のパス (例: [\"設定\", \"Linux\"])。上位の見出しは省略できます"
+    });
+    vec![
+        json!({
+            "name": "md_outline",
+            "description": "md_data の id の Markdown の見出しを、木 (level , title , line , children) で表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id
+                },
+                "required": ["id"]
+            }
+        }),
+        json!({
+            "name": "md_render",
+            "description": "md_data の id の Markdown を HTML にします。script などは取り除きます。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id
+                },
+                "required": ["id"]
+            }
+        }),
+        json!({
+            "name": "md_section_get",
+            "description": "md_data の id の、見出しのパスの section (見出しの行と下位の見出しを含む) と etag を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "path": path
+                },
+                "required": ["id", "path"]
+            }
+        }),
+        json!({
+            "name": "md_section_replace",
+            "description": "md_data の id の、見出しのパスの section を content に置き換えます。他の section は変更しません。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "path": path,
+                    "content": {
+                        "type": "string",
+                        "description": "見出しの行を含む section 全体の Markdown"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "md_section_get , md_outline の etag。一致しない場合は更新しません"
+                    }
+                },
+                "required": ["id", "path", "content"]
+            }
+        }),
+    ]
+}
+
+/**
+* webhook_* の tools/list。
+*
+* @return
+*/
+fn webhook_tools() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "webhook_create",
+            "description": "data_* , purchase の変更を POST する webhook を登録します。送信先はこのマシンの URL のみです。payload は secret で署名します (X-Webhook-Signature)。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "送信先 URL (localhost , 127.0.0.1 , ::1)"
+                    },
+                    "secret": {
+                        "type": "string",
+                        "description": "署名の secret (16文字以上)"
+                    },
+                    "events": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": mod_webhook::WEBHOOK_EVENTS },
+                        "description": "送信する変更。省略した場合はすべて"
+                    },
+                    "collection": {
+                        "type": "string",
+                        "description": "collection 名 (purchase は item_price)。省略した場合はすべて"
+                    }
+                },
+                "required": ["url", "secret"]
+            }
+        }),
+        json!({
+            "name": "webhook_list",
+            "description": "登録した webhook の一覧を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {},
+                "required": []
+            }
+        }),
+        json!({
+            "name": "webhook_delete",
+            "description": "id の webhook と、送信の記録を削除します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "number",
+                        "description": "webhook の id"
+                    }
+                },
+                "required": ["id"]
+            }
+        }),
+        json!({
+            "name": "webhook_deliveries",
+            "description": "webhook の送信の記録 (status: pending , delivered , failed , 回数 , エラー) を、新しい順に表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "webhook_id": {
+                        "type": "number",
+                        "description": "webhook の id"
+                    },
+                    "status": {
+                        "type": "string",
+                        "enum": ["pending", "delivered", "failed"],
+                        "description": "status"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "件数 (既定 20 , 最大 100)"
+                    }
+                },
+                "required": []
+            }
+        }),
+        json!({
+            "name": "webhook_retry",
+            "description": "pending , failed の送信を、すぐに再送します。送信は background で行い、結果は webhook_deliveries で確認します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "delivery_id": {
+                        "type": "number",
+                        "description": "送信の記録の id。省略した場合はすべて"
+                    }
+                },
+                "required": []
+            }
+        }),
+    ]
+}
+
+/**
+* data_* の tools/list。content は登録済みの collection 名のみ指定できる。
+*
+* @param collections
+*
+* @return
+*/
+fn data_tools(collections: &[mod_collection::Collection]) -> Vec<Value> {
+    let content = json!({
+        "type": "string",
+        "enum": collections.iter().map(|collection| collection.name.clone()).collect::<Vec<String>>(),
+        "description": format!("collection 名 ({})", collections.iter()
+            .map(|collection| format!("{}: {}", collection.name, collection.description))
+            .collect::<Vec<String>>().join(" , "))
+    });
+    let mode = json!({
+        "type": "string",
+        "enum": ["atomic", "best_effort"],
+        "description": "atomic: 1件でも失敗した場合はすべて取り消します (既定) , best_effort: 成功した分のみ反映します"
+    });
+    vec![
+        json!({
+            "name": "data_create",
+            "description": "collection にデータを登録します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "data": {
+                        "type": "string",
+                        "description": "登録するデータ (JSON 文字列 , md_data は Markdown)"
+                    }
+                },
+                "required": ["content", "data"]
+            }
+        }),
+        json!({
+            "name": "data_list",
+            "description": "collection のデータを表示します。JSON パスの条件、並び順、項目の指定ができます。既定は新しい順に 10 件。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "filter": {
+                        "type": "array",
+                        "description": "条件 (すべて満たすデータ)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "JSON パス (例: $.status)"
+                                },
+                                "op": {
+                                    "type": "string",
+                                    "enum": ["eq", "ne", "gt", "gte", "lt", "lte", "in", "contains", "exists"],
+                                    "description": "比較 (既定 eq)。contains: 配列の要素 / 文字列の部分一致 , exists: value が false の場合は項目が無いデータ"
+                                },
+                                "value": {
+                                    "description": "比較する値。in の場合は配列"
+                                }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    "sort": {
+                        "type": "object",
+                        "description": "並び順 (既定 created_at の降順)",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "JSON パス、または id , created_at , updated_at"
+                            },
+                            "order": {
+                                "type": "string",
+                                "enum": ["asc", "desc"],
+                                "description": "既定 desc"
+                            }
+                        },
+                        "required": ["path"]
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "表示する項目の JSON パス。省略時は data 全体"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "件数 (既定 10 , 最大 100)"
+                    },
+                    "offset": {
+                        "type": "number",
+                        "description": "開始位置"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "前の結果の next_cursor。次のページを表示します"
+                    }
+                },
+                "required": ["content"]
+            }
+        }),
+        json!({
+            "name": "data_getone",
+            "description": "collection の id のデータを表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+        json!({
+            "name": "data_update",
+            "description": "collection の id のデータを更新します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は更新せず、現在のデータを返します"
+                    },
+                    "data": {
+                        "type": "string",
+                        "description": "更新するデータ (JSON 文字列)"
+                    }
+                },
+                "required": ["content", "id", "data"]
+            }
+        }),
+        json!({
+            "name": "data_patch",
+            "description": "collection の id のデータの一部を更新し、更新後のデータを表示します。merge_patch (RFC 7396) または patch (RFC 6902) を指定します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は更新せず、現在のデータを返します"
+                    },
+                    "merge_patch": {
+                        "type": "object",
+                        "description": "JSON Merge Patch。null の項目は削除します (例: {\"status\": \"done\"})"
+                    },
+                    "patch": {
+                        "type": "array",
+                        "description": "JSON Patch の操作 (例: [{\"op\": \"replace\", \"path\": \"/status\", \"value\": \"done\"}])",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "op": {
+                                    "type": "string",
+                                    "enum": ["add", "remove", "replace", "move", "copy", "test"]
+                                },
+                                "path": { "type": "string" },
+                                "from": { "type": "string" },
+                                "value": {}
+                            },
+                            "required": ["op", "path"]
+                        }
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+        json!({
+            "name": "data_history",
+            "description": "collection の id のデータの変更履歴 (登録 , 更新 , 削除 , 復元) を、新しい順に表示します。削除したデータも表示できます。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "件数 (既定 20 , 最大 100)"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+        json!({
+            "name": "data_revert",
+            "description": "collection の id のデータを、data_history の version のデータに戻します。削除したデータは同じ id で復元します。version を省略した場合は、最後の変更を取り消します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "version": {
+                        "type": "number",
+                        "description": "戻す version (data_history の version)"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は戻しません"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+        json!({
+            "name": "data_bulk_create",
+            "description": "collection に複数のデータを1つの transaction で登録し、1件ごとの結果と新しい id を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "items": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "登録するデータ (JSON 文字列) の配列 (最大 500 件)"
+                    },
+                    "mode": mode
+                },
+                "required": ["content", "items"]
+            }
+        }),
+        json!({
+            "name": "data_bulk_update",
+            "description": "collection の複数のデータを1つの transaction で更新し、1件ごとの結果を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "items": {
+                        "type": "array",
+                        "description": "更新するデータの配列 (最大 500 件)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "id": { "type": "number" },
+                                "data": {
+                                    "type": "string",
+                                    "description": "更新するデータ (JSON 文字列)"
+                                },
+                                "etag": { "type": "string" }
+                            },
+                            "required": ["id", "data"]
+                        }
+                    },
+                    "mode": mode
+                },
+                "required": ["content", "items"]
+            }
+        }),
+        json!({
+            "name": "data_bulk_delete",
+            "description": "collection の複数のデータを1つの transaction で削除し、1件ごとの結果を表示します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "items": {
+                        "type": "array",
+                        "description": "削除するデータの配列 (最大 500 件)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "id": { "type": "number" },
+                                "etag": { "type": "string" }
+                            },
+                            "required": ["id"]
+                        }
+                    },
+                    "mode": mode
+                },
+                "required": ["content", "items"]
+            }
+        }),
+        json!({
+            "name": "data_delete",
+            "description": "collection の id のデータを削除します。",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": content,
+                    "id": {
+                        "type": "number",
+                        "description": "id"
+                    },
+                    "etag": {
+                        "type": "string",
+                        "description": "data_list , data_getone の etag。一致しない場合は更新せず、現在のデータを返します"
+                    }
+                },
+                "required": ["content", "id"]
+            }
+        }),
+    ]
+}
 
 async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
     match request.method.as_str() {
+        "initialize" => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: request.id,
+            result: Some(json!({
+                "protocolVersion": "2024-11-05",
+                "serverInfo": {
+                    "name": "rust-data-server",
+                    "version": "1.0.0"
+                },
+                "capabilities": {
+                    "tools": {}
+                }
+            })),
+            error: None,
+        },
+        "tools/list" => {
+            let mut tools = vec![
+                json!({
+                    "name": "purchase",
+                    "description": "品名と価格を受け取り、値をAPIに送信します。",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "name": {
+                                "type": "string",
+                                "description": "購入する品名"
+                            },
+                            "price": {
+                                "type": "number",
+                                "description": "価格"
+                            }
+                        },
+                        "required": ["name", "price"]
+                    }
+                }),
+                json!({
+                    "name": "purchase_list",
+                    "description": "購入品リストを、表示します。",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {},
+                        "required": []
+                    }
+                }),
+                json!({
+                    "name": "purchase_delete",
+                    "description": "id の購入品を削除します。",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "number",
+                                "description": "id"
+                            }
+                        },
+                        "required": ["id"]
+                    }
+                }),
+            ];
+            tools.extend(data_tools(&list_collections().await));
+            tools.extend(collection_tools());
+            tools.extend(er_tools());
+            tools.extend(md_tools());
+            tools.extend(webhook_tools());
+            JsonRpcResponse {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                result: Some(json!({ "tools": tools })),
+                error: None,
+            }
+        }
         "tools/call" => {
             if let Some(params) = request.params {
                 if let Some(tool_name) = params.get("name").and_then(|v| v.as_str()) {
@@ -80,8 +857,50 @@ async fn handle_request(request: JsonRpcRequest) -> JsonRpcResponse {
                         mod_data::data_delete_handler(params, request.id).await
                     } else if tool_name == "data_update"{
                         mod_data::data_update_handler(params, request.id).await
+                    } else if tool_name == "data_patch"{
+                        mod_data::data_patch_handler(params, request.id).await
                     } else if tool_name == "data_getone"{
                         mod_data::data_getone_handler(params, request.id).await
+                    } else if tool_name == "data_history"{
+                        mod_history::data_history_handler(params, request.id).await
+                    } else if tool_name == "data_revert"{
+                        mod_history::data_revert_handler(params, request.id).await
+                    } else if tool_name == "data_bulk_create"{
+                        mod_data::data_bulk_create_handler(params, request.id).await
+                    } else if tool_name == "data_bulk_update"{
+                        mod_data::data_bulk_update_handler(params, request.id).await
+                    } else if tool_name == "data_bulk_delete"{
+                        mod_data::data_bulk_delete_handler(params, request.id).await
+                    } else if tool_name == "collection_list"{
+                        mod_collection_admin::collection_list_handler(params, request.id).await
+                    } else if tool_name == "collection_describe"{
+                        mod_collection_admin::collection_describe_handler(params, request.id).await
+                    } else if tool_name == "collection_create"{
+                        mod_collection_admin::collection_create_handler(params, request.id).await
+                    } else if tool_name == "collection_drop"{
+                        mod_collection_admin::collection_drop_handler(params, request.id).await
+                    } else if tool_name == "er_render"{
+                        mod_er::er_render_handler(params, request.id).await
+                    } else if tool_name == "er_import"{
+                        mod_er::er_import_handler(params, request.id).await
+                    } else if tool_name == "md_outline"{
+                        mod_md::md_outline_handler(params, request.id).await
+                    } else if tool_name == "md_render"{
+                        mod_md::md_render_handler(params, request.id).await
+                    } else if tool_name == "md_section_get"{
+                        mod_md::md_section_get_handler(params, request.id).await
+                    } else if tool_name == "md_section_replace"{
+                        mod_md::md_section_replace_handler(params, request.id).await
+                    } else if tool_name == "webhook_create"{
+                        mod_webhook::webhook_create_handler(params, request.id).await
+                    } else if tool_name == "webhook_list"{
+                        mod_webhook::webhook_list_handler(params, request.id).await
+                    } else if tool_name == "webhook_delete"{
+                        mod_webhook::webhook_delete_handler(params, request.id).await
+                    } else if tool_name == "webhook_deliveries"{
+                        mod_webhook::webhook_deliveries_handler(params, request.id).await
+                    } else if tool_name == "webhook_retry"{
+                        mod_webhook::webhook_retry_handler(params, request.id).await
                     } else {
                         JsonRpcResponse {
                             jsonrpc: "2.0".to_string(),
@@ -134,6 +953,8 @@ async fn main() {
     let mut stdout = io::stdout();
     
     eprintln!("MCP Server started. Waiting for requests...");
+    // 送信に失敗した webhook を定期的に再送する
+    tokio::spawn(mod_webhook::retry_loop());
 
     for line in stdin.lock().lines() {
         match line {
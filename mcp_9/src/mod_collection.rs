@@ -0,0 +1,220 @@
+use libsql::Connection;
+use serde_json::{json, Value};
+
+/// JSON Schema の後に行う data の検証
+pub type DataCheck = fn(&Value) -> Result<(), String>;
+
+/// data_* で扱う collection (table)。schema は data の JSON Schema
+/// declared は scheme.sql の collection , false は collection_create で作成した collection
+/// check は JSON Schema の後に行う検証 (JSON Schema で書けない参照の確認など)
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub name: String,
+    pub description: String,
+    pub schema: Option<Value>,
+    pub declared: bool,
+    pub check: Option<DataCheck>,
+}
+
+// collection_create で作成した collection の一覧の table
+pub const REGISTRY_TABLE: &str = "mcp_collection";
+
+/**
+* scheme.sql で作成する collection の一覧。
+*
+* @return
+*/
+pub fn declared_collections() -> Vec<Collection> {
+    vec![
+        Collection {
+            name: "todo".to_string(),
+            description: "TODO (title , body)".to_string(),
+            schema: Some(json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "body": { "type": "string" }
+                },
+                "required": ["title"]
+            })),
+            declared: true,
+            check: None,
+        },
+        Collection {
+            name: "er_chart".to_string(),
+            description: "ER 図 (entities , relations)".to_string(),
+            schema: Some(super::mod_er::chart_schema()),
+            declared: true,
+            check: Some(super::mod_er::check_chart_value),
+        },
+        Collection {
+            name: "md_data".to_string(),
+            description: "Markdown".to_string(),
+            schema: None,
+            declared: true,
+            check: None,
+        },
+    ]
+}
+
+/**
+* collection の一覧の table が無い場合は作成する。
+*
+* @param conn
+*
+* @return
+*/
+pub async fn ensure_registry(conn: &Connection) -> Result<(), String> {
+    let sql = format!("CREATE TABLE IF NOT EXISTS {} (
+      name TEXT PRIMARY KEY,
+      description TEXT NOT NULL DEFAULT '',
+      schema TEXT,
+      created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    )", REGISTRY_TABLE);
+    conn.execute(&sql, ()).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/**
+* collection_create で作成した collection の一覧。
+*
+* @param conn
+*
+* @return
+*/
+pub async fn registered_collections(conn: &Connection) -> Result<Vec<Collection>, String> {
+    let sql = format!("SELECT name, description, schema FROM {} ORDER BY name", REGISTRY_TABLE);
+    let mut rows = conn.query(&sql, ()).await.map_err(|e| e.to_string())?;
+    let mut collections = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        let schema: Option<String> = row.get(2).map_err(|e| e.to_string())?;
+        collections.push(Collection {
+            name: row.get(0).map_err(|e| e.to_string())?,
+            description: row.get(1).map_err(|e| e.to_string())?,
+            schema: schema.and_then(|schema| serde_json::from_str(&schema).ok()),
+            declared: false,
+            check: None,
+        });
+    }
+    Ok(collections)
+}
+
+/**
+* scheme.sql の collection と、collection_create で作成した collection の一覧。
+* 一覧の table がまだ無い場合は、scheme.sql の collection のみとする。
+*
+* @param conn
+*
+* @return
+*/
+pub async fn load_collections(conn: &Connection) -> Vec<Collection> {
+    let mut collections = declared_collections();
+    match registered_collections(conn).await {
+        Ok(registered) => collections.extend(registered),
+        Err(e) => eprintln!("{} not loaded: {}", REGISTRY_TABLE, e),
+    }
+    collections
+}
+
+/**
+* 名前から collection を探す。未登録の名前はエラーとする。
+*
+* @param conn
+* @param name
+*
+* @return
+*/
+pub async fn find_collection(conn: &Connection, name: &str) -> Result<Collection, String> {
+    let collections = load_collections(conn).await;
+    let names: Vec<&str> = collections.iter().map(|collection| collection.name.as_str()).collect();
+    let message = format!("unknown collection: {} ({})", name, names.join(" , "));
+    collections.into_iter()
+        .find(|collection| collection.name == name)
+        .ok_or(message)
+}
+
+/**
+* data を collection の JSON Schema で検証する。schema の無い collection は検証しない。
+* collection_create で作成した collection は、JSON パスの index があるため data は JSON のみとする。
+*
+* @param collection
+* @param data
+*
+* @return
+*/
+pub fn validate_data(collection: &Collection, data: &str) -> Result<(), String> {
+    if collection.schema.is_none() && collection.declared {
+        return Ok(());
+    }
+    let value: Value = serde_json::from_str(data).map_err(|e| format!("data is not JSON: {}", e))?;
+    if let Some(schema) = &collection.schema {
+        validate_value(schema, &value, "$")?;
+    }
+    match collection.check {
+        Some(check) => check(&value),
+        None => Ok(()),
+    }
+}
+
+fn type_matches(type_name: &str, value: &Value) -> bool {
+    match type_name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/**
+* JSON Schema の type , enum , required , properties , additionalProperties , items で検証する。
+*
+* @param schema
+* @param value
+* @param path エラー表示用の JSON パス
+*
+* @return
+*/
+fn validate_value(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    match &schema["type"] {
+        Value::String(type_name) if !type_matches(type_name, value) => {
+            return Err(format!("{}: expected {}", path, type_name));
+        }
+        Value::Array(type_names) if !type_names.iter().any(|t| type_matches(t.as_str().unwrap_or(""), value)) => {
+            return Err(format!("{}: expected {}", path, Value::Array(type_names.clone())));
+        }
+        _ => {}
+    }
+    if let Some(values) = schema["enum"].as_array()
+        && !values.contains(value) {
+        return Err(format!("{}: must be one of {}", path, Value::Array(values.clone())));
+    }
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for key in required.iter().filter_map(|key| key.as_str()) {
+                if !object.contains_key(key) {
+                    return Err(format!("{}.{}: required", path, key));
+                }
+            }
+        }
+        let properties = schema["properties"].as_object();
+        for (key, child) in object {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(child_schema) => validate_value(child_schema, child, &format!("{}.{}", path, key))?,
+                None if schema["additionalProperties"] == json!(false) => {
+                    return Err(format!("{}.{}: unknown property", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_value(item_schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+    Ok(())
+}
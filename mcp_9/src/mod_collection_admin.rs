@@ -0,0 +1,315 @@
+use libsql::Connection;
+use libsql::params;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::mod_collection::{Collection, REGISTRY_TABLE};
+use super::{connect, error_response, parse_arguments, text_response};
+
+// collection_describe で項目名を調べる件数 (新しい順)
+const SAMPLE_ROWS: i64 = 100;
+
+/**
+* collection 名を検証する。英小文字で始まり、英小文字 , 数字 , _ のみ (64文字まで)。
+*
+* @param name
+*
+* @return
+*/
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(format!("invalid collection name: {} (英小文字で始まり、英小文字 , 数字 , _ のみ)", name));
+    }
+    if name.starts_with("sqlite_") || name == REGISTRY_TABLE {
+        return Err(format!("reserved name: {}", name));
+    }
+    Ok(())
+}
+
+/**
+* JSON パスから index 名を作る。例: todo , $.tags[0] -> idx_todo_tags_0
+*
+* @param name
+* @param path
+*
+* @return
+*/
+fn index_name(name: &str, path: &str) -> String {
+    let suffix: String = path.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let suffix: Vec<&str> = suffix.split('_').filter(|part| !part.is_empty()).collect();
+    format!("idx_{}_{}", name, suffix.join("_"))
+}
+
+async fn table_exists(conn: &Connection, name: &str) -> Result<bool, String> {
+    let mut rows = conn.query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1", params![name])
+        .await.map_err(|e| e.to_string())?;
+    Ok(rows.next().await.map_err(|e| e.to_string())?.is_some())
+}
+
+async fn count_rows(conn: &Connection, name: &str) -> Result<i64, String> {
+    let sql = format!("SELECT count(*) FROM {}", name);
+    let mut rows = conn.query(&sql, ()).await.map_err(|e| e.to_string())?;
+    match rows.next().await.map_err(|e| e.to_string())? {
+        Some(row) => row.get(0).map_err(|e| e.to_string()),
+        None => Ok(0),
+    }
+}
+
+/**
+* collection_create: id , data , created_at , updated_at の table と、JSON パスの index を作成し、
+* collection の一覧に登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn collection_create_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct CreateParams {
+        name: String,
+        #[serde(default)]
+        description: String,
+        schema: Option<Value>,
+        #[serde(default)]
+        indexes: Vec<String>,
+    }
+    let create_params = match parse_arguments::<CreateParams>(&params, &request_id) {
+        Ok(create_params) => create_params,
+        Err(response) => return response,
+    };
+    let name = create_params.name.clone();
+    if let Err(e) = validate_name(&name) {
+        return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+    }
+    if let Some(schema) = &create_params.schema
+        && !schema.is_object() {
+        return error_response(request_id, -32602, "Invalid parameters, schema must be an object".to_string());
+    }
+    let mut index_sql_list = Vec::new();
+    for path in &create_params.indexes {
+        let literal = match super::mod_query::path_literal(path) {
+            Ok(literal) => literal,
+            Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+        };
+        let index = index_name(&name, path);
+        if index_sql_list.iter().any(|(other, _)| *other == index) {
+            return error_response(request_id, -32602, format!("Invalid parameters, duplicate index: {}", path));
+        }
+        let sql = format!("CREATE INDEX {} ON {} (json_extract(data, {}))", index, name, literal);
+        index_sql_list.push((index, sql));
+    }
+
+    let conn = connect().await;
+    if let Err(e) = super::mod_collection::ensure_registry(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    if super::mod_collection::find_collection(&conn, &name).await.is_ok() {
+        return error_response(request_id, -32602, format!("Invalid parameters, collection already exists: {}", name));
+    }
+    // collection 以外の table (item_price など) と同じ名前は使えない
+    match table_exists(&conn, &name).await {
+        Ok(false) => {}
+        Ok(true) => return error_response(request_id, -32602, format!("Invalid parameters, table already exists: {}", name)),
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+
+    let tx = conn.transaction().await.unwrap();
+    let table_sql = format!("CREATE TABLE {} (
+      id INTEGER PRIMARY KEY AUTOINCREMENT,
+      data TEXT NOT NULL,
+      created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+      updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    )", name);
+    let register_sql = format!("INSERT INTO {} (name, description, schema) VALUES (?1, ?2, ?3)", REGISTRY_TABLE);
+    let schema = create_params.schema.as_ref().map(|schema| schema.to_string());
+    let mut sql_list = vec![table_sql];
+    sql_list.extend(index_sql_list.iter().map(|(_, sql)| sql.clone()));
+    for sql in &sql_list {
+        eprintln!("sql={}", sql);
+        if let Err(e) = tx.execute(sql, ()).await {
+            tx.rollback().await.ok();
+            return error_response(request_id, -32603, format!("Query error: {}", e));
+        }
+    }
+    if let Err(e) = tx.execute(&register_sql, params![name.clone(), create_params.description.clone(), schema]).await {
+        tx.rollback().await.ok();
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    tx.commit().await.unwrap();
+
+    let indexes: Vec<&str> = index_sql_list.iter().map(|(index, _)| index.as_str()).collect();
+    text_response(request_id, format!("Complete create, name={} , indexes=[{}]", name, indexes.join(" , ")))
+}
+
+/**
+* collection_list: collection の一覧と件数を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn collection_list_handler(_params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let conn = connect().await;
+    let mut items = Vec::new();
+    for collection in super::mod_collection::load_collections(&conn).await {
+        // scheme.sql を実行していない場合は table が無いため、件数は null とする
+        let count = count_rows(&conn, &collection.name).await.ok();
+        items.push(json!({
+            "name": collection.name,
+            "description": collection.description,
+            "declared": collection.declared,
+            "schema": collection.schema.is_some(),
+            "count": count,
+        }));
+    }
+    text_response(request_id, Value::Array(items).to_string())
+}
+
+/**
+* 新しい順に SAMPLE_ROWS 件の data から、最上位の項目名と件数を集計する。
+*
+* @param conn
+* @param name
+*
+* @return
+*/
+async fn sample_keys(conn: &Connection, name: &str) -> Result<Vec<Value>, String> {
+    let sql = format!("SELECT j.key, count(*) AS n
+    FROM (SELECT data FROM {} ORDER BY id DESC LIMIT ?1) AS t, json_each(t.data) AS j
+    WHERE json_valid(t.data) AND json_type(t.data) = 'object'
+    GROUP BY j.key
+    ORDER BY n DESC, j.key
+    ", name);
+    let mut rows = conn.query(&sql, params![SAMPLE_ROWS]).await.map_err(|e| e.to_string())?;
+    let mut keys = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        let key: String = row.get(0).map_err(|e| e.to_string())?;
+        let count: i64 = row.get(1).map_err(|e| e.to_string())?;
+        keys.push(json!({ "key": key, "count": count }));
+    }
+    Ok(keys)
+}
+
+async fn table_indexes(conn: &Connection, name: &str) -> Result<Vec<Value>, String> {
+    let mut rows = conn.query("SELECT name, sql FROM sqlite_master WHERE type = 'index' AND tbl_name = ?1 AND sql IS NOT NULL ORDER BY name", params![name])
+        .await.map_err(|e| e.to_string())?;
+    let mut indexes = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        let index: String = row.get(0).map_err(|e| e.to_string())?;
+        let sql: String = row.get(1).map_err(|e| e.to_string())?;
+        indexes.push(json!({ "name": index, "sql": sql }));
+    }
+    Ok(indexes)
+}
+
+async fn describe(conn: &Connection, collection: &Collection) -> Result<Value, String> {
+    let sql = format!("SELECT count(*), min(created_at), max(updated_at) FROM {}", collection.name);
+    let mut rows = conn.query(&sql, ()).await.map_err(|e| e.to_string())?;
+    let row = rows.next().await.map_err(|e| e.to_string())?.ok_or("no rows".to_string())?;
+    let count: i64 = row.get(0).map_err(|e| e.to_string())?;
+    let first_created_at: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+    let last_updated_at: Option<String> = row.get(2).map_err(|e| e.to_string())?;
+    Ok(json!({
+        "name": collection.name,
+        "description": collection.description,
+        "declared": collection.declared,
+        "schema": collection.schema,
+        "count": count,
+        "first_created_at": first_created_at,
+        "last_updated_at": last_updated_at,
+        "sample_rows": SAMPLE_ROWS.min(count),
+        "keys": sample_keys(conn, &collection.name).await?,
+        "indexes": table_indexes(conn, &collection.name).await?,
+    }))
+}
+
+/**
+* collection_describe: 件数 , 項目名 (新しい順の data から集計) , JSON Schema , index を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn collection_describe_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DescribeParams {
+        name: String,
+    }
+    let describe_params = match parse_arguments::<DescribeParams>(&params, &request_id) {
+        Ok(describe_params) => describe_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &describe_params.name).await {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    match describe(&conn, &collection).await {
+        Ok(description) => text_response(request_id, description.to_string()),
+        Err(e) => error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+}
+
+/**
+* collection_drop: collection_create で作成した collection の table を削除する。
+* confirm に collection 名を指定した場合のみ削除する。scheme.sql の collection は削除できない。
+*
+* @param
+*
+* @return
+*/
+pub async fn collection_drop_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DropParams {
+        name: String,
+        #[serde(default)]
+        confirm: String,
+    }
+    let drop_params = match parse_arguments::<DropParams>(&params, &request_id) {
+        Ok(drop_params) => drop_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &drop_params.name).await {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    if collection.declared {
+        return error_response(request_id, -32602, format!("Invalid parameters, {} is declared in scheme.sql and cannot be dropped", collection.name));
+    }
+    let count = count_rows(&conn, &collection.name).await.unwrap_or(0);
+    if drop_params.confirm != collection.name {
+        return error_response(request_id, -32602, format!(
+            "Invalid parameters, confirm is required: {} rows will be deleted. Set confirm to \"{}\"", count, collection.name));
+    }
+
+    if let Err(e) = super::mod_history::ensure_history(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    let tx = conn.transaction().await.unwrap();
+    let drop_sql = format!("DROP TABLE IF EXISTS {}", collection.name);
+    let unregister_sql = format!("DELETE FROM {} WHERE name = ?1", REGISTRY_TABLE);
+    eprintln!("sql={}", drop_sql);
+    let result = match tx.execute(&drop_sql, ()).await {
+        Ok(_) => tx.execute(&unregister_sql, params![collection.name.clone()]).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    // 同じ名前で作成し直した collection に、古い履歴が残らないようにする
+    let result = match result {
+        Ok(_) => super::mod_history::delete_history(&tx, &collection.name).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tx.rollback().await.ok();
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    tx.commit().await.unwrap();
+    text_response(request_id, format!("Complete drop, name={} , rows={}", collection.name, count))
+}
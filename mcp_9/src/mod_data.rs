@@ -7,15 +7,18 @@ use serde_json::{json, Value};
 use std::env;
 use std::io::{self, BufRead, Write};
 use dotenvy::dotenv;
+use super::{connect, error_response, parse_arguments};
 
 #[derive(Debug, Deserialize,Serialize)]
 struct ItemParams {
     content: String,
     data: String,
 }
-#[derive(Debug, Deserialize,Serialize)]
+#[derive(Debug, Deserialize)]
 struct ItemListParams {
     content: String,
+    #[serde(flatten)]
+    query: super::mod_query::ListQuery,
 }
 #[derive(Debug, Deserialize,Serialize)]
 struct ItemGetParams {
@@ -24,21 +27,103 @@ struct ItemGetParams {
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Item {
-    id: i64,
-    data: String,
-    created_at: String,
-    updated_at: String,
+    pub id: i64,
+    pub data: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub etag: String,
 }
 #[derive(Debug, Deserialize,Serialize)]
 struct ItemDeleteParams {
     content: String,
     id: i32,
+    etag: Option<String>,
+}
+
+// 更新の競合 (etag の不一致)
+const CONFLICT_CODE: i32 = -32009;
+
+impl Item {
+    fn new(id: i64, data: String, created_at: String, updated_at: String) -> Self {
+        let etag = item_etag(&data, &updated_at);
+        Item {
+            id,
+            data,
+            created_at,
+            updated_at,
+            etag,
+        }
+    }
+}
+
+/**
+* updated_at と data から etag を作る (FNV-1a 64bit)。
+* updated_at は秒単位のため、同じ秒の更新も区別できるよう data も含める。
+*
+* @param data
+* @param updated_at
+*
+* @return
+*/
+pub fn item_etag(data: &str, updated_at: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in updated_at.bytes().chain([b'\n']).chain(data.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/**
+* id のデータを読み込む。
+*
+* @param conn
+* @param table 登録済みの collection 名
+* @param id
+*
+* @return
+*/
+pub async fn find_item(conn: &Connection, table: &str, id: i32) -> Option<Item> {
+    let sql = format!("SELECT id, data ,created_at, updated_at FROM {} WHERE id = ?1", table);
+    let row = conn.query(&sql, params![id]).await.unwrap().next().await.unwrap()?;
+    Some(Item::new(row.get(0).unwrap(), row.get(1).unwrap(), row.get(2).unwrap(), row.get(3).unwrap()))
+}
+
+/**
+* etag が一致しない場合のエラー。現在のデータを含める。
+*/
+pub fn conflict_response(request_id: Option<Value>, etag: &str, current: Option<Item>) -> super::JsonRpcResponse {
+    let message = match current {
+        Some(current) => format!("Conflict, etag={} , current={}", etag, serde_json::to_string(&current).expect("JSON convert error")),
+        None => format!("Conflict, etag={} , current=deleted", etag),
+    };
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(super::JsonRpcError {
+            code: CONFLICT_CODE,
+            message,
+        }),
+    }
 }
 
 pub fn purchase(product_name: String, price: i32) -> String {
     format!("「{}」を{}円で購入しました。", product_name, price)
 }
 
+fn invalid_collection_response(request_id: Option<Value>, message: String) -> super::JsonRpcResponse {
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: None,
+        error: Some(super::JsonRpcError {
+            code: -32602,
+            message: format!("Invalid parameters, {}", message),
+        }),
+    }
+}
+
 pub async fn data_create_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
 {
     let url = super::TURSO_DATABASE_URL.to_string();
@@ -50,15 +135,25 @@ pub async fn data_create_handler(params: Value, request_id: Option<Value>) -> su
     if let Some(arguments) = params.get("arguments") {
         match serde_json::from_value::<ItemParams>(arguments.clone()) {
             Ok(item_params) => {
-                let post_data = ItemParams {
-                    content: item_params.content.clone(),
-                    data: item_params.data.clone()
-                };    
-                let sql = format!("INSERT INTO {} (data) VALUES ('{}')", &post_data.content, &post_data.data);
-                let mut result = conn
-                    .execute(&sql, ())
+                let collection = match super::mod_collection::find_collection(&conn, &item_params.content).await {
+                    Ok(collection) => collection,
+                    Err(e) => return invalid_collection_response(request_id, e),
+                };
+                if let Err(e) = super::mod_collection::validate_data(&collection, &item_params.data) {
+                    return invalid_collection_response(request_id, e);
+                }
+                super::mod_history::ensure_history(&conn).await.unwrap();
+                let tx = conn.transaction().await.unwrap();
+                let sql = format!("INSERT INTO {} (data) VALUES (?1) RETURNING id", collection.name);
+                let row = tx
+                    .query(&sql, params![item_params.data.clone()])
                     .await
-                    .unwrap();
+                    .unwrap()
+                    .next().await.unwrap().unwrap();
+                let new_id: i64 = row.get(0).unwrap();
+                super::mod_history::record_history(&tx, &collection.name, new_id, super::mod_history::HistoryAction::Create, None, Some(&item_params.data)).await.unwrap();
+                tx.commit().await.unwrap();
+                super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataCreate, &collection.name, new_id, Some(&item_params.data)).await;
 
                 return super::JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
@@ -117,28 +212,25 @@ pub async fn data_getone_handler(params: Value, request_id: Option<Value>) -> su
     if let Some(arguments) = params.get("arguments") {
         match serde_json::from_value::<ItemGetParams>(arguments.clone()) {
           Ok(item_get_params) => {
-            let content = item_get_params.content.clone();
+            let collection = match super::mod_collection::find_collection(&conn, &item_get_params.content).await {
+                Ok(collection) => collection,
+                Err(e) => return invalid_collection_response(request_id, e),
+            };
             let id_value = item_get_params.id;
-            let order_sql = "ORDER BY created_at DESC LIMIT 5;";
             let sql = format!("SELECT id, data ,created_at, updated_at 
-            FROM {} WHERE ID = {}
+            FROM {} WHERE ID = ?1
             "
-            , content, id_value
+            , collection.name
             );
             println!("sql={}", sql);
             let mut rows = conn.query(&sql,
-                (),  // 引数なし
+                params![id_value],
             ).await.unwrap();
             let mut todos: Vec<Item> = Vec::new();
             while let Some(row) = rows.next().await.unwrap() {
                 let id: i64 = row.get(0).unwrap();
                 let data: String = row.get(1).unwrap();
-                todos.push(Item {
-                    id: id,
-                    data: data,
-                    created_at: row.get(2).unwrap(),
-                    updated_at: row.get(3).unwrap(),        
-                });        
+                todos.push(Item::new(id, data, row.get(2).unwrap(), row.get(3).unwrap()));
             }
             let json_string_variable = serde_json::to_string(&todos).expect("JSON convert error");
             println!("変換されたJSON文字列: {}", json_string_variable);            
@@ -197,40 +289,67 @@ pub async fn data_list_handler(params: Value, request_id: Option<Value>) -> supe
     if let Some(arguments) = params.get("arguments") {
         match serde_json::from_value::<ItemListParams>(arguments.clone()) {
           Ok(item_list_params) => {
-            let content = item_list_params.content.clone();
-            let order_sql = "ORDER BY created_at DESC LIMIT 10;";
-            let sql = format!("SELECT id, data ,created_at, updated_at 
-            FROM {} {}
-            "
-            , content, order_sql
-            );
-            println!("sql={}", sql);
-            let mut rows = conn.query(&sql,
-                (),  // 引数なし
-            ).await.unwrap();
+            let content = match super::mod_collection::find_collection(&conn, &item_list_params.content).await {
+                Ok(collection) => collection.name,
+                Err(e) => return invalid_collection_response(request_id, e),
+            };
+            let query = &item_list_params.query;
+            let list_sql = match super::mod_query::build_list_sql(&content, query) {
+                Ok(list_sql) => list_sql,
+                Err(e) => return invalid_collection_response(request_id, e),
+            };
+            eprintln!("sql={}", list_sql.sql);
+            let mut rows = match conn.query(&list_sql.sql, list_sql.values).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    return super::JsonRpcResponse {
+                        jsonrpc: "2.0".to_string(),
+                        id: request_id,
+                        result: None,
+                        error: Some(super::JsonRpcError {
+                            code: -32603,
+                            message: format!("Query error: {}", e),
+                        }),
+                    };
+                }
+            };
             let mut todos: Vec<Item> = Vec::new();
+            let mut last_sort_value = libsql::Value::Null;
+            let mut next_cursor: Option<String> = None;
             while let Some(row) = rows.next().await.unwrap() {
                 let id: i64 = row.get(0).unwrap();
                 let data: String = row.get(1).unwrap();
-                todos.push(Item {
-                    id: id,
-                    data: data,
-                    created_at: row.get(2).unwrap(),
-                    updated_at: row.get(3).unwrap(),        
-                });        
+                if todos.len() as i64 == list_sql.limit {
+                    // limit + 1 件目があれば、最後の行から次のページの cursor を作る
+                    let last = todos.last().unwrap();
+                    next_cursor = Some(super::mod_query::make_cursor(&last_sort_value, last.id));
+                    break;
+                }
+                last_sort_value = row.get_value(4).unwrap();
+                // etag は data 全体から作る
+                let mut item = Item::new(id, data, row.get(2).unwrap(), row.get(3).unwrap());
+                if !query.fields.is_empty() {
+                    item.data = super::mod_query::project_fields(&item.data, &query.fields);
+                }
+                todos.push(item);
             }
             let json_string_variable = serde_json::to_string(&todos).expect("JSON convert error");
             println!("変換されたJSON文字列: {}", json_string_variable);            
+            let mut content_items = vec![json!({
+                "type": "text",
+                "text": json_string_variable.to_string()
+            })];
+            if let Some(next_cursor) = next_cursor {
+                content_items.push(json!({
+                    "type": "text",
+                    "text": format!("next_cursor={}", next_cursor)
+                }));
+            }
             return super::JsonRpcResponse {
                 jsonrpc: "2.0".to_string(),
                 id: request_id,
                 result: Some(json!({
-                    "content": [
-                        {
-                            "type": "text",
-                            "text": json_string_variable.to_string()
-                        }
-                    ]
+                    "content": content_items
                 })),
                 error: None,
             };  
@@ -278,22 +397,12 @@ pub async fn data_delete_handler(params: Value, request_id: Option<Value>) -> su
         match serde_json::from_value::<ItemDeleteParams>(arguments.clone()) {
             Ok(item_delete_params) => {
                 let id_value = item_delete_params.id;
-                let content_value = item_delete_params.content.clone();
+                let content_value = match super::mod_collection::find_collection(&conn, &item_delete_params.content).await {
+                    Ok(collection) => collection.name,
+                    Err(e) => return invalid_collection_response(request_id, e),
+                };
                 //select-id
-                let select_sql = format!("SELECT id, data ,created_at, updated_at 
-                FROM {}
-                WHERE id= {} ;
-                "
-                ,content_value , id_value
-                );
-                let mut select_rows = conn.query(&select_sql,
-                    (),  // 引数なし
-                ).await.unwrap(); 
-                let mut count = 0;
-                while let Some(row) = select_rows.next().await.unwrap() {
-                    count += 1;
-                }
-                if count == 0 {
+                let Some(current) = find_item(&conn, &content_value, id_value).await else {
                     return super::JsonRpcResponse {
                         jsonrpc: "2.0".to_string(),
                         id: request_id,
@@ -303,14 +412,34 @@ pub async fn data_delete_handler(params: Value, request_id: Option<Value>) -> su
                             message: format!("Invalid parameters, id={}", id_value),
                         }),
                     };                            
+                };
+                if let Some(etag) = &item_delete_params.etag
+                    && *etag != current.etag {
+                    return conflict_response(request_id, etag, Some(current));
                 }
-
-                let sql = format!("DELETE FROM {} WHERE id = {}"
-                ,content_value , id_value);
-                let mut result = conn
-                    .execute(&sql, ())
-                    .await
-                    .unwrap();
+                super::mod_history::ensure_history(&conn).await.unwrap();
+                let tx = conn.transaction().await.unwrap();
+                let mut sql = format!("DELETE FROM {} WHERE id = ?1", content_value);
+                let mut values = vec![libsql::Value::Integer(id_value as i64)];
+                if item_delete_params.etag.is_some() {
+                    // 読み込み後に更新された場合は削除しない
+                    sql.push_str(" AND data = ?2 AND updated_at = ?3");
+                    values.push(libsql::Value::Text(current.data.clone()));
+                    values.push(libsql::Value::Text(current.updated_at.clone()));
+                }
+                sql.push_str(" RETURNING data, created_at");
+                let deleted = tx.query(&sql, values).await.unwrap().next().await.unwrap();
+                let Some(deleted) = deleted else {
+                    let latest = find_item(&tx, &content_value, id_value).await;
+                    tx.rollback().await.ok();
+                    return conflict_response(request_id, item_delete_params.etag.as_deref().unwrap_or_default(), latest);
+                };
+                // 履歴には、実際に削除したデータを残す
+                let deleted_data: String = deleted.get(0).unwrap();
+                let deleted_created_at: Option<String> = deleted.get(1).unwrap();
+                super::mod_history::record_delete_history(&tx, &content_value, id_value as i64, &deleted_data, deleted_created_at.as_deref()).await.unwrap();
+                tx.commit().await.unwrap();
+                super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataDelete, &content_value, id_value as i64, Some(&deleted_data)).await;
 
                 let resp = format!("Complete delete, id={}", id_value);
                 return super::JsonRpcResponse {
@@ -365,6 +494,7 @@ pub async fn data_update_handler(params: Value, request_id: Option<Value>) -> su
         content: String,
         data: String,
         id: i32,
+        etag: Option<String>,
     }    
     let url = super::TURSO_DATABASE_URL.to_string();
     let token = super::TURSO_AUTH_TOKEN.to_string();
@@ -382,14 +512,38 @@ pub async fn data_update_handler(params: Value, request_id: Option<Value>) -> su
                     id: item_params.id,
                 };    
                 */
-                let sql = format!("UPDATE {} SET data = '{}' WHERE id = {}"
-                , &item_params.content, &item_params.data, &item_params.id
+                let collection = match super::mod_collection::find_collection(&conn, &item_params.content).await {
+                    Ok(collection) => collection,
+                    Err(e) => return invalid_collection_response(request_id, e),
+                };
+                if let Err(e) = super::mod_collection::validate_data(&collection, &item_params.data) {
+                    return invalid_collection_response(request_id, e);
+                }
+                let Some(current) = find_item(&conn, &collection.name, item_params.id).await else {
+                    return invalid_collection_response(request_id, format!("id={}", item_params.id));
+                };
+                if let Some(etag) = &item_params.etag
+                    && *etag != current.etag {
+                    return conflict_response(request_id, etag, Some(current));
+                }
+                super::mod_history::ensure_history(&conn).await.unwrap();
+                let tx = conn.transaction().await.unwrap();
+                // 読み込み後に他の更新があった場合は、更新しない
+                let sql = format!("UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2 AND data = ?3 AND updated_at = ?4"
+                , collection.name
               );
-//                let sql = format!("INSERT INTO {} (data) VALUES ('{}')", &post_data.content, &post_data.data);
-                let mut result = conn
-                    .execute(&sql, ())
+                let count = tx
+                    .execute(&sql, params![item_params.data.clone(), item_params.id, current.data.clone(), current.updated_at.clone()])
                     .await
                     .unwrap();
+                let updated = find_item(&tx, &collection.name, item_params.id).await;
+                if count == 0 {
+                    tx.rollback().await.ok();
+                    return conflict_response(request_id, &current.etag, updated);
+                }
+                super::mod_history::record_history(&tx, &collection.name, item_params.id as i64, super::mod_history::HistoryAction::Update, Some(&current.data), Some(&item_params.data)).await.unwrap();
+                tx.commit().await.unwrap();
+                super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataUpdate, &collection.name, item_params.id as i64, Some(&item_params.data)).await;
 
                 return super::JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
@@ -398,7 +552,7 @@ pub async fn data_update_handler(params: Value, request_id: Option<Value>) -> su
                         "content": [
                             {
                                 "type": "text",
-                                "text": "OK".to_string()
+                                "text": format!("OK, etag={}", updated.map(|item| item.etag).unwrap_or_default())
                             }
                         ]
                     })),
@@ -428,4 +582,348 @@ pub async fn data_update_handler(params: Value, request_id: Option<Value>) -> su
             message: "Tool not found".to_string(),
         }),
     }
-}
\ No newline at end of file
+}
+
+/**
+* data_patch: id のデータに merge_patch (RFC 7396) または patch (RFC 6902) を適用する。
+* 読み込みから更新までを transaction で行い、更新後のデータを返す。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_patch_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse 
+{
+    #[derive(Debug, Deserialize)]
+    struct PatchParams {
+        content: String,
+        id: i32,
+        merge_patch: Option<Value>,
+        patch: Option<Vec<super::mod_patch::PatchOperation>>,
+        etag: Option<String>,
+    }
+    let patch_params = match parse_arguments::<PatchParams>(&params, &request_id) {
+        Ok(patch_params) => patch_params,
+        Err(response) => return response,
+    };
+    if patch_params.merge_patch.is_some() == patch_params.patch.is_some() {
+        return error_response(request_id, -32602, "Invalid parameters, merge_patch or patch is required".to_string());
+    }
+
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &patch_params.content).await {
+        Ok(collection) => collection,
+        Err(e) => return invalid_collection_response(request_id, e),
+    };
+    super::mod_history::ensure_history(&conn).await.unwrap();
+    let tx = conn.transaction().await.unwrap();
+
+    let current = match find_item(&tx, &collection.name, patch_params.id).await {
+        Some(current) => current,
+        None => {
+            tx.rollback().await.ok();
+            return error_response(request_id, -32602, format!("Invalid parameters, id={}", patch_params.id));
+        }
+    };
+    if let Some(etag) = &patch_params.etag
+        && *etag != current.etag {
+        tx.rollback().await.ok();
+        return conflict_response(request_id, etag, Some(current));
+    }
+    let mut document: Value = match serde_json::from_str(&current.data) {
+        Ok(document) => document,
+        Err(e) => {
+            tx.rollback().await.ok();
+            return error_response(request_id, -32603, format!("data is not JSON: {}", e));
+        }
+    };
+    let patched = match (&patch_params.merge_patch, &patch_params.patch) {
+        (Some(merge_patch), _) => {
+            super::mod_patch::merge_patch(&mut document, merge_patch);
+            Ok(())
+        }
+        (_, Some(operations)) => super::mod_patch::json_patch(&mut document, operations),
+        _ => Ok(()),
+    };
+    let data = document.to_string();
+    if let Err(e) = patched.and_then(|_| super::mod_collection::validate_data(&collection, &data)) {
+        tx.rollback().await.ok();
+        return invalid_collection_response(request_id, e);
+    }
+
+    let update_sql = format!("UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2 AND data = ?3 AND updated_at = ?4", collection.name);
+    let count = tx.execute(&update_sql, params![data, patch_params.id, current.data.clone(), current.updated_at.clone()]).await.unwrap();
+    let item = find_item(&tx, &collection.name, patch_params.id).await;
+    if count == 0 {
+        tx.rollback().await.ok();
+        return conflict_response(request_id, &current.etag, item);
+    }
+    let item = item.unwrap();
+    super::mod_history::record_history(&tx, &collection.name, item.id, super::mod_history::HistoryAction::Update, Some(&current.data), Some(&item.data)).await.unwrap();
+    tx.commit().await.unwrap();
+    super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataUpdate, &collection.name, item.id, Some(&item.data)).await;
+
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": serde_json::to_string(&item).expect("JSON convert error")
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
+// data_bulk_* の1回の件数の上限
+const MAX_BULK_ITEMS: usize = 500;
+
+/// data_bulk_* の実行方法。atomic: 1件でも失敗した場合はすべて取り消す , best_effort: 成功した分のみ反映する
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum BulkMode {
+    #[default]
+    Atomic,
+    BestEffort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BulkKind {
+    Create,
+    Update,
+    Delete,
+}
+
+/// data_bulk_* の1件の結果。status は created , updated , deleted , error , rolled_back , skipped
+#[derive(Debug, Serialize)]
+struct BulkResult {
+    index: usize,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// data_bulk_* の1件の実行結果。data は変更後 (削除の場合は削除したデータ)
+struct BulkDone {
+    id: i64,
+    etag: Option<String>,
+    data: String,
+}
+
+/**
+* data_bulk_* の1件を実行する。成功した場合は id と etag (削除の場合は None) を返す。
+*
+* @param conn transaction
+* @param collection
+* @param kind
+* @param item data_bulk_create は data (JSON 文字列) , それ以外は {id, data, etag}
+*
+* @return
+*/
+async fn run_bulk_item(conn: &Connection, collection: &super::mod_collection::Collection, kind: BulkKind, item: &Value) -> Result<BulkDone, String> {
+    #[derive(Debug, Deserialize)]
+    struct BulkItem {
+        id: i32,
+        data: Option<String>,
+        etag: Option<String>,
+    }
+    if kind == BulkKind::Create {
+        let data = item.as_str().ok_or("data must be a JSON string".to_string())?;
+        super::mod_collection::validate_data(collection, data)?;
+        let sql = format!("INSERT INTO {} (data) VALUES (?1) RETURNING id, updated_at", collection.name);
+        let mut rows = conn.query(&sql, params![data]).await.map_err(|e| e.to_string())?;
+        let row = rows.next().await.map_err(|e| e.to_string())?.ok_or("insert failed".to_string())?;
+        let id: i64 = row.get(0).map_err(|e| e.to_string())?;
+        let updated_at: String = row.get(1).map_err(|e| e.to_string())?;
+        super::mod_history::record_history(conn, &collection.name, id, super::mod_history::HistoryAction::Create, None, Some(data)).await?;
+        return Ok(BulkDone { id, etag: Some(item_etag(data, &updated_at)), data: data.to_string() });
+    }
+    let bulk_item: BulkItem = serde_json::from_value(item.clone()).map_err(|e| e.to_string())?;
+    if let Some(data) = &bulk_item.data {
+        super::mod_collection::validate_data(collection, data)?;
+    }
+    let current = find_item(conn, &collection.name, bulk_item.id).await.ok_or(format!("id={} not found", bulk_item.id))?;
+    if let Some(etag) = &bulk_item.etag
+        && *etag != current.etag {
+        return Err(format!("Conflict, etag={} , current etag={}", etag, current.etag));
+    }
+    // 読み込み後に他の更新があった場合は、更新しない
+    let (count, new_data) = match kind {
+        BulkKind::Update => {
+            let data = bulk_item.data.ok_or("data is required".to_string())?;
+            let sql = format!("UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2 AND data = ?3 AND updated_at = ?4", collection.name);
+            let count = conn.execute(&sql, params![data.clone(), bulk_item.id, current.data.clone(), current.updated_at.clone()]).await;
+            (count, Some(data))
+        }
+        _ => {
+            let sql = format!("DELETE FROM {} WHERE id = ?1 AND data = ?2 AND updated_at = ?3", collection.name);
+            let count = conn.execute(&sql, params![bulk_item.id, current.data.clone(), current.updated_at.clone()]).await;
+            (count, None)
+        }
+    };
+    if count.map_err(|e| e.to_string())? == 0 {
+        return Err(format!("Conflict, etag={} , updated by another request", current.etag));
+    }
+    match &new_data {
+        Some(data) => super::mod_history::record_history(conn, &collection.name, bulk_item.id as i64, super::mod_history::HistoryAction::Update, Some(&current.data), Some(data)).await?,
+        None => super::mod_history::record_delete_history(conn, &collection.name, bulk_item.id as i64, &current.data, Some(&current.created_at)).await?,
+    }
+    let etag = match kind {
+        BulkKind::Update => find_item(conn, &collection.name, bulk_item.id).await.map(|item| item.etag),
+        _ => None,
+    };
+    Ok(BulkDone { id: bulk_item.id as i64, etag, data: new_data.unwrap_or(current.data) })
+}
+
+/**
+* data_bulk_create , data_bulk_update , data_bulk_delete: items を1つの transaction で実行し、1件ごとの結果を返す。
+* best_effort の場合は、失敗した件のみ SAVEPOINT まで戻して、成功した件を commit する。
+*
+* @param params
+* @param request_id
+* @param kind
+*
+* @return
+*/
+async fn data_bulk_handler(params: Value, request_id: Option<Value>, kind: BulkKind) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct BulkParams {
+        content: String,
+        items: Vec<Value>,
+        #[serde(default)]
+        mode: BulkMode,
+    }
+    let bulk_params = match parse_arguments::<BulkParams>(&params, &request_id) {
+        Ok(bulk_params) => bulk_params,
+        Err(response) => return response,
+    };
+    if bulk_params.items.is_empty() || bulk_params.items.len() > MAX_BULK_ITEMS {
+        return error_response(request_id, -32602, format!("Invalid parameters, items must be 1 to {} items", MAX_BULK_ITEMS));
+    }
+
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &bulk_params.content).await {
+        Ok(collection) => collection,
+        Err(e) => return invalid_collection_response(request_id, e),
+    };
+    super::mod_history::ensure_history(&conn).await.unwrap();
+    let tx = conn.transaction().await.unwrap();
+
+    let done_status = match kind {
+        BulkKind::Create => "created",
+        BulkKind::Update => "updated",
+        BulkKind::Delete => "deleted",
+    };
+    let mut results: Vec<BulkResult> = Vec::new();
+    let mut done: Vec<BulkDone> = Vec::new();
+    let mut failed = false;
+    for (index, item) in bulk_params.items.iter().enumerate() {
+        if failed && bulk_params.mode == BulkMode::Atomic {
+            results.push(BulkResult { index, status: "skipped", id: None, etag: None, error: None });
+            continue;
+        }
+        // 1件ごとに SAVEPOINT で区切り、途中で失敗した件の変更 (履歴を含む) は取り消す
+        let savepoint = format!("bulk_{}", index);
+        tx.execute(&format!("SAVEPOINT {}", savepoint), ()).await.unwrap();
+        match run_bulk_item(&tx, &collection, kind, item).await {
+            Ok(bulk_done) => {
+                results.push(BulkResult { index, status: done_status, id: Some(bulk_done.id), etag: bulk_done.etag.clone(), error: None });
+                done.push(bulk_done);
+            }
+            Err(e) => {
+                tx.execute(&format!("ROLLBACK TO {}", savepoint), ()).await.unwrap();
+                failed = true;
+                results.push(BulkResult { index, status: "error", id: None, etag: None, error: Some(e) });
+            }
+        }
+        tx.execute(&format!("RELEASE {}", savepoint), ()).await.unwrap();
+    }
+
+    let committed = !(failed && bulk_params.mode == BulkMode::Atomic);
+    if committed {
+        tx.commit().await.unwrap();
+        let event = match kind {
+            BulkKind::Create => super::mod_webhook::WebhookEvent::DataCreate,
+            BulkKind::Update => super::mod_webhook::WebhookEvent::DataUpdate,
+            BulkKind::Delete => super::mod_webhook::WebhookEvent::DataDelete,
+        };
+        for bulk_done in &done {
+            super::mod_webhook::emit(&conn, event, &collection.name, bulk_done.id, Some(&bulk_done.data)).await;
+        }
+    } else {
+        tx.rollback().await.unwrap();
+        // 取り消した分は、登録した id , etag を返さない
+        for result in results.iter_mut().filter(|result| result.status == done_status) {
+            result.status = "rolled_back";
+            if kind == BulkKind::Create {
+                result.id = None;
+            }
+            result.etag = None;
+        }
+    }
+    let succeeded = results.iter().filter(|result| result.status == done_status).count();
+    let summary = json!({
+        "mode": bulk_params.mode,
+        "committed": committed,
+        "succeeded": succeeded,
+        "failed": results.iter().filter(|result| result.status == "error").count(),
+        "results": results,
+    });
+    super::JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: request_id,
+        result: Some(json!({
+            "content": [
+                {
+                    "type": "text",
+                    "text": summary.to_string()
+                }
+            ]
+        })),
+        error: None,
+    }
+}
+
+/**
+* data_bulk_create: items (JSON 文字列の配列) を登録し、新しい id を返す。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_bulk_create_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    data_bulk_handler(params, request_id, BulkKind::Create).await
+}
+
+/**
+* data_bulk_update: items ({id, data, etag} の配列) を更新する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_bulk_update_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    data_bulk_handler(params, request_id, BulkKind::Update).await
+}
+
+/**
+* data_bulk_delete: items ({id, etag} の配列) を削除する。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_bulk_delete_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    data_bulk_handler(params, request_id, BulkKind::Delete).await
+}
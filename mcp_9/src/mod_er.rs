@@ -0,0 +1,757 @@
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use super::{connect, error_response, parse_arguments, text_response};
+
+/// er_chart の data。entities と relations の ER 図
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ErChart {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub entities: Vec<Entity>,
+    #[serde(default)]
+    pub relations: Vec<Relation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Entity {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub columns: Vec<Column>,
+}
+
+/// 列。references は参照先の entity.column
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub primary_key: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub not_null: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unique: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub references: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Text,
+    Real,
+    Boolean,
+    Timestamp,
+    Date,
+    Json,
+    Blob,
+}
+
+const COLUMN_TYPES: [&str; 8] = ["integer", "text", "real", "boolean", "timestamp", "date", "json", "blob"];
+
+/// from から見た to の多重度
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    OneToOne,
+    OneToMany,
+    ManyToOne,
+    ManyToMany,
+}
+
+const CARDINALITIES: [&str; 4] = ["one_to_one", "one_to_many", "many_to_one", "many_to_many"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Relation {
+    pub from: String,
+    pub to: String,
+    pub cardinality: Cardinality,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+}
+
+/// DDL の種類
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+/**
+* er_chart の JSON Schema。collection の schema に使う。
+*
+* @return
+*/
+pub fn chart_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "description": { "type": "string" },
+                        "columns": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "type": { "type": "string", "enum": COLUMN_TYPES },
+                                    "primary_key": { "type": "boolean" },
+                                    "not_null": { "type": "boolean" },
+                                    "unique": { "type": "boolean" },
+                                    "references": { "type": "string" }
+                                },
+                                "required": ["name", "type"],
+                                "additionalProperties": false
+                            }
+                        }
+                    },
+                    "required": ["name", "columns"],
+                    "additionalProperties": false
+                }
+            },
+            "relations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "from": { "type": "string" },
+                        "to": { "type": "string" },
+                        "cardinality": { "type": "string", "enum": CARDINALITIES },
+                        "label": { "type": "string" }
+                    },
+                    "required": ["from", "to", "cardinality"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["entities"],
+        "additionalProperties": false
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/**
+* ER 図を検証する。名前の重複、references , relations の参照先を確認する。
+*
+* @param chart
+*
+* @return
+*/
+pub fn validate_chart(chart: &ErChart) -> Result<(), String> {
+    if chart.entities.is_empty() {
+        return Err("entities is empty".to_string());
+    }
+    let mut entity_names = HashSet::new();
+    for entity in &chart.entities {
+        if !is_identifier(&entity.name) {
+            return Err(format!("invalid entity name: {}", entity.name));
+        }
+        if !entity_names.insert(entity.name.to_lowercase()) {
+            return Err(format!("duplicate entity: {}", entity.name));
+        }
+        if entity.columns.is_empty() {
+            return Err(format!("{}: columns is empty", entity.name));
+        }
+        let mut column_names = HashSet::new();
+        for column in &entity.columns {
+            if !is_identifier(&column.name) {
+                return Err(format!("{}: invalid column name: {}", entity.name, column.name));
+            }
+            if !column_names.insert(column.name.to_lowercase()) {
+                return Err(format!("{}: duplicate column: {}", entity.name, column.name));
+            }
+        }
+    }
+    for entity in &chart.entities {
+        for column in &entity.columns {
+            if let Some(references) = &column.references {
+                let target = references.split_once('.')
+                    .and_then(|(table, name)| find_column(chart, table, name));
+                if target.is_none() {
+                    return Err(format!("{}.{}: unknown reference {} (entity.column)", entity.name, column.name, references));
+                }
+            }
+        }
+    }
+    for relation in &chart.relations {
+        for name in [&relation.from, &relation.to] {
+            if find_entity(chart, name).is_none() {
+                return Err(format!("relation {} -> {}: unknown entity {}", relation.from, relation.to, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+* er_chart の data を検証する。collection の検証 (JSON Schema の後) に使う。
+*
+* @param value
+*
+* @return
+*/
+pub fn check_chart_value(value: &Value) -> Result<(), String> {
+    let chart: ErChart = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+    validate_chart(&chart)
+}
+
+fn find_entity<'a>(chart: &'a ErChart, name: &str) -> Option<&'a Entity> {
+    chart.entities.iter().find(|entity| entity.name.eq_ignore_ascii_case(name))
+}
+
+fn find_column<'a>(chart: &'a ErChart, table: &str, name: &str) -> Option<&'a Column> {
+    find_entity(chart, table)?.columns.iter().find(|column| column.name.eq_ignore_ascii_case(name))
+}
+
+/// references から作る relation (from の列が to を参照する)
+fn reference_relations(chart: &ErChart) -> Vec<Relation> {
+    let mut relations = Vec::new();
+    for entity in &chart.entities {
+        for column in &entity.columns {
+            let Some((table, _)) = column.references.as_deref().and_then(|references| references.split_once('.')) else {
+                continue;
+            };
+            let Some(target) = find_entity(chart, table) else {
+                continue;
+            };
+            let single_key = column.primary_key && entity.columns.iter().filter(|column| column.primary_key).count() == 1;
+            relations.push(Relation {
+                from: entity.name.clone(),
+                to: target.name.clone(),
+                cardinality: if column.unique || single_key { Cardinality::OneToOne } else { Cardinality::ManyToOne },
+                label: column.name.clone(),
+            });
+        }
+    }
+    relations
+}
+
+/**
+* Mermaid の erDiagram にする。relations に無い references も relation として表示する。
+*
+* @param chart
+*
+* @return
+*/
+pub fn to_mermaid(chart: &ErChart) -> String {
+    let mut lines = vec!["erDiagram".to_string()];
+    for entity in &chart.entities {
+        lines.push(format!("    {} {{", entity.name));
+        for column in &entity.columns {
+            let mut keys = Vec::new();
+            if column.primary_key {
+                keys.push("PK");
+            }
+            if column.references.is_some() {
+                keys.push("FK");
+            }
+            if column.unique && !column.primary_key {
+                keys.push("UK");
+            }
+            let type_name = COLUMN_TYPES[column.column_type as usize];
+            let keys = if keys.is_empty() { String::new() } else { format!(" {}", keys.join(",")) };
+            lines.push(format!("        {} {}{}", type_name, column.name, keys));
+        }
+        lines.push("    }".to_string());
+    }
+    let mut relations = chart.relations.clone();
+    for relation in reference_relations(chart) {
+        let exists = relations.iter().any(|other| {
+            (other.from.eq_ignore_ascii_case(&relation.from) && other.to.eq_ignore_ascii_case(&relation.to))
+                || (other.from.eq_ignore_ascii_case(&relation.to) && other.to.eq_ignore_ascii_case(&relation.from))
+        });
+        if !exists {
+            relations.push(relation);
+        }
+    }
+    for relation in &relations {
+        let symbol = match relation.cardinality {
+            Cardinality::OneToOne => "||--||",
+            Cardinality::OneToMany => "||--o{",
+            Cardinality::ManyToOne => "}o--||",
+            Cardinality::ManyToMany => "}o--o{",
+        };
+        lines.push(format!("    {} {} {} : \"{}\"", relation.from, symbol, relation.to, relation.label.replace('"', "'")));
+    }
+    lines.join("\n")
+}
+
+fn sql_type(column_type: ColumnType, dialect: Dialect) -> &'static str {
+    match (column_type, dialect) {
+        (ColumnType::Integer, _) => "INTEGER",
+        (ColumnType::Text, _) => "TEXT",
+        (ColumnType::Real, Dialect::Sqlite) => "REAL",
+        (ColumnType::Real, Dialect::Postgres) => "DOUBLE PRECISION",
+        (ColumnType::Boolean, Dialect::Sqlite) => "INTEGER",
+        (ColumnType::Boolean, Dialect::Postgres) => "BOOLEAN",
+        (ColumnType::Timestamp, _) => "TIMESTAMP",
+        (ColumnType::Date, _) => "DATE",
+        (ColumnType::Json, Dialect::Sqlite) => "TEXT",
+        (ColumnType::Json, Dialect::Postgres) => "JSONB",
+        (ColumnType::Blob, Dialect::Sqlite) => "BLOB",
+        (ColumnType::Blob, Dialect::Postgres) => "BYTEA",
+    }
+}
+
+// 識別子として使う場合に引用符が必要な予約語
+const RESERVED_WORDS: [&str; 14] = [
+    "user", "order", "group", "table", "select", "from", "where", "index", "key", "references", "check", "default", "limit", "offset",
+];
+
+fn quote_identifier(name: &str) -> String {
+    if RESERVED_WORDS.contains(&name.to_lowercase().as_str()) {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/**
+* 参照先の entity が先になるように並べる。循環している場合は元の順とする。
+*/
+fn ordered_entities(chart: &ErChart) -> Vec<&Entity> {
+    let mut ordered: Vec<&Entity> = Vec::new();
+    let mut rest: Vec<&Entity> = chart.entities.iter().collect();
+    while !rest.is_empty() {
+        let ready = rest.iter().position(|entity| {
+            entity.columns.iter().all(|column| match column.references.as_deref().and_then(|references| references.split_once('.')) {
+                Some((table, _)) => table.eq_ignore_ascii_case(&entity.name)
+                    || ordered.iter().any(|done| done.name.eq_ignore_ascii_case(table))
+                    || find_entity(chart, table).is_none(),
+                None => true,
+            })
+        }).unwrap_or(0);
+        ordered.push(rest.remove(ready));
+    }
+    ordered
+}
+
+/**
+* CREATE TABLE の DDL にする。
+*
+* @param chart
+* @param dialect
+*
+* @return
+*/
+pub fn to_ddl(chart: &ErChart, dialect: Dialect) -> String {
+    let mut statements = Vec::new();
+    for entity in ordered_entities(chart) {
+        let primary_keys: Vec<&Column> = entity.columns.iter().filter(|column| column.primary_key).collect();
+        let mut definitions = Vec::new();
+        for column in &entity.columns {
+            let mut definition = format!("  {} {}", quote_identifier(&column.name), sql_type(column.column_type, dialect));
+            if column.primary_key && primary_keys.len() == 1 {
+                definition.push_str(" PRIMARY KEY");
+            } else if column.not_null || column.primary_key {
+                definition.push_str(" NOT NULL");
+            }
+            if column.unique && !column.primary_key {
+                definition.push_str(" UNIQUE");
+            }
+            if let Some((table, name)) = column.references.as_deref().and_then(|references| references.split_once('.')) {
+                definition.push_str(&format!(" REFERENCES {} ({})", quote_identifier(table), quote_identifier(name)));
+            }
+            definitions.push(definition);
+        }
+        if primary_keys.len() > 1 {
+            let names: Vec<String> = primary_keys.iter().map(|column| quote_identifier(&column.name)).collect();
+            definitions.push(format!("  PRIMARY KEY ({})", names.join(", ")));
+        }
+        statements.push(format!("CREATE TABLE {} (\n{}\n);", quote_identifier(&entity.name), definitions.join(",\n")));
+    }
+    statements.join("\n\n")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(char),
+    Literal,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn name(&self) -> Option<String> {
+        match self {
+            Token::Word(word) | Token::Quoted(word) => Some(word.clone()),
+            _ => None,
+        }
+    }
+}
+
+/**
+* DDL を token に分ける。コメント、文字列、数値は Literal とする。
+*
+* @param sql
+*
+* @return
+*/
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let take_until = |start: usize, end: char| -> Result<(String, usize), String> {
+        let close = chars[start..].iter().position(|c| *c == end).ok_or(format!("unclosed {}", end))?;
+        Ok((chars[start..start + close].iter().collect(), start + close + 1))
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            i = chars[i..].iter().position(|c| *c == '\n').map(|n| i + n + 1).unwrap_or(chars.len());
+        } else if c == '/' && next == Some('*') {
+            let close = chars[i + 2..].windows(2).position(|pair| pair == ['*', '/']).ok_or("unclosed comment".to_string())?;
+            i += close + 4;
+        } else if c == '\'' {
+            let (_, end) = take_until(i + 1, '\'')?;
+            tokens.push(Token::Literal);
+            i = end;
+        } else if c == '"' || c == '`' || c == '[' {
+            let close = if c == '[' { ']' } else { c };
+            let (name, end) = take_until(i + 1, close)?;
+            tokens.push(Token::Quoted(name));
+            i = end;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Literal);
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/**
+* 括弧の内側を、深さ 0 の , で分ける。tokens[start] は ( とする。
+*
+* @return (定義の一覧 , ) の次の位置)
+*/
+fn split_definitions(tokens: &[Token], start: usize) -> Result<(Vec<Vec<Token>>, usize), String> {
+    let mut definitions = vec![Vec::new()];
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Symbol('(') => {
+                depth += 1;
+                if depth == 1 {
+                    continue;
+                }
+            }
+            Token::Symbol(')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((definitions.into_iter().filter(|definition| !definition.is_empty()).collect(), index + 1));
+                }
+            }
+            Token::Symbol(',') if depth == 1 => {
+                definitions.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        definitions.last_mut().unwrap().push(token.clone());
+    }
+    Err("unclosed (".to_string())
+}
+
+/// ( a , b ) の名前の一覧
+fn name_list(tokens: &[Token]) -> Vec<String> {
+    let start = tokens.iter().position(|token| *token == Token::Symbol('(')).unwrap_or(tokens.len());
+    tokens[start..].iter()
+        .take_while(|token| **token != Token::Symbol(')'))
+        .filter_map(|token| token.name())
+        .collect()
+}
+
+fn column_type(type_name: &str) -> ColumnType {
+    let type_name = type_name.to_lowercase();
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|prefix| type_name.starts_with(prefix));
+    if starts(&["bool"]) {
+        ColumnType::Boolean
+    } else if starts(&["int", "bigint", "smallint", "tinyint", "serial", "bigserial", "smallserial"]) {
+        ColumnType::Integer
+    } else if starts(&["real", "float", "double", "numeric", "decimal", "money"]) {
+        ColumnType::Real
+    } else if starts(&["timestamp", "datetime"]) {
+        ColumnType::Timestamp
+    } else if type_name == "date" {
+        ColumnType::Date
+    } else if starts(&["json"]) {
+        ColumnType::Json
+    } else if starts(&["blob", "bytea", "binary", "varbinary"]) {
+        ColumnType::Blob
+    } else {
+        ColumnType::Text
+    }
+}
+
+/// 参照先の (table , column)。column を省略した場合は None
+type Reference = (String, Option<String>);
+
+/// REFERENCES table ( column ) の参照先
+fn parse_references(tokens: &[Token]) -> Option<Reference> {
+    let index = tokens.iter().position(|token| token.is_keyword("REFERENCES"))?;
+    let mut rest = &tokens[index + 1..];
+    let mut table = rest.first()?.name()?;
+    // schema.table
+    while let [Token::Symbol('.'), next, ..] = rest.get(1..).unwrap_or(&[]) {
+        table = next.name()?;
+        rest = &rest[2..];
+    }
+    let column = match rest.get(1) {
+        Some(Token::Symbol('(')) => name_list(&rest[1..]).into_iter().next(),
+        _ => None,
+    };
+    Some((table, column))
+}
+
+// 列の定義で、型の後に来る制約の keyword
+const CONSTRAINT_WORDS: [&str; 11] = [
+    "CONSTRAINT", "PRIMARY", "NOT", "NULL", "UNIQUE", "REFERENCES", "DEFAULT", "CHECK", "COLLATE", "GENERATED", "AUTOINCREMENT",
+];
+
+fn parse_column(definition: &[Token]) -> Result<(Column, Option<Reference>), String> {
+    let name = definition.first().and_then(|token| token.name()).ok_or("column name not found".to_string())?;
+    let type_name = match definition.get(1) {
+        Some(Token::Word(word)) if !CONSTRAINT_WORDS.iter().any(|keyword| word.eq_ignore_ascii_case(keyword)) => word.clone(),
+        _ => String::new(),
+    };
+    let has = |first: &str, second: &str| definition.windows(2).any(|pair| pair[0].is_keyword(first) && pair[1].is_keyword(second));
+    let column = Column {
+        name,
+        column_type: column_type(&type_name),
+        primary_key: has("PRIMARY", "KEY"),
+        not_null: has("NOT", "NULL"),
+        unique: definition.iter().any(|token| token.is_keyword("UNIQUE")),
+        references: None,
+    };
+    Ok((column, parse_references(definition)))
+}
+
+/**
+* CREATE TABLE の DDL を ER 図にする。CREATE TABLE 以外の文は読み飛ばす。
+* REFERENCES は references と、relation (many_to_one , 一意の列は one_to_one) にする。
+*
+* @param sql
+*
+* @return
+*/
+pub fn parse_ddl(sql: &str) -> Result<ErChart, String> {
+    let tokens = tokenize(sql)?;
+    let mut chart = ErChart::default();
+    // (entity , column , 参照先 table , 参照先 column)
+    let mut references: Vec<(String, String, String, Option<String>)> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if !tokens[i].is_keyword("CREATE") {
+            i += 1;
+            continue;
+        }
+        let mut j = i + 1;
+        while tokens.get(j).is_some_and(|token| token.is_keyword("TEMP") || token.is_keyword("TEMPORARY") || token.is_keyword("UNLOGGED")) {
+            j += 1;
+        }
+        if !tokens.get(j).is_some_and(|token| token.is_keyword("TABLE")) {
+            i += 1;
+            continue;
+        }
+        j += 1;
+        if tokens.get(j).is_some_and(|token| token.is_keyword("IF")) {
+            j += 3;
+        }
+        let mut table = tokens.get(j).and_then(|token| token.name()).ok_or("table name not found".to_string())?;
+        j += 1;
+        while tokens.get(j) == Some(&Token::Symbol('.')) {
+            table = tokens.get(j + 1).and_then(|token| token.name()).ok_or("table name not found".to_string())?;
+            j += 2;
+        }
+        if tokens.get(j) != Some(&Token::Symbol('(')) {
+            return Err(format!("{}: ( not found", table));
+        }
+        let (definitions, end) = split_definitions(&tokens, j)?;
+        let mut entity = Entity {
+            name: table.clone(),
+            ..Default::default()
+        };
+        let mut table_constraints = Vec::new();
+        for definition in definitions {
+            let mut head = definition.as_slice();
+            if head.first().is_some_and(|token| token.is_keyword("CONSTRAINT")) {
+                head = head.get(2..).unwrap_or(&[]);
+            }
+            match head.first() {
+                Some(token) if ["PRIMARY", "FOREIGN", "UNIQUE", "CHECK", "EXCLUDE"].iter().any(|keyword| token.is_keyword(keyword)) => {
+                    table_constraints.push(head.to_vec());
+                }
+                _ => {
+                    let (column, reference) = parse_column(&definition)?;
+                    if let Some((to_table, to_column)) = reference {
+                        references.push((table.clone(), column.name.clone(), to_table, to_column));
+                    }
+                    entity.columns.push(column);
+                }
+            }
+        }
+        for constraint in table_constraints {
+            let names = name_list(&constraint);
+            let find = |entity: &mut Entity, name: &str| entity.columns.iter_mut().position(|column| column.name.eq_ignore_ascii_case(name));
+            if constraint[0].is_keyword("PRIMARY") {
+                for name in &names {
+                    if let Some(index) = find(&mut entity, name) {
+                        entity.columns[index].primary_key = true;
+                    }
+                }
+            } else if constraint[0].is_keyword("UNIQUE") && names.len() == 1 {
+                if let Some(index) = find(&mut entity, &names[0]) {
+                    entity.columns[index].unique = true;
+                }
+            } else if constraint[0].is_keyword("FOREIGN") && names.len() == 1
+                && let Some((to_table, to_column)) = parse_references(&constraint) {
+                references.push((table.clone(), names[0].clone(), to_table, to_column));
+            }
+        }
+        chart.entities.push(entity);
+        i = end;
+    }
+    if chart.entities.is_empty() {
+        return Err("CREATE TABLE not found".to_string());
+    }
+    for (table, name, to_table, to_column) in references {
+        // 参照先の列が無い場合は、参照先の主キーとする
+        let to_column = match to_column {
+            Some(to_column) => to_column,
+            None => find_entity(&chart, &to_table)
+                .and_then(|entity| entity.columns.iter().find(|column| column.primary_key))
+                .map(|column| column.name.clone())
+                .ok_or(format!("{}.{}: primary key of {} not found", table, name, to_table))?,
+        };
+        let to_entity = find_entity(&chart, &to_table).map(|entity| entity.name.clone()).unwrap_or(to_table);
+        if let Some(column) = chart.entities.iter_mut()
+            .find(|entity| entity.name.eq_ignore_ascii_case(&table))
+            .and_then(|entity| entity.columns.iter_mut().find(|column| column.name.eq_ignore_ascii_case(&name))) {
+            column.references = Some(format!("{}.{}", to_entity, to_column));
+        }
+    }
+    chart.relations = reference_relations(&chart);
+    validate_chart(&chart)?;
+    Ok(chart)
+}
+
+/**
+* er_render: er_chart の id のデータを、Mermaid の erDiagram , SQLite / Postgres の DDL にする。
+*
+* @param
+*
+* @return
+*/
+pub async fn er_render_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum RenderFormat {
+        Mermaid,
+        Sqlite,
+        Postgres,
+    }
+    #[derive(Debug, Deserialize)]
+    struct RenderParams {
+        id: i32,
+        format: RenderFormat,
+    }
+    let render_params = match parse_arguments::<RenderParams>(&params, &request_id) {
+        Ok(render_params) => render_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let mut rows = conn.query("SELECT data FROM er_chart WHERE id = ?1", params![render_params.id]).await.unwrap();
+    let Some(row) = rows.next().await.unwrap() else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", render_params.id));
+    };
+    let data: String = row.get(0).unwrap();
+    // ER 図の形式になる前に登録したデータは、表示できない
+    let chart = match serde_json::from_str::<ErChart>(&data).map_err(|e| e.to_string()).and_then(|chart| validate_chart(&chart).map(|_| chart)) {
+        Ok(chart) => chart,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, id={} is not an ER chart: {}", render_params.id, e)),
+    };
+    let text = match render_params.format {
+        RenderFormat::Mermaid => to_mermaid(&chart),
+        RenderFormat::Sqlite => to_ddl(&chart, Dialect::Sqlite),
+        RenderFormat::Postgres => to_ddl(&chart, Dialect::Postgres),
+    };
+    text_response(request_id, text)
+}
+
+/**
+* er_import: CREATE TABLE の DDL を ER 図にして、er_chart に登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn er_import_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct ImportParams {
+        ddl: String,
+        #[serde(default)]
+        name: String,
+    }
+    let import_params = match parse_arguments::<ImportParams>(&params, &request_id) {
+        Ok(import_params) => import_params,
+        Err(response) => return response,
+    };
+    let mut chart = match parse_ddl(&import_params.ddl) {
+        Ok(chart) => chart,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, ddl: {}", e)),
+    };
+    chart.name = import_params.name.clone();
+    let data = serde_json::to_string(&chart).expect("JSON convert error");
+
+    let conn = connect().await;
+    super::mod_history::ensure_history(&conn).await.unwrap();
+    let tx = conn.transaction().await.unwrap();
+    let row = tx.query("INSERT INTO er_chart (data) VALUES (?1) RETURNING id", params![data.clone()])
+        .await.unwrap()
+        .next().await.unwrap().unwrap();
+    let id: i64 = row.get(0).unwrap();
+    super::mod_history::record_history(&tx, "er_chart", id, super::mod_history::HistoryAction::Create, None, Some(&data)).await.unwrap();
+    tx.commit().await.unwrap();
+    super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataCreate, "er_chart", id, Some(&data)).await;
+    text_response(request_id, format!("Complete import, id={} , entities={} , relations={}\n{}",
+        id, chart.entities.len(), chart.relations.len(), to_mermaid(&chart)))
+}
@@ -0,0 +1,304 @@
+use libsql::Connection;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::{connect, error_response, parse_arguments, text_response};
+
+// data_* の変更履歴の table
+pub const HISTORY_TABLE: &str = "mcp_data_history";
+
+// 履歴の table を作成済みか (プロセスごとに1回だけ作成する)
+static HISTORY_READY: AtomicBool = AtomicBool::new(false);
+
+/// 変更の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryAction {
+    Create,
+    Update,
+    Delete,
+    Revert,
+}
+
+impl HistoryAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HistoryAction::Create => "create",
+            HistoryAction::Update => "update",
+            HistoryAction::Delete => "delete",
+            HistoryAction::Revert => "revert",
+        }
+    }
+}
+
+/// 履歴の1件。old_data は変更前 , new_data は変更後 (削除の場合は null)
+#[derive(Debug, Serialize)]
+struct HistoryEntry {
+    version: i64,
+    action: String,
+    old_data: Option<String>,
+    new_data: Option<String>,
+    created_at: String,
+}
+
+/**
+* 履歴の table が無い場合は作成する。transaction の取り消しで table が無くならないよう、
+* data を変更する transaction の前に呼び出す。
+*
+* @param conn
+*
+* @return
+*/
+pub async fn ensure_history(conn: &Connection) -> Result<(), String> {
+    if HISTORY_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let sql_list = [
+        format!("CREATE TABLE IF NOT EXISTS {} (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          collection TEXT NOT NULL,
+          record_id INTEGER NOT NULL,
+          version INTEGER NOT NULL,
+          action TEXT NOT NULL,
+          old_data TEXT,
+          new_data TEXT,
+          record_created_at TIMESTAMP,
+          created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )", HISTORY_TABLE),
+        format!("CREATE UNIQUE INDEX IF NOT EXISTS idx_{table}_record ON {table} (collection, record_id, version)", table = HISTORY_TABLE),
+    ];
+    for sql in &sql_list {
+        conn.execute(sql, ()).await.map_err(|e| e.to_string())?;
+    }
+    // record_created_at の無い、以前に作成した table には列を追加する
+    let mut rows = conn.query("SELECT 1 FROM pragma_table_info(?1) WHERE name = 'record_created_at'", params![HISTORY_TABLE])
+        .await.map_err(|e| e.to_string())?;
+    if rows.next().await.map_err(|e| e.to_string())?.is_none() {
+        let sql = format!("ALTER TABLE {} ADD COLUMN record_created_at TIMESTAMP", HISTORY_TABLE);
+        conn.execute(&sql, ()).await.map_err(|e| e.to_string())?;
+    }
+    HISTORY_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/**
+* 変更を履歴に追加する。version は collection , id ごとに 1 から順に付ける。
+* data の変更と同じ transaction で呼び出す。事前に ensure_history を呼び出しておく。
+* 削除は record_delete_history を使う。
+*
+* @param conn
+* @param collection
+* @param record_id
+* @param action
+* @param old_data
+* @param new_data
+*
+* @return
+*/
+pub async fn record_history(conn: &Connection, collection: &str, record_id: i64, action: HistoryAction, old_data: Option<&str>, new_data: Option<&str>) -> Result<(), String> {
+    insert_history(conn, collection, record_id, action, old_data, new_data, None).await
+}
+
+/**
+* 削除を履歴に追加する。復元の時に使うため、削除したデータの登録日時も記録する。
+*
+* @param conn
+* @param collection
+* @param record_id
+* @param old_data 削除したデータ
+* @param record_created_at 削除したデータの created_at
+*
+* @return
+*/
+pub async fn record_delete_history(conn: &Connection, collection: &str, record_id: i64, old_data: &str, record_created_at: Option<&str>) -> Result<(), String> {
+    insert_history(conn, collection, record_id, HistoryAction::Delete, Some(old_data), None, record_created_at).await
+}
+
+async fn insert_history(conn: &Connection, collection: &str, record_id: i64, action: HistoryAction, old_data: Option<&str>, new_data: Option<&str>, record_created_at: Option<&str>) -> Result<(), String> {
+    let sql = format!("INSERT INTO {table} (collection, record_id, version, action, old_data, new_data, record_created_at)
+    SELECT ?1, ?2, coalesce(max(version), 0) + 1, ?3, ?4, ?5, ?6
+    FROM {table} WHERE collection = ?1 AND record_id = ?2
+    ", table = HISTORY_TABLE);
+    conn.execute(&sql, params![collection, record_id, action.as_str(), old_data, new_data, record_created_at])
+        .await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/**
+* collection の履歴をすべて削除する。collection_drop で使う。事前に ensure_history を呼び出しておく。
+*
+* @param conn
+* @param collection
+*
+* @return
+*/
+pub async fn delete_history(conn: &Connection, collection: &str) -> Result<(), String> {
+    let sql = format!("DELETE FROM {} WHERE collection = ?1", HISTORY_TABLE);
+    conn.execute(&sql, params![collection]).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn list_history(conn: &Connection, collection: &str, record_id: i64, limit: i64) -> Result<Vec<HistoryEntry>, String> {
+    ensure_history(conn).await?;
+    let sql = format!("SELECT version, action, old_data, new_data, created_at FROM {}
+    WHERE collection = ?1 AND record_id = ?2
+    ORDER BY version DESC LIMIT ?3
+    ", HISTORY_TABLE);
+    let mut rows = conn.query(&sql, params![collection, record_id, limit]).await.map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        entries.push(HistoryEntry {
+            version: row.get(0).map_err(|e| e.to_string())?,
+            action: row.get(1).map_err(|e| e.to_string())?,
+            old_data: row.get(2).map_err(|e| e.to_string())?,
+            new_data: row.get(3).map_err(|e| e.to_string())?,
+            created_at: row.get(4).map_err(|e| e.to_string())?,
+        });
+    }
+    Ok(entries)
+}
+
+/**
+* data_history: collection の id のデータの変更履歴を、新しい順に表示する。削除したデータも表示できる。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_history_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct HistoryParams {
+        content: String,
+        id: i64,
+        #[serde(default = "default_limit")]
+        limit: i64,
+    }
+    fn default_limit() -> i64 {
+        20
+    }
+    let history_params = match parse_arguments::<HistoryParams>(&params, &request_id) {
+        Ok(history_params) => history_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &history_params.content).await {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    match list_history(&conn, &collection.name, history_params.id, history_params.limit.clamp(1, 100)).await {
+        Ok(entries) => text_response(request_id, serde_json::to_string(&entries).expect("JSON convert error")),
+        Err(e) => error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+}
+
+/**
+* 戻す先の data を決める。version を指定した場合はその version の変更後、
+* 省略した場合は最後の変更の変更前 (削除の場合は削除前のデータ) とする。
+*/
+async fn revert_target(conn: &Connection, collection: &str, record_id: i64, version: Option<i64>) -> Result<(i64, String), String> {
+    let sql = format!("SELECT version, old_data, new_data FROM {}
+    WHERE collection = ?1 AND record_id = ?2 AND (?3 IS NULL OR version = ?3)
+    ORDER BY version DESC LIMIT 1
+    ", HISTORY_TABLE);
+    let mut rows = conn.query(&sql, params![collection, record_id, version]).await.map_err(|e| e.to_string())?;
+    let row = rows.next().await.map_err(|e| e.to_string())?
+        .ok_or(format!("no history: id={}{}", record_id, version.map(|version| format!(" , version={}", version)).unwrap_or_default()))?;
+    let found: i64 = row.get(0).map_err(|e| e.to_string())?;
+    let old_data: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+    let new_data: Option<String> = row.get(2).map_err(|e| e.to_string())?;
+    let target = if version.is_some() { new_data } else { old_data };
+    match target {
+        Some(data) => Ok((found, data)),
+        None if version.is_some() => Err(format!("version {} is a delete. Specify an earlier version", found)),
+        None => Err(format!("version {} is the first version. Nothing to revert", found)),
+    }
+}
+
+/**
+* data_revert: collection の id のデータを、履歴の version のデータに戻す。削除したデータは復元する。
+* version を省略した場合は、最後の変更を取り消す。
+*
+* @param
+*
+* @return
+*/
+pub async fn data_revert_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RevertParams {
+        content: String,
+        id: i64,
+        version: Option<i64>,
+        etag: Option<String>,
+    }
+    let revert_params = match parse_arguments::<RevertParams>(&params, &request_id) {
+        Ok(revert_params) => revert_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let collection = match super::mod_collection::find_collection(&conn, &revert_params.content).await {
+        Ok(collection) => collection,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    if let Err(e) = ensure_history(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    let record_id = revert_params.id;
+    let tx = conn.transaction().await.unwrap();
+    let (version, data) = match revert_target(&tx, &collection.name, record_id, revert_params.version).await {
+        Ok(target) => target,
+        Err(e) => {
+            tx.rollback().await.ok();
+            return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+        }
+    };
+    if let Err(e) = super::mod_collection::validate_data(&collection, &data) {
+        tx.rollback().await.ok();
+        return error_response(request_id, -32602, format!("Invalid parameters, version {}: {}", version, e));
+    }
+
+    let select_sql = format!("SELECT data, updated_at FROM {} WHERE id = ?1", collection.name);
+    let current = tx.query(&select_sql, params![record_id]).await.unwrap().next().await.unwrap();
+    let existed = current.is_some();
+    let result = match current {
+        Some(row) => {
+            let current_data: String = row.get(0).unwrap();
+            let updated_at: String = row.get(1).unwrap();
+            if let Some(etag) = &revert_params.etag
+                && *etag != super::mod_data::item_etag(&current_data, &updated_at) {
+                tx.rollback().await.ok();
+                return error_response(request_id, -32009, format!("Conflict, etag={}", etag));
+            }
+            let sql = format!("UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2", collection.name);
+            match tx.execute(&sql, params![data.clone(), record_id]).await {
+                Ok(_) => record_history(&tx, &collection.name, record_id, HistoryAction::Revert, Some(&current_data), Some(&data)).await,
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        None => {
+            // 削除したデータは、同じ id と元の登録日時で復元する
+            // (最後の削除で記録した登録日時、無い場合は登録の履歴の日時)
+            let sql = format!("INSERT INTO {table} (id, data, created_at)
+            VALUES (?1, ?2, coalesce(
+                (SELECT record_created_at FROM {history} WHERE collection = ?3 AND record_id = ?1 AND action = 'delete'
+                    AND record_created_at IS NOT NULL ORDER BY version DESC LIMIT 1),
+                (SELECT min(created_at) FROM {history} WHERE collection = ?3 AND record_id = ?1 AND action = 'create'),
+                CURRENT_TIMESTAMP))
+            ", table = collection.name, history = HISTORY_TABLE);
+            match tx.execute(&sql, params![record_id, data.clone(), collection.name.clone()]).await {
+                Ok(_) => record_history(&tx, &collection.name, record_id, HistoryAction::Revert, None, Some(&data)).await,
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    };
+    if let Err(e) = result {
+        tx.rollback().await.ok();
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    tx.commit().await.unwrap();
+    // 削除したデータの復元は登録とする
+    let event = if existed { super::mod_webhook::WebhookEvent::DataUpdate } else { super::mod_webhook::WebhookEvent::DataCreate };
+    super::mod_webhook::emit(&conn, event, &collection.name, record_id, Some(&data)).await;
+    text_response(request_id, format!("Complete revert, id={} , version={}", record_id, version))
+}
@@ -0,0 +1,309 @@
+use libsql::params;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use super::{connect, error_response, parse_arguments, text_response};
+
+// Markdown の collection
+const MD_TABLE: &str = "md_data";
+
+/// 見出し。start から end までが見出しの section (下位の見出しを含む)
+#[derive(Debug, Clone)]
+struct Heading {
+    level: usize,
+    title: String,
+    line: usize,
+    start: usize,
+    end: usize,
+    path: Vec<String>,
+}
+
+/// md_outline の見出しの木
+#[derive(Debug, Serialize)]
+struct OutlineNode {
+    level: usize,
+    title: String,
+    line: usize,
+    children: Vec<OutlineNode>,
+}
+
+fn parser_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES
+}
+
+/**
+* Markdown の見出しの一覧。引用 , リストの中の見出しは section の区切りにしない。
+* path は上位の見出しから自分までの見出し名。
+*
+* @param text
+*
+* @return
+*/
+fn headings(text: &str) -> Vec<Heading> {
+    let mut list: Vec<Heading> = Vec::new();
+    let mut depth = 0;
+    let mut current: Option<Heading> = None;
+    for (event, range) in Parser::new_ext(text, parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::BlockQuote(_) | Tag::List(_)) => depth += 1,
+            Event::End(TagEnd::BlockQuote(_) | TagEnd::List(_)) => depth -= 1,
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                current = Some(Heading {
+                    level: level as usize,
+                    title: String::new(),
+                    line: text[..range.start].matches('\n').count() + 1,
+                    start: range.start,
+                    end: text.len(),
+                    path: Vec::new(),
+                });
+            }
+            Event::Text(value) | Event::Code(value) => {
+                if let Some(heading) = current.as_mut() {
+                    heading.title.push_str(&value);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(mut heading) = current.take() {
+                    heading.title = heading.title.trim().to_string();
+                    list.push(heading);
+                }
+            }
+            _ => {}
+        }
+    }
+    // section の終わりは、同じか上位の次の見出しの前
+    for index in 0..list.len() {
+        let level = list[index].level;
+        if let Some(next) = list[index + 1..].iter().find(|next| next.level <= level) {
+            list[index].end = next.start;
+        }
+        let mut path: Vec<String> = list[..index].iter()
+            .filter(|parent| parent.level < level && parent.end > list[index].start)
+            .map(|parent| parent.title.clone())
+            .collect();
+        path.push(list[index].title.clone());
+        list[index].path = path;
+    }
+    list
+}
+
+fn outline(headings: &[Heading]) -> Vec<OutlineNode> {
+    let mut nodes = Vec::new();
+    let mut index = 0;
+    while index < headings.len() {
+        let heading = &headings[index];
+        let children_end = headings[index + 1..].iter()
+            .position(|next| next.level <= heading.level)
+            .map(|position| index + 1 + position)
+            .unwrap_or(headings.len());
+        nodes.push(OutlineNode {
+            level: heading.level,
+            title: heading.title.clone(),
+            line: heading.line,
+            children: outline(&headings[index + 1..children_end]),
+        });
+        index = children_end;
+    }
+    nodes
+}
+
+/**
+* 見出し名のパスで section を探す。パスは上位の見出しを省略できる (末尾が一致する見出し)。
+* 複数の見出しが一致する場合はエラーとする。
+*
+* @param headings
+* @param path
+*
+* @return
+*/
+fn find_section<'a>(headings: &'a [Heading], path: &[String]) -> Result<&'a Heading, String> {
+    let path: Vec<&str> = path.iter().map(|title| title.trim()).collect();
+    if path.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let found: Vec<&Heading> = headings.iter()
+        .filter(|heading| heading.path.len() >= path.len() && heading.path[heading.path.len() - path.len()..] == path[..])
+        .collect();
+    match found.as_slice() {
+        [heading] => Ok(heading),
+        [] => Err(format!("heading not found: {}", path.join(" > "))),
+        _ => {
+            let candidates: Vec<String> = found.iter().map(|heading| heading.path.join(" > ")).collect();
+            Err(format!("ambiguous heading path: {} ({})", path.join(" > "), candidates.join(" , ")))
+        }
+    }
+}
+
+/**
+* section を content に置き換える。content は見出しの行を含む section 全体。
+*
+* @param text
+* @param heading
+* @param content
+*
+* @return
+*/
+fn replace_section(text: &str, heading: &Heading, content: &str) -> String {
+    let mut section = content.to_string();
+    if !section.ends_with('\n') {
+        section.push('\n');
+    }
+    // 次の見出しと続かないよう、空行を入れる
+    if heading.end < text.len() && !section.ends_with("\n\n") {
+        section.push('\n');
+    }
+    format!("{}{}{}", &text[..heading.start], section, &text[heading.end..])
+}
+
+/**
+* Markdown を HTML にする。script などは取り除く。
+*
+* @param text
+*
+* @return
+*/
+fn render_html(text: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(text, parser_options()));
+    ammonia::clean(&html)
+}
+
+#[derive(Debug, Deserialize)]
+struct DocumentParams {
+    id: i32,
+}
+
+/**
+* md_outline: md_data の id の見出しを木にして表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn md_outline_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let document_params = match parse_arguments::<DocumentParams>(&params, &request_id) {
+        Ok(document_params) => document_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let Some(item) = super::mod_data::find_item(&conn, MD_TABLE, document_params.id).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", document_params.id));
+    };
+    let result = json!({
+        "id": item.id,
+        "etag": item.etag,
+        "outline": outline(&headings(&item.data)),
+    });
+    text_response(request_id, result.to_string())
+}
+
+/**
+* md_render: md_data の id を HTML にする。HTML は script , イベント属性などを取り除く。
+*
+* @param
+*
+* @return
+*/
+pub async fn md_render_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let document_params = match parse_arguments::<DocumentParams>(&params, &request_id) {
+        Ok(document_params) => document_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let Some(item) = super::mod_data::find_item(&conn, MD_TABLE, document_params.id).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", document_params.id));
+    };
+    text_response(request_id, render_html(&item.data))
+}
+
+/**
+* md_section_get: md_data の id の、見出しのパスの section (見出しの行と下位の見出しを含む) を表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn md_section_get_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct SectionParams {
+        id: i32,
+        path: Vec<String>,
+    }
+    let section_params = match parse_arguments::<SectionParams>(&params, &request_id) {
+        Ok(section_params) => section_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let Some(item) = super::mod_data::find_item(&conn, MD_TABLE, section_params.id).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", section_params.id));
+    };
+    let headings = headings(&item.data);
+    let heading = match find_section(&headings, &section_params.path) {
+        Ok(heading) => heading,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    let result = json!({
+        "id": item.id,
+        "etag": item.etag,
+        "path": heading.path,
+        "line": heading.line,
+        "content": &item.data[heading.start..heading.end],
+    });
+    text_response(request_id, result.to_string())
+}
+
+/**
+* md_section_replace: md_data の id の、見出しのパスの section を content に置き換える。
+* content は見出しの行を含む section 全体。etag を指定した場合は、一致する場合のみ更新する。
+*
+* @param
+*
+* @return
+*/
+pub async fn md_section_replace_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct ReplaceParams {
+        id: i32,
+        path: Vec<String>,
+        content: String,
+        etag: Option<String>,
+    }
+    let replace_params = match parse_arguments::<ReplaceParams>(&params, &request_id) {
+        Ok(replace_params) => replace_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    let Some(current) = super::mod_data::find_item(&conn, MD_TABLE, replace_params.id).await else {
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", replace_params.id));
+    };
+    if let Some(etag) = &replace_params.etag
+        && *etag != current.etag {
+        return super::mod_data::conflict_response(request_id, etag, Some(current));
+    }
+    let headings = headings(&current.data);
+    let heading = match find_section(&headings, &replace_params.path) {
+        Ok(heading) => heading,
+        Err(e) => return error_response(request_id, -32602, format!("Invalid parameters, {}", e)),
+    };
+    let data = replace_section(&current.data, heading, &replace_params.content);
+
+    super::mod_history::ensure_history(&conn).await.unwrap();
+    let tx = conn.transaction().await.unwrap();
+    // 読み込み後に他の更新があった場合は、更新しない
+    let sql = format!("UPDATE {} SET data = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2 AND data = ?3 AND updated_at = ?4", MD_TABLE);
+    let count = tx.execute(&sql, params![data.clone(), replace_params.id, current.data.clone(), current.updated_at.clone()])
+        .await.unwrap();
+    let updated = super::mod_data::find_item(&tx, MD_TABLE, replace_params.id).await;
+    if count == 0 {
+        tx.rollback().await.ok();
+        return super::mod_data::conflict_response(request_id, &current.etag, updated);
+    }
+    super::mod_history::record_history(&tx, MD_TABLE, replace_params.id as i64, super::mod_history::HistoryAction::Update, Some(&current.data), Some(&data)).await.unwrap();
+    tx.commit().await.unwrap();
+    super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::DataUpdate, MD_TABLE, replace_params.id as i64, Some(&data)).await;
+    text_response(request_id, format!("OK, etag={}", updated.map(|item| item.etag).unwrap_or_default()))
+}
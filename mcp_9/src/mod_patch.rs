@@ -0,0 +1,271 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// RFC 6902 JSON Patch の操作
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/**
+* RFC 7396 JSON Merge Patch を適用する。null の項目は削除する。
+*
+* @param target
+* @param patch
+*/
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Some(patch_object) = patch.as_object() else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target_object = target.as_object_mut().unwrap();
+    for (key, value) in patch_object {
+        if value.is_null() {
+            target_object.remove(key);
+        } else {
+            merge_patch(target_object.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/**
+* JSON Pointer (RFC 6901) を、親の pointer と最後の token に分ける。
+*/
+fn split_pointer(path: &str) -> Result<(String, String), String> {
+    if !path.starts_with('/') {
+        return Err(format!("invalid pointer: {}", path));
+    }
+    let index = path.rfind('/').unwrap();
+    let token = path[index + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((path[..index].to_string(), token))
+}
+
+fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    if allow_end && token == "-" {
+        return Ok(len);
+    }
+    let index: usize = token.parse().map_err(|_| format!("invalid array index: {}", token))?;
+    if index > len || (!allow_end && index == len) {
+        return Err(format!("array index out of range: {}", token));
+    }
+    Ok(index)
+}
+
+fn parent_mut<'a>(document: &'a mut Value, parent: &str, path: &str) -> Result<&'a mut Value, String> {
+    document.pointer_mut(parent).ok_or(format!("path not found: {}", path))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, token) = split_pointer(path)?;
+    match parent_mut(document, &parent, path)? {
+        Value::Object(object) => {
+            object.insert(token, value);
+        }
+        Value::Array(array) => {
+            let index = array_index(&token, array.len(), true)?;
+            array.insert(index, value);
+        }
+        _ => return Err(format!("path not found: {}", path)),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Ok(std::mem::take(document));
+    }
+    let (parent, token) = split_pointer(path)?;
+    match parent_mut(document, &parent, path)? {
+        Value::Object(object) => object.remove(&token).ok_or(format!("path not found: {}", path)),
+        Value::Array(array) => {
+            let index = array_index(&token, array.len(), false)?;
+            Ok(array.remove(index))
+        }
+        _ => Err(format!("path not found: {}", path)),
+    }
+}
+
+fn get(document: &Value, path: &str) -> Result<Value, String> {
+    document.pointer(path).cloned().ok_or(format!("path not found: {}", path))
+}
+
+/**
+* RFC 6902 JSON Patch を適用する。1つでも失敗した場合は、document を変更せずにエラーを返す。
+*
+* @param document
+* @param operations
+*
+* @return
+*/
+pub fn json_patch(document: &mut Value, operations: &[PatchOperation]) -> Result<(), String> {
+    let mut patched = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        let result = match operation {
+            PatchOperation::Add { path, value } => add(&mut patched, path, value.clone()),
+            PatchOperation::Remove { path } => remove(&mut patched, path).map(|_| ()),
+            PatchOperation::Replace { path, value } => {
+                get(&patched, path).and_then(|_| remove(&mut patched, path)).and_then(|_| add(&mut patched, path, value.clone()))
+            }
+            PatchOperation::Move { from, path } => {
+                if path.starts_with(&format!("{}/", from)) {
+                    Err(format!("cannot move {} into itself", from))
+                } else {
+                    remove(&mut patched, from).and_then(|value| add(&mut patched, path, value))
+                }
+            }
+            PatchOperation::Copy { from, path } => get(&patched, from).and_then(|value| add(&mut patched, path, value)),
+            PatchOperation::Test { path, value } => match get(&patched, path) {
+                Ok(current) if current == *value => Ok(()),
+                Ok(current) => Err(format!("test failed: {} is {}", path, current)),
+                Err(e) => Err(e),
+            },
+        };
+        result.map_err(|e| format!("patch[{}]: {}", index, e))?;
+    }
+    *document = patched;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn operations(value: Value) -> Vec<PatchOperation> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn merge_patch_removes_null_and_merges_objects() {
+        let mut target = json!({"a": 1, "b": {"c": 2, "d": 3}, "e": [1, 2]});
+        merge_patch(&mut target, &json!({"a": null, "b": {"c": 4}, "e": [3]}));
+        assert_eq!(target, json!({"b": {"c": 4, "d": 3}, "e": [3]}));
+    }
+
+    #[test]
+    fn merge_patch_replaces_non_object_target() {
+        let mut target = json!("text");
+        merge_patch(&mut target, &json!({"a": 1}));
+        assert_eq!(target, json!({"a": 1}));
+    }
+
+    #[test]
+    fn add_to_array_end_and_index() {
+        let mut document = json!({"tags": ["a", "c"]});
+        json_patch(&mut document, &operations(json!([
+            {"op": "add", "path": "/tags/-", "value": "d"},
+            {"op": "add", "path": "/tags/1", "value": "b"},
+        ]))).unwrap();
+        assert_eq!(document, json!({"tags": ["a", "b", "c", "d"]}));
+    }
+
+    #[test]
+    fn add_out_of_range_index_fails() {
+        let mut document = json!({"tags": ["a"]});
+        let error = json_patch(&mut document, &operations(json!([
+            {"op": "add", "path": "/tags/2", "value": "b"},
+        ]))).unwrap_err();
+        assert!(error.starts_with("patch[0]:"), "{}", error);
+    }
+
+    #[test]
+    fn remove_end_index_fails() {
+        let mut document = json!({"tags": ["a"]});
+        assert!(json_patch(&mut document, &operations(json!([
+            {"op": "remove", "path": "/tags/-"},
+        ]))).is_err());
+        assert_eq!(document, json!({"tags": ["a"]}));
+    }
+
+    #[test]
+    fn replace_requires_existing_path() {
+        let mut document = json!({"a": 1});
+        assert!(json_patch(&mut document, &operations(json!([
+            {"op": "replace", "path": "/b", "value": 2},
+        ]))).is_err());
+        json_patch(&mut document, &operations(json!([
+            {"op": "replace", "path": "/a", "value": 2},
+        ]))).unwrap();
+        assert_eq!(document, json!({"a": 2}));
+    }
+
+    #[test]
+    fn move_and_copy() {
+        let mut document = json!({"a": {"b": 1}, "list": [1, 2]});
+        json_patch(&mut document, &operations(json!([
+            {"op": "move", "from": "/a/b", "path": "/c"},
+            {"op": "copy", "from": "/list/0", "path": "/list/-"},
+        ]))).unwrap();
+        assert_eq!(document, json!({"a": {}, "c": 1, "list": [1, 2, 1]}));
+    }
+
+    #[test]
+    fn move_into_child_fails() {
+        let mut document = json!({"a": {"b": 1}});
+        let error = json_patch(&mut document, &operations(json!([
+            {"op": "move", "from": "/a", "path": "/a/b/c"},
+        ]))).unwrap_err();
+        assert!(error.contains("into itself"), "{}", error);
+        assert_eq!(document, json!({"a": {"b": 1}}));
+    }
+
+    #[test]
+    fn move_to_sibling_with_same_prefix() {
+        let mut document = json!({"a": 1});
+        json_patch(&mut document, &operations(json!([
+            {"op": "move", "from": "/a", "path": "/ab"},
+        ]))).unwrap();
+        assert_eq!(document, json!({"ab": 1}));
+    }
+
+    #[test]
+    fn escaped_pointer_tokens() {
+        let mut document = json!({});
+        json_patch(&mut document, &operations(json!([
+            {"op": "add", "path": "/a~1b", "value": 1},
+            {"op": "add", "path": "/c~0d", "value": 2},
+        ]))).unwrap();
+        assert_eq!(document, json!({"a/b": 1, "c~d": 2}));
+    }
+
+    #[test]
+    fn test_failure_leaves_document_unchanged() {
+        let mut document = json!({"status": "open", "count": 1});
+        let error = json_patch(&mut document, &operations(json!([
+            {"op": "replace", "path": "/count", "value": 2},
+            {"op": "test", "path": "/status", "value": "closed"},
+        ]))).unwrap_err();
+        assert!(error.starts_with("patch[1]: test failed"), "{}", error);
+        assert_eq!(document, json!({"status": "open", "count": 1}));
+    }
+
+    #[test]
+    fn root_path_replaces_document() {
+        let mut document = json!({"a": 1});
+        json_patch(&mut document, &operations(json!([
+            {"op": "add", "path": "", "value": [1]},
+        ]))).unwrap();
+        assert_eq!(document, json!([1]));
+    }
+
+    #[test]
+    fn invalid_pointer_fails() {
+        let mut document = json!({"a": 1});
+        assert!(json_patch(&mut document, &operations(json!([
+            {"op": "remove", "path": "a"},
+        ]))).is_err());
+    }
+}
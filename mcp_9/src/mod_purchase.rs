@@ -63,6 +63,7 @@ pub async fn purchase_handler(params: Value, request_id: Option<Value>) -> super
                             .execute(&sql, ())
                             .await
                             .unwrap();
+                        super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::PurchaseCreate, "item_price", conn.last_insert_rowid(), Some(&json_string_variable)).await;
 
                         let result = purchase(purchase_params.name, purchase_params.price);
                         return super::JsonRpcResponse {
@@ -205,8 +206,10 @@ pub async fn purchase_delete_handler(params: Value, request_id: Option<Value>) -
                             (),  // 引数なし
                         ).await.unwrap(); 
                         let mut count = 0;
+                        let mut deleted_data: Option<String> = None;
                         while let Some(row) = select_rows.next().await.unwrap() {
                             count += 1;
+                            deleted_data = row.get(1).ok();
                         }
                         if count == 0 {
                             return super::JsonRpcResponse {
@@ -225,6 +228,7 @@ pub async fn purchase_delete_handler(params: Value, request_id: Option<Value>) -
                             .execute(&sql, ())
                             .await
                             .unwrap();
+                        super::mod_webhook::emit(&conn, super::mod_webhook::WebhookEvent::PurchaseDelete, "item_price", id_value as i64, deleted_data.as_deref()).await;
 
                         let resp = format!("Complete delete, id={}", id_value);
                         return super::JsonRpcResponse {
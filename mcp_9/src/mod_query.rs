@@ -0,0 +1,478 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// data_list の JSON パスの条件。path は $.status , $.tags[0] の形式
+#[derive(Debug, Clone, Deserialize)]
+pub struct Filter {
+    pub path: String,
+    #[serde(default)]
+    pub op: FilterOp,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    #[default]
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    In,
+    Contains,
+    Exists,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// 並び順。path は JSON パス、または id , created_at , updated_at
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sort {
+    pub path: String,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// data_list の検索条件
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub filter: Vec<Filter>,
+    pub sort: Option<Sort>,
+    #[serde(default)]
+    pub fields: Vec<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+    pub cursor: Option<String>,
+}
+
+fn default_limit() -> i64 {
+    10
+}
+
+const SORT_COLUMNS: [&str; 3] = ["id", "created_at", "updated_at"];
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/**
+* JSON パスを分解する。$ から始まり、.key と [n] のみ使用できる。
+*
+* @param path
+*
+* @return
+*/
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = || format!("invalid path: {} (例: $.status , $.tags[0])", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(key.to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            segments.push(PathSegment::Index(after[..end].parse().map_err(|_| invalid())?));
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+/**
+* JSON パスを検証し、SQL の文字列リテラルにする。
+* collection_create の index と同じ式になるように、条件と並び順の JSON パスは bind しない。
+* parse_path で使える文字は英数字 , _ , - , . , [ , ] のみのため、引用符は含まれない。
+*
+* @param path
+*
+* @return
+*/
+pub fn path_literal(path: &str) -> Result<String, String> {
+    parse_path(path)?;
+    Ok(format!("'{}'", path))
+}
+
+/**
+* JSON パスの値を取り出す。
+*
+* @param value
+* @param path
+*
+* @return
+*/
+pub fn extract_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let segments = parse_path(path).ok()?;
+    segments.iter().try_fold(value, |current, segment| match segment {
+        PathSegment::Key(key) => current.get(key),
+        PathSegment::Index(index) => current.get(*index),
+    })
+}
+
+/**
+* data から fields の値のみ取り出す。key は $. を除いたパスとする。
+*
+* @param data
+* @param fields
+*
+* @return
+*/
+pub fn project_fields(data: &str, fields: &[String]) -> String {
+    let value: Value = serde_json::from_str(data).unwrap_or(Value::Null);
+    let mut projected = Map::new();
+    for field in fields {
+        let key = field.strip_prefix("$.").unwrap_or(field).to_string();
+        projected.insert(key, extract_path(&value, field).cloned().unwrap_or(Value::Null));
+    }
+    Value::Object(projected).to_string()
+}
+
+/**
+* JSON の値を、json_extract の結果と比較できる SQL の値にする。
+* true / false は json_extract と同じく 1 / 0 とする。
+*/
+fn sql_value(value: &Value) -> libsql::Value {
+    match value {
+        Value::Null => libsql::Value::Null,
+        Value::Bool(b) => libsql::Value::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => libsql::Value::Integer(i),
+            None => libsql::Value::Real(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(s) => libsql::Value::Text(s.clone()),
+        _ => libsql::Value::Text(value.to_string()),
+    }
+}
+
+fn json_value(value: &libsql::Value) -> Value {
+    match value {
+        libsql::Value::Null => Value::Null,
+        libsql::Value::Integer(i) => json!(i),
+        libsql::Value::Real(f) => json!(f),
+        libsql::Value::Text(s) => json!(s),
+        libsql::Value::Blob(b) => json!(b),
+    }
+}
+
+/// 組み立て中の SQL と、bind する値
+#[derive(Default)]
+struct SqlBuilder {
+    where_list: Vec<String>,
+    values: Vec<libsql::Value>,
+}
+
+impl SqlBuilder {
+    /// 値を追加し、placeholder (?n) を返す
+    fn bind(&mut self, value: libsql::Value) -> String {
+        self.values.push(value);
+        format!("?{}", self.values.len())
+    }
+}
+
+fn filter_sql(builder: &mut SqlBuilder, filter: &Filter) -> Result<String, String> {
+    let path = path_literal(&filter.path)?;
+    let expr = format!("json_extract(data, {})", path);
+    let compare = |builder: &mut SqlBuilder, op: &str| {
+        if filter.value.is_array() || filter.value.is_object() {
+            return Err(format!("{}: value must be a scalar", filter.path));
+        }
+        Ok(format!("{} {} {}", expr, op, builder.bind(sql_value(&filter.value))))
+    };
+    match filter.op {
+        FilterOp::Eq if filter.value.is_null() => Ok(format!("{} IS NULL", expr)),
+        FilterOp::Ne if filter.value.is_null() => Ok(format!("{} IS NOT NULL", expr)),
+        FilterOp::Eq => compare(builder, "="),
+        FilterOp::Ne => Ok(format!("({} IS NULL OR {})", expr, compare(builder, "<>")?)),
+        FilterOp::Gt => compare(builder, ">"),
+        FilterOp::Gte => compare(builder, ">="),
+        FilterOp::Lt => compare(builder, "<"),
+        FilterOp::Lte => compare(builder, "<="),
+        FilterOp::In => {
+            let values = filter.value.as_array().ok_or(format!("{}: in requires an array", filter.path))?;
+            if values.is_empty() {
+                return Ok("0".to_string());
+            }
+            let placeholders: Vec<String> = values.iter().map(|value| builder.bind(sql_value(value))).collect();
+            Ok(format!("{} IN ({})", expr, placeholders.join(", ")))
+        }
+        FilterOp::Contains => {
+            // 配列は要素の一致、文字列は部分一致
+            let value = builder.bind(sql_value(&filter.value));
+            Ok(format!("((json_type(data, {path}) = 'array' AND EXISTS (SELECT 1 FROM json_each(data, {path}) WHERE json_each.value = {value}))
+            OR (json_type(data, {path}) = 'text' AND instr({expr}, {value}) > 0))", path = path, value = value, expr = expr))
+        }
+        FilterOp::Exists => {
+            let exists = filter.value.as_bool().unwrap_or(true);
+            Ok(format!("json_type(data, {}) IS {}NULL", path, if exists { "NOT " } else { "" }))
+        }
+    }
+}
+
+/**
+* 並び順の式。id , created_at , updated_at は列、それ以外は JSON パスとする。
+*/
+fn sort_sql(sort: &Sort) -> Result<String, String> {
+    if SORT_COLUMNS.contains(&sort.path.as_str()) {
+        return Ok(sort.path.clone());
+    }
+    Ok(format!("json_extract(data, {})", path_literal(&sort.path)?))
+}
+
+/**
+* cursor の条件。cursor は前のページの最後の行の [並び順の値, id]。
+* 並び順の値が NULL の行は、昇順では先頭、降順では末尾とする (SQLite の並び順)。
+*/
+fn cursor_sql(builder: &mut SqlBuilder, sort_expr: &str, order: SortOrder, cursor: &str) -> Result<String, String> {
+    let invalid = || format!("invalid cursor: {}", cursor);
+    let parsed: Value = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    let (sort_value, id) = match parsed.as_array().map(|items| items.as_slice()) {
+        Some([sort_value, id]) => (sort_value.clone(), id.as_i64().ok_or_else(invalid)?),
+        _ => return Err(invalid()),
+    };
+    let id = builder.bind(libsql::Value::Integer(id));
+    let (op, id_op) = match order {
+        SortOrder::Asc => (">", ">"),
+        SortOrder::Desc => ("<", "<"),
+    };
+    if sort_value.is_null() {
+        return Ok(match order {
+            SortOrder::Asc => format!("(({e} IS NULL AND id {o} {id}) OR {e} IS NOT NULL)", e = sort_expr, o = id_op, id = id),
+            SortOrder::Desc => format!("({e} IS NULL AND id {o} {id})", e = sort_expr, o = id_op, id = id),
+        });
+    }
+    let value = builder.bind(sql_value(&sort_value));
+    let after = format!("({e} {op} {v} OR ({e} = {v} AND id {o} {id}))", e = sort_expr, op = op, v = value, o = id_op, id = id);
+    Ok(match order {
+        SortOrder::Asc => after,
+        SortOrder::Desc => format!("({} OR {} IS NULL)", after, sort_expr),
+    })
+}
+
+/// data_list の SQL。列は id , data , created_at , updated_at , 並び順の値
+pub struct ListSql {
+    pub sql: String,
+    pub values: Vec<libsql::Value>,
+    pub limit: i64,
+}
+
+/**
+* data_list の条件から SQLite の SELECT を作る。JSON パス以外の値はすべて bind する。
+* 次のページの有無を判定するため、limit + 1 件を取得する。
+*
+* @param table 登録済みの collection 名
+* @param query
+*
+* @return
+*/
+pub fn build_list_sql(table: &str, query: &ListQuery) -> Result<ListSql, String> {
+    let mut builder = SqlBuilder::default();
+    let limit = query.limit.clamp(1, MAX_LIMIT);
+    for field in &query.fields {
+        parse_path(field)?;
+    }
+    for filter in &query.filter {
+        let sql = filter_sql(&mut builder, filter)?;
+        builder.where_list.push(sql);
+    }
+    let sort = query.sort.clone().unwrap_or(Sort {
+        path: "created_at".to_string(),
+        order: SortOrder::Desc,
+    });
+    let sort_expr = sort_sql(&sort)?;
+    if let Some(cursor) = &query.cursor {
+        let sql = cursor_sql(&mut builder, &sort_expr, sort.order, cursor)?;
+        builder.where_list.push(sql);
+    }
+    let where_sql = if builder.where_list.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", builder.where_list.join(" AND "))
+    };
+    let direction = match sort.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    let limit_sql = builder.bind(libsql::Value::Integer(limit + 1));
+    let offset_sql = builder.bind(libsql::Value::Integer(query.offset.max(0)));
+    let sql = format!("SELECT id, data, created_at, updated_at, {sort}
+    FROM {table}
+    {where_sql}
+    ORDER BY {sort} {direction}, id {direction}
+    LIMIT {limit} OFFSET {offset}
+    ", sort = sort_expr, table = table, where_sql = where_sql, direction = direction,
+    limit = limit_sql, offset = offset_sql);
+    Ok(ListSql {
+        sql,
+        values: builder.values,
+        limit,
+    })
+}
+
+/**
+* 行の並び順の値と id から、次のページの cursor を作る。
+*
+* @param sort_value
+* @param id
+*
+* @return
+*/
+pub fn make_cursor(sort_value: &libsql::Value, id: i64) -> String {
+    json!([json_value(sort_value), id]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(value: Value) -> ListQuery {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn parse_path_keys_and_indexes() {
+        assert_eq!(parse_path("$").unwrap(), vec![]);
+        assert_eq!(parse_path("$.tags[0].name").unwrap(), vec![
+            PathSegment::Key("tags".to_string()),
+            PathSegment::Index(0),
+            PathSegment::Key("name".to_string()),
+        ]);
+        assert_eq!(parse_path("$.a_b-c[12]").unwrap(), vec![
+            PathSegment::Key("a_b-c".to_string()),
+            PathSegment::Index(12),
+        ]);
+    }
+
+    #[test]
+    fn parse_path_rejects_invalid() {
+        for path in ["status", "$.", "$..a", "$.a'b", "$.a b", "$[x]", "$[-1]", "$[0", "$.a]"] {
+            assert!(parse_path(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn extract_and_project() {
+        let value = json!({"status": "open", "tags": ["a", "b"]});
+        assert_eq!(extract_path(&value, "$.tags[1]"), Some(&json!("b")));
+        assert_eq!(extract_path(&value, "$.missing"), None);
+        assert_eq!(project_fields(&value.to_string(), &["$.status".to_string(), "$.none".to_string()]),
+            json!({"status": "open", "none": null}).to_string());
+    }
+
+    #[test]
+    fn build_list_sql_binds_values() {
+        let list_sql = build_list_sql("todo", &query(json!({
+            "filter": [
+                {"path": "$.status", "value": "open"},
+                {"path": "$.priority", "op": "in", "value": [1, 2]},
+            ],
+            "sort": {"path": "$.priority", "order": "asc"},
+            "limit": 500,
+        }))).unwrap();
+        assert!(list_sql.sql.contains("json_extract(data, '$.status') = ?1"), "{}", list_sql.sql);
+        assert!(list_sql.sql.contains("json_extract(data, '$.priority') IN (?2, ?3)"), "{}", list_sql.sql);
+        assert!(list_sql.sql.contains("ORDER BY json_extract(data, '$.priority') ASC, id ASC"), "{}", list_sql.sql);
+        assert_eq!(list_sql.limit, MAX_LIMIT);
+        assert_eq!(list_sql.values, vec![
+            libsql::Value::Text("open".to_string()),
+            libsql::Value::Integer(1),
+            libsql::Value::Integer(2),
+            libsql::Value::Integer(MAX_LIMIT + 1),
+            libsql::Value::Integer(0),
+        ]);
+    }
+
+    #[test]
+    fn build_list_sql_rejects_invalid_input() {
+        assert!(build_list_sql("todo", &query(json!({"filter": [{"path": "$.a' OR 1=1 --"}]}))).is_err());
+        assert!(build_list_sql("todo", &query(json!({"sort": {"path": "name"}}))).is_err());
+        assert!(build_list_sql("todo", &query(json!({"fields": ["status"]}))).is_err());
+        assert!(build_list_sql("todo", &query(json!({"filter": [{"path": "$.a", "op": "gt", "value": [1]}]}))).is_err());
+        assert!(build_list_sql("todo", &query(json!({"cursor": "[1]"}))).is_err());
+        assert!(build_list_sql("todo", &query(json!({"cursor": "[1, \"x\"]"}))).is_err());
+    }
+
+    #[test]
+    fn cursor_sql_null_value() {
+        let mut builder = SqlBuilder::default();
+        let sql = cursor_sql(&mut builder, "e", SortOrder::Asc, "[null, 5]").unwrap();
+        assert_eq!(sql, "((e IS NULL AND id > ?1) OR e IS NOT NULL)");
+        let mut builder = SqlBuilder::default();
+        let sql = cursor_sql(&mut builder, "e", SortOrder::Desc, "[null, 5]").unwrap();
+        assert_eq!(sql, "(e IS NULL AND id < ?1)");
+        let mut builder = SqlBuilder::default();
+        let sql = cursor_sql(&mut builder, "e", SortOrder::Desc, "[3, 5]").unwrap();
+        assert_eq!(sql, "((e < ?2 OR (e = ?2 AND id < ?1)) OR e IS NULL)");
+        assert_eq!(builder.values, vec![libsql::Value::Integer(5), libsql::Value::Integer(3)]);
+    }
+
+    /// cursor で全ページをたどった結果が、cursor を使わない並び順と一致すること
+    async fn assert_cursor_pages(order: &str) {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("CREATE TABLE todo (id INTEGER PRIMARY KEY, data TEXT, created_at TEXT, updated_at TEXT)", ()).await.unwrap();
+        let priorities = [json!(2), json!(null), json!(1), json!(2), json!(null), json!(3), json!(1), json!(null)];
+        for (index, priority) in priorities.iter().enumerate() {
+            let data = if priority.is_null() { json!({}) } else { json!({"priority": priority}) };
+            conn.execute("INSERT INTO todo (id, data) VALUES (?1, ?2)", libsql::params![index as i64 + 1, data.to_string()]).await.unwrap();
+        }
+
+        // (id, その行の cursor) の一覧
+        let read_rows = async |list_sql: ListSql| {
+            let mut rows = conn.query(&list_sql.sql, list_sql.values).await.unwrap();
+            let mut result: Vec<(i64, String)> = Vec::new();
+            while let Some(row) = rows.next().await.unwrap() {
+                let id: i64 = row.get(0).unwrap();
+                result.push((id, make_cursor(&row.get_value(4).unwrap(), id)));
+            }
+            result
+        };
+        let sort = json!({"path": "$.priority", "order": order});
+        let expected: Vec<i64> = read_rows(build_list_sql("todo", &query(json!({"sort": sort, "limit": 100}))).unwrap()).await
+            .into_iter().map(|(id, _)| id).collect();
+        assert_eq!(expected.len(), priorities.len());
+
+        let mut paged: Vec<i64> = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let list_sql = build_list_sql("todo", &query(json!({"sort": sort, "limit": 2, "cursor": cursor}))).unwrap();
+            let limit = list_sql.limit as usize;
+            let mut rows = read_rows(list_sql).await;
+            let has_next = rows.len() > limit;
+            rows.truncate(limit);
+            paged.extend(rows.iter().map(|(id, _)| *id));
+            if !has_next {
+                break;
+            }
+            cursor = rows.last().map(|(_, cursor)| cursor.clone());
+        }
+        assert_eq!(paged, expected, "order={}", order);
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_with_null_sort_values() {
+        assert_cursor_pages("asc").await;
+        assert_cursor_pages("desc").await;
+    }
+}
@@ -0,0 +1,592 @@
+use libsql::Connection;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use super::{connect, error_response, parse_arguments, text_response};
+
+// webhook の登録と、送信の記録の table
+pub const WEBHOOK_TABLE: &str = "mcp_webhook";
+pub const DELIVERY_TABLE: &str = "mcp_webhook_delivery";
+
+// 再送間隔: 30秒から倍々で、最大1時間。8回失敗した場合は failed とする
+const RETRY_BASE_SECS: u64 = 30;
+const RETRY_MAX_SECS: u64 = 3600;
+const RETRY_LOOP_SECS: u64 = 30;
+const MAX_ATTEMPTS: i64 = 8;
+
+// secret の最小の長さ
+const MIN_SECRET_LEN: usize = 16;
+
+// webhook の table を作成済みか
+static WEBHOOK_READY: AtomicBool = AtomicBool::new(false);
+
+// 送信を直列化し、同じ送信を重複して行わないようにする
+static DELIVERY_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 変更の種類
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "data.create")]
+    DataCreate,
+    #[serde(rename = "data.update")]
+    DataUpdate,
+    #[serde(rename = "data.delete")]
+    DataDelete,
+    #[serde(rename = "purchase.create")]
+    PurchaseCreate,
+    #[serde(rename = "purchase.delete")]
+    PurchaseDelete,
+}
+
+pub const WEBHOOK_EVENTS: [&str; 5] = ["data.create", "data.update", "data.delete", "purchase.create", "purchase.delete"];
+
+impl WebhookEvent {
+    fn as_str(&self) -> &'static str {
+        WEBHOOK_EVENTS[*self as usize]
+    }
+}
+
+/// 登録した webhook。events が空の場合はすべての変更 , collection が None の場合はすべての collection
+#[derive(Debug, Serialize)]
+struct Webhook {
+    id: i64,
+    url: String,
+    events: Vec<WebhookEvent>,
+    collection: Option<String>,
+    created_at: String,
+}
+
+/// 送信の記録。status は pending , delivered , failed
+#[derive(Debug, Serialize)]
+struct Delivery {
+    id: i64,
+    webhook_id: i64,
+    event: String,
+    status: String,
+    attempts: i64,
+    next_retry_at: i64,
+    last_status: Option<i64>,
+    last_error: Option<String>,
+    created_at: String,
+    delivered_at: Option<String>,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn backoff_secs(attempts: i64) -> i64 {
+    RETRY_BASE_SECS.saturating_mul(1u64 << attempts.saturating_sub(1).clamp(0, 16)).min(RETRY_MAX_SECS) as i64
+}
+
+/**
+* HMAC-SHA256 (RFC 2104)。
+*
+* @param key
+* @param message
+*
+* @return
+*/
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    let outer_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    let inner = Sha256::new().chain_update(&inner_pad).chain_update(message).finalize();
+    Sha256::new().chain_update(&outer_pad).chain_update(inner).finalize().into()
+}
+
+/**
+* 送信する payload の署名。"{timestamp}.{body}" の HMAC-SHA256 を16進数にする。
+*
+* @param secret
+* @param timestamp
+* @param body
+*
+* @return
+*/
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    hmac_sha256(secret.as_bytes(), format!("{}.{}", timestamp, body).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/**
+* 送信先はこのマシンの URL (localhost , 127.0.0.1 , ::1) のみとする。
+*
+* @param url
+*
+* @return
+*/
+fn validate_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(format!("url must be http or https: {}", url));
+    }
+    match parsed.host_str() {
+        Some("localhost" | "127.0.0.1" | "[::1]") => Ok(()),
+        _ => Err(format!("url must be a local URL (localhost , 127.0.0.1 , ::1): {}", url)),
+    }
+}
+
+/**
+* webhook の table が無い場合は作成する。
+*
+* @param conn
+*
+* @return
+*/
+async fn ensure_webhook(conn: &Connection) -> Result<(), String> {
+    if WEBHOOK_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let sql_list = [
+        format!("CREATE TABLE IF NOT EXISTS {} (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          url TEXT NOT NULL,
+          events TEXT NOT NULL,
+          collection TEXT,
+          secret TEXT NOT NULL,
+          created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )", WEBHOOK_TABLE),
+        format!("CREATE TABLE IF NOT EXISTS {} (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          webhook_id INTEGER NOT NULL,
+          event TEXT NOT NULL,
+          payload TEXT NOT NULL,
+          status TEXT NOT NULL DEFAULT 'pending',
+          attempts INTEGER NOT NULL DEFAULT 0,
+          next_retry_at INTEGER NOT NULL,
+          last_status INTEGER,
+          last_error TEXT,
+          created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+          delivered_at TIMESTAMP
+        )", DELIVERY_TABLE),
+        format!("CREATE INDEX IF NOT EXISTS idx_{table}_status ON {table} (status, next_retry_at)", table = DELIVERY_TABLE),
+    ];
+    for sql in &sql_list {
+        conn.execute(sql, ()).await.map_err(|e| e.to_string())?;
+    }
+    WEBHOOK_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/**
+* webhook の table があるか。webhook を登録していない場合は、table を作成せずに何もしない。
+*/
+async fn webhook_exists(conn: &Connection) -> Result<bool, String> {
+    if WEBHOOK_READY.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    let mut rows = conn.query("SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", params![WEBHOOK_TABLE])
+        .await.map_err(|e| e.to_string())?;
+    let count: i64 = match rows.next().await.map_err(|e| e.to_string())? {
+        Some(row) => row.get(0).map_err(|e| e.to_string())?,
+        None => 0,
+    };
+    if count > 0 {
+        WEBHOOK_READY.store(true, Ordering::Relaxed);
+    }
+    Ok(count > 0)
+}
+
+async fn load_webhooks(conn: &Connection) -> Result<Vec<Webhook>, String> {
+    let sql = format!("SELECT id, url, events, collection, created_at FROM {} ORDER BY id", WEBHOOK_TABLE);
+    let mut rows = conn.query(&sql, ()).await.map_err(|e| e.to_string())?;
+    let mut webhooks = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        let events: String = row.get(2).map_err(|e| e.to_string())?;
+        webhooks.push(Webhook {
+            id: row.get(0).map_err(|e| e.to_string())?,
+            url: row.get(1).map_err(|e| e.to_string())?,
+            events: serde_json::from_str(&events).map_err(|e| e.to_string())?,
+            collection: row.get(3).map_err(|e| e.to_string())?,
+            created_at: row.get(4).map_err(|e| e.to_string())?,
+        });
+    }
+    Ok(webhooks)
+}
+
+async fn enqueue(conn: &Connection, event: WebhookEvent, collection: &str, record_id: i64, data: Option<&str>) -> Result<usize, String> {
+    if !webhook_exists(conn).await? {
+        return Ok(0);
+    }
+    let targets: Vec<Webhook> = load_webhooks(conn).await?.into_iter()
+        .filter(|webhook| webhook.events.is_empty() || webhook.events.contains(&event))
+        .filter(|webhook| webhook.collection.as_deref().is_none_or(|name| name == collection))
+        .collect();
+    let now = now_secs();
+    let payload = json!({
+        "event": event.as_str(),
+        "collection": collection,
+        "id": record_id,
+        "data": data,
+        "occurred_at": now,
+    }).to_string();
+    let sql = format!("INSERT INTO {} (webhook_id, event, payload, next_retry_at) VALUES (?1, ?2, ?3, ?4)", DELIVERY_TABLE);
+    for webhook in &targets {
+        conn.execute(&sql, params![webhook.id, event.as_str(), payload.clone(), now]).await.map_err(|e| e.to_string())?;
+    }
+    Ok(targets.len())
+}
+
+/**
+* 変更を、一致する webhook の送信の記録に追加し、送信を始める。
+* data の変更の commit の後に呼び出す。webhook のエラーで変更を失敗にはしない。
+*
+* @param conn
+* @param event
+* @param collection
+* @param record_id
+* @param data 登録 , 更新は変更後 , 削除は削除したデータ
+*/
+pub async fn emit(conn: &Connection, event: WebhookEvent, collection: &str, record_id: i64, data: Option<&str>) {
+    match enqueue(conn, event, collection, record_id, data).await {
+        Ok(0) => {}
+        Ok(_) => spawn_delivery(),
+        Err(e) => eprintln!("webhook enqueue error: {}", e),
+    }
+}
+
+/**
+* 送信を background で始める。stdio の応答を送信の完了まで待たせない。
+*/
+fn spawn_delivery() {
+    tokio::spawn(async {
+        if let Err(e) = deliver_due(&connect().await).await {
+            eprintln!("webhook delivery error: {}", e);
+        }
+    });
+}
+
+/**
+* 1件を送信する。2xx 以外はエラーとする。
+*
+* @return 応答の status
+*/
+async fn post(url: &str, secret: &str, delivery_id: i64, event: &str, payload: &str) -> (Option<i64>, Result<(), String>) {
+    let timestamp = now_secs();
+    let client = reqwest::Client::new();
+    let request = client
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", event)
+        .header("X-Webhook-Delivery", delivery_id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", sign_payload(secret, timestamp, payload)))
+        .body(payload.to_string());
+    match request.send().await {
+        Ok(res) if res.status().is_success() => (Some(res.status().as_u16() as i64), Ok(())),
+        Ok(res) => (Some(res.status().as_u16() as i64), Err(format!("webhook request failed with status: {}", res.status()))),
+        Err(e) => (None, Err(format!("webhook request failed: {}", e))),
+    }
+}
+
+/**
+* 再送時刻を過ぎた pending の送信を行う。失敗した場合は再送時刻を延ばし、MAX_ATTEMPTS 回で failed とする。
+*
+* @param conn
+*
+* @return (送信件数, 失敗件数)
+*/
+async fn deliver_due(conn: &Connection) -> Result<(usize, usize), String> {
+    let _guard = DELIVERY_LOCK.lock().await;
+    if !webhook_exists(conn).await? {
+        return Ok((0, 0));
+    }
+    let sql = format!("SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret
+    FROM {} d JOIN {} w ON w.id = d.webhook_id
+    WHERE d.status = 'pending' AND d.next_retry_at <= ?1
+    ORDER BY d.id LIMIT 100
+    ", DELIVERY_TABLE, WEBHOOK_TABLE);
+    let mut rows = conn.query(&sql, params![now_secs()]).await.map_err(|e| e.to_string())?;
+    let mut due = Vec::new();
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        let id: i64 = row.get(0).map_err(|e| e.to_string())?;
+        let event: String = row.get(1).map_err(|e| e.to_string())?;
+        let payload: String = row.get(2).map_err(|e| e.to_string())?;
+        let attempts: i64 = row.get(3).map_err(|e| e.to_string())?;
+        let url: String = row.get(4).map_err(|e| e.to_string())?;
+        let secret: String = row.get(5).map_err(|e| e.to_string())?;
+        due.push((id, event, payload, attempts, url, secret));
+    }
+    let (mut sent, mut failed) = (0, 0);
+    for (id, event, payload, attempts, url, secret) in due {
+        let attempts = attempts + 1;
+        let (last_status, result) = post(&url, &secret, id, &event, &payload).await;
+        match result {
+            Ok(()) => {
+                sent += 1;
+                let sql = format!("UPDATE {} SET status = 'delivered', attempts = ?1, last_status = ?2, last_error = NULL, delivered_at = CURRENT_TIMESTAMP WHERE id = ?3", DELIVERY_TABLE);
+                conn.execute(&sql, params![attempts, last_status, id]).await.map_err(|e| e.to_string())?;
+            }
+            Err(e) => {
+                failed += 1;
+                let status = if attempts >= MAX_ATTEMPTS { "failed" } else { "pending" };
+                let sql = format!("UPDATE {} SET status = ?1, attempts = ?2, next_retry_at = ?3, last_status = ?4, last_error = ?5 WHERE id = ?6", DELIVERY_TABLE);
+                conn.execute(&sql, params![status, attempts, now_secs() + backoff_secs(attempts), last_status, e, id])
+                    .await.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok((sent, failed))
+}
+
+/**
+* 再送時刻を過ぎた送信を、定期的に行う。
+*/
+pub async fn retry_loop() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(RETRY_LOOP_SECS)).await;
+        if let Err(e) = deliver_due(&connect().await).await {
+            eprintln!("webhook retry error: {}", e);
+        }
+    }
+}
+
+/**
+* webhook_create: 変更を POST する webhook を登録する。
+*
+* @param
+*
+* @return
+*/
+pub async fn webhook_create_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct CreateParams {
+        url: String,
+        secret: String,
+        #[serde(default)]
+        events: Vec<WebhookEvent>,
+        collection: Option<String>,
+    }
+    let create_params = match parse_arguments::<CreateParams>(&params, &request_id) {
+        Ok(create_params) => create_params,
+        Err(response) => return response,
+    };
+    if let Err(e) = validate_url(&create_params.url) {
+        return error_response(request_id, -32602, format!("Invalid parameters, {}", e));
+    }
+    if create_params.secret.chars().count() < MIN_SECRET_LEN {
+        return error_response(request_id, -32602, format!("Invalid parameters, secret must be at least {} characters", MIN_SECRET_LEN));
+    }
+    let conn = connect().await;
+    if let Err(e) = ensure_webhook(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    let events = serde_json::to_string(&create_params.events).expect("JSON convert error");
+    let sql = format!("INSERT INTO {} (url, events, collection, secret) VALUES (?1, ?2, ?3, ?4) RETURNING id", WEBHOOK_TABLE);
+    let row = match conn.query(&sql, params![create_params.url, events, create_params.collection, create_params.secret]).await {
+        Ok(mut rows) => rows.next().await,
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    };
+    match row {
+        Ok(Some(row)) => text_response(request_id, format!("Complete create, id={}", row.get::<i64>(0).unwrap())),
+        Ok(None) => error_response(request_id, -32603, "Query error: insert failed".to_string()),
+        Err(e) => error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+}
+
+/**
+* webhook_list: 登録した webhook の一覧。secret は表示しない。
+*
+* @param
+*
+* @return
+*/
+pub async fn webhook_list_handler(_params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    let conn = connect().await;
+    let webhooks = match webhook_exists(&conn).await {
+        Ok(true) => load_webhooks(&conn).await,
+        Ok(false) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    };
+    match webhooks {
+        Ok(webhooks) => text_response(request_id, serde_json::to_string(&webhooks).expect("JSON convert error")),
+        Err(e) => error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+}
+
+/**
+* webhook_delete: webhook と、その送信の記録を削除する。
+*
+* @param
+*
+* @return
+*/
+pub async fn webhook_delete_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DeleteParams {
+        id: i64,
+    }
+    let delete_params = match parse_arguments::<DeleteParams>(&params, &request_id) {
+        Ok(delete_params) => delete_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    if let Err(e) = ensure_webhook(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    let tx = conn.transaction().await.unwrap();
+    let sql = format!("DELETE FROM {} WHERE id = ?1", WEBHOOK_TABLE);
+    let count = tx.execute(&sql, params![delete_params.id]).await.unwrap();
+    if count == 0 {
+        tx.rollback().await.ok();
+        return error_response(request_id, -32602, format!("Invalid parameters, id={}", delete_params.id));
+    }
+    let sql = format!("DELETE FROM {} WHERE webhook_id = ?1", DELIVERY_TABLE);
+    tx.execute(&sql, params![delete_params.id]).await.unwrap();
+    tx.commit().await.unwrap();
+    text_response(request_id, format!("Complete delete, id={}", delete_params.id))
+}
+
+/**
+* webhook_deliveries: 送信の記録を、新しい順に表示する。
+*
+* @param
+*
+* @return
+*/
+pub async fn webhook_deliveries_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct DeliveriesParams {
+        webhook_id: Option<i64>,
+        status: Option<String>,
+        #[serde(default = "default_limit")]
+        limit: i64,
+    }
+    fn default_limit() -> i64 {
+        20
+    }
+    let deliveries_params = match parse_arguments::<DeliveriesParams>(&params, &request_id) {
+        Ok(deliveries_params) => deliveries_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    match webhook_exists(&conn).await {
+        Ok(true) => {}
+        Ok(false) => return text_response(request_id, "[]".to_string()),
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    }
+    let sql = format!("SELECT id, webhook_id, event, status, attempts, next_retry_at, last_status, last_error, created_at, delivered_at
+    FROM {}
+    WHERE (?1 IS NULL OR webhook_id = ?1) AND (?2 IS NULL OR status = ?2)
+    ORDER BY id DESC LIMIT ?3
+    ", DELIVERY_TABLE);
+    let query_params = params![deliveries_params.webhook_id, deliveries_params.status, deliveries_params.limit.clamp(1, 100)];
+    let mut rows = match conn.query(&sql, query_params).await {
+        Ok(rows) => rows,
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    };
+    let mut deliveries = Vec::new();
+    while let Ok(Some(row)) = rows.next().await {
+        deliveries.push(Delivery {
+            id: row.get(0).unwrap(),
+            webhook_id: row.get(1).unwrap(),
+            event: row.get(2).unwrap(),
+            status: row.get(3).unwrap(),
+            attempts: row.get(4).unwrap(),
+            next_retry_at: row.get(5).unwrap(),
+            last_status: row.get(6).unwrap(),
+            last_error: row.get(7).unwrap(),
+            created_at: row.get(8).unwrap(),
+            delivered_at: row.get(9).unwrap(),
+        });
+    }
+    text_response(request_id, serde_json::to_string(&deliveries).expect("JSON convert error"))
+}
+
+/**
+* webhook_retry: pending , failed の送信を、再送時刻に関係なく送信する。
+* 送信は background で行い、結果は webhook_deliveries で確認する。
+*
+* @param
+*
+* @return
+*/
+pub async fn webhook_retry_handler(params: Value, request_id: Option<Value>) -> super::JsonRpcResponse
+{
+    #[derive(Debug, Deserialize)]
+    struct RetryParams {
+        delivery_id: Option<i64>,
+    }
+    let retry_params = match parse_arguments::<RetryParams>(&params, &request_id) {
+        Ok(retry_params) => retry_params,
+        Err(response) => return response,
+    };
+    let conn = connect().await;
+    if let Err(e) = ensure_webhook(&conn).await {
+        return error_response(request_id, -32603, format!("Query error: {}", e));
+    }
+    // failed は attempts を戻して、再び MAX_ATTEMPTS 回まで再送する
+    let sql = format!("UPDATE {} SET status = 'pending', next_retry_at = ?1,
+      attempts = CASE WHEN status = 'failed' THEN 0 ELSE attempts END
+    WHERE status IN ('pending', 'failed') AND (?2 IS NULL OR id = ?2)
+    ", DELIVERY_TABLE);
+    let count = match conn.execute(&sql, params![now_secs(), retry_params.delivery_id]).await {
+        Ok(count) => count,
+        Err(e) => return error_response(request_id, -32603, format!("Query error: {}", e)),
+    };
+    if count == 0 && let Some(delivery_id) = retry_params.delivery_id {
+        return error_response(request_id, -32602, format!("Invalid parameters, delivery_id={} is not pending or failed", delivery_id));
+    }
+    if count > 0 {
+        spawn_delivery();
+    }
+    text_response(request_id, format!("Complete retry, queued={}", count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn hmac_sha256_rfc4231_test_vectors() {
+        // RFC 4231 4.2 Test Case 1
+        assert_eq!(hex(&hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7");
+        // RFC 4231 4.3 Test Case 2
+        assert_eq!(hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        // RFC 4231 4.7 Test Case 6 (block より長い key)
+        assert_eq!(hex(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    }
+
+    #[test]
+    fn sign_payload_signs_timestamp_and_body() {
+        assert_eq!(sign_payload("change-me-0123456789", 1700000000, r#"{"id":1}"#),
+            "a96f90e6d0e23cd92f06931de8a4de6f8eb6c7aa150d1ee2494afa60e4aabd33");
+        assert_ne!(sign_payload("change-me-0123456789", 1700000001, r#"{"id":1}"#),
+            sign_payload("change-me-0123456789", 1700000000, r#"{"id":1}"#));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        assert_eq!(backoff_secs(0), 30);
+        assert_eq!(backoff_secs(1), 30);
+        assert_eq!(backoff_secs(2), 60);
+        assert_eq!(backoff_secs(3), 120);
+        assert_eq!(backoff_secs(7), 1920);
+        assert_eq!(backoff_secs(8), 3600);
+        assert_eq!(backoff_secs(100), 3600);
+    }
+}